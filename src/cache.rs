@@ -0,0 +1,202 @@
+//! On-disk, content-hashed cache for LLM calls that `chunker::explain_code`
+//! would otherwise repeat on every run of the same file. Keyed by a hash of
+//! whatever can change the answer — the exact input text, which
+//! provider/model answered it, and `PROMPT_VERSION` — so editing one
+//! function in a large file only costs an LLM call for that function's
+//! chunk (and, transitively, for the synthesis step that reads it).
+
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunker::FollowUpQuestion;
+use crate::config;
+
+/// Bump whenever `chunker`'s prompts change meaningfully enough that a
+/// previously cached answer could no longer be trusted.
+const PROMPT_VERSION: u32 = 1;
+
+/// How long a cached tool-help entry is trusted before it's evicted as
+/// stale, even if its key (tool/subcommand/version) is never looked up
+/// again — e.g. a tool that got uninstalled.
+const TOOL_HELP_TTL_SECS: u64 = 30 * 24 * 3600;
+
+/// Cap on the number of cached tool-help entries before the oldest get
+/// evicted to make room, so a machine that churns through many one-off
+/// CLIs doesn't grow this directory unbounded.
+const TOOL_HELP_CACHE_CAP: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChunkEntry {
+    explanation: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SynthesisEntry {
+    summary: String,
+    questions: Vec<FollowUpQuestion>,
+}
+
+fn cache_dir() -> PathBuf {
+    config::config_dir().join("cache").join("chunks")
+}
+
+fn hash_key(parts: &[&str]) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    PROMPT_VERSION.hash(&mut hasher);
+    for part in parts {
+        part.hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+fn entry_path(kind: &str, key: &str) -> PathBuf {
+    cache_dir().join(format!("{}-{}.json", kind, key))
+}
+
+fn read_entry<T: for<'de> Deserialize<'de>>(kind: &str, key: &str) -> Option<T> {
+    let raw = fs::read_to_string(entry_path(kind, key)).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+/// Best-effort write — a cache miss on write just means we call the LLM
+/// again next time, so failures (read-only fs, disk full) are swallowed
+/// rather than surfaced.
+fn write_entry<T: Serialize>(kind: &str, key: &str, entry: &T) {
+    if fs::create_dir_all(cache_dir()).is_err() {
+        return;
+    }
+    if let Ok(raw) = serde_json::to_string(entry) {
+        let _ = fs::write(entry_path(kind, key), raw);
+    }
+}
+
+/// Look up a cached explanation for a chunk with this exact content,
+/// context prefix, and provider/model.
+pub fn lookup_chunk(content: &str, context_prefix: &str, provider_name: &str, model: &str) -> Option<String> {
+    let key = hash_key(&[provider_name, model, context_prefix, content]);
+    read_entry::<ChunkEntry>("chunk", &key).map(|e| e.explanation)
+}
+
+/// Cache `explanation` for a chunk with this exact content, context prefix,
+/// and provider/model.
+pub fn store_chunk(content: &str, context_prefix: &str, provider_name: &str, model: &str, explanation: &str) {
+    let key = hash_key(&[provider_name, model, context_prefix, content]);
+    write_entry("chunk", &key, &ChunkEntry { explanation: explanation.to_string() });
+}
+
+/// Look up a cached synthesis (overall summary + follow-up questions) for
+/// this exact combined per-chunk explanation text. Since `combined` is built
+/// from the (possibly cached) per-chunk explanations, this naturally only
+/// misses when at least one of them actually changed.
+pub fn lookup_synthesis(combined: &str, provider_name: &str, model: &str) -> Option<(String, Vec<FollowUpQuestion>)> {
+    let key = hash_key(&[provider_name, model, combined]);
+    read_entry::<SynthesisEntry>("synthesis", &key).map(|e| (e.summary, e.questions))
+}
+
+/// Cache a synthesis result for this exact combined per-chunk explanation text.
+pub fn store_synthesis(combined: &str, provider_name: &str, model: &str, summary: &str, questions: &[FollowUpQuestion]) {
+    let key = hash_key(&[provider_name, model, combined]);
+    write_entry(
+        "synthesis",
+        &key,
+        &SynthesisEntry {
+            summary: summary.to_string(),
+            questions: questions.to_vec(),
+        },
+    );
+}
+
+// ─── Tool help ──────────────────────────────────────────────────────────────
+//
+// Keyed by (tool, subcommand, tool version) so an upgraded tool naturally
+// misses the cache instead of serving stale flags — like the gradle plugin's
+// `.gradletasknamecache`, which only regenerates when the build file changes.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ToolHelpEntry {
+    version: String,
+    help_text: String,
+    cached_at_unix: u64,
+}
+
+fn tool_help_dir() -> PathBuf {
+    config::config_dir().join("cache").join("tools")
+}
+
+fn tool_help_path(tool: &str, subcommand: Option<&str>) -> PathBuf {
+    let key = hash_key(&[tool, subcommand.unwrap_or("")]);
+    tool_help_dir().join(format!("{}.json", key))
+}
+
+/// Look up cached help text for `tool`/`subcommand`, reusing it only if
+/// `current_version` (from `tool --version`) matches what was cached —
+/// an upgraded tool regenerates instead of serving stale flags.
+pub fn lookup_tool_help(tool: &str, subcommand: Option<&str>, current_version: &str) -> Option<String> {
+    let raw = fs::read_to_string(tool_help_path(tool, subcommand)).ok()?;
+    let entry: ToolHelpEntry = serde_json::from_str(&raw).ok()?;
+    if entry.version != current_version {
+        return None;
+    }
+    Some(entry.help_text)
+}
+
+/// Cache `help_text` for `tool`/`subcommand` at `version`.
+pub fn store_tool_help(tool: &str, subcommand: Option<&str>, version: &str, help_text: &str) {
+    if fs::create_dir_all(tool_help_dir()).is_err() {
+        return;
+    }
+    let entry = ToolHelpEntry {
+        version: version.to_string(),
+        help_text: help_text.to_string(),
+        cached_at_unix: unix_now(),
+    };
+    if let Ok(raw) = serde_json::to_string(&entry) {
+        let _ = fs::write(tool_help_path(tool, subcommand), raw);
+    }
+}
+
+/// Evicts tool-help entries older than `TOOL_HELP_TTL_SECS` (e.g. for tools
+/// that have since been uninstalled), then — if still over
+/// `TOOL_HELP_CACHE_CAP` — evicts the oldest remaining entries until back
+/// under the cap.
+pub fn prune_tool_help() {
+    let dir = tool_help_dir();
+    let Ok(entries) = fs::read_dir(&dir) else { return };
+
+    let now = unix_now();
+    let mut alive = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(raw) = fs::read_to_string(&path) else { continue };
+        let Ok(parsed) = serde_json::from_str::<ToolHelpEntry>(&raw) else {
+            let _ = fs::remove_file(&path);
+            continue;
+        };
+
+        if now.saturating_sub(parsed.cached_at_unix) > TOOL_HELP_TTL_SECS {
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+
+        alive.push((path, parsed.cached_at_unix));
+    }
+
+    if alive.len() > TOOL_HELP_CACHE_CAP {
+        alive.sort_by_key(|(_, cached_at)| *cached_at);
+        for (path, _) in alive.iter().take(alive.len() - TOOL_HELP_CACHE_CAP) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,15 +1,33 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::thread;
 use std::time::Instant;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-use crate::llm::{self, Provider};
+use crate::cache;
+use crate::llm::{self, Provider, Usage};
 
-/// Maximum lines per chunk for LLM processing
+/// Maximum lines per chunk for LLM processing when we have no token budget
+/// to size against (no provider context window given) — the long-standing
+/// fixed fallback.
 const MAX_CHUNK_LINES: usize = 200;
 /// Context overlap — carry last N lines from previous chunk for boundary continuity
 const CONTEXT_OVERLAP_LINES: usize = 10;
 /// Max tokens for chunk analysis
 const CHUNK_MAX_TOKENS: u32 = 4096;
+/// Fraction of a provider's context window a single chunk is allowed to grow
+/// into, leaving the rest for the system prompt, `CHUNK_MAX_TOKENS` of
+/// headroom for the reply, and some margin for the model's own reasoning.
+const CONTEXT_WINDOW_BUDGET_FRACTION: f64 = 0.5;
+/// Never size a chunk below this many lines, even on a tiny context window —
+/// a chunk this small is still useful and avoids pathological one-line chunks.
+const MIN_CHUNK_LINES: usize = 20;
+/// Rough chars-per-token ratio used when no BPE encoder is available for a
+/// provider (e.g. local Ollama models) — the same ballpark most BPE
+/// tokenizers land in for English-ish source code.
+const CHARS_PER_TOKEN_FALLBACK: f64 = 4.0;
 /// Max tokens for synthesis step
 const SYNTHESIS_MAX_TOKENS: u32 = 4096;
 /// Max tokens for follow-up questions
@@ -22,29 +40,324 @@ pub struct CodeChunk {
     pub end_line: usize,
     pub content: String,
     pub context_prefix: String,
+    /// Human-readable description of the syntactic boundary this chunk
+    /// covers (e.g. "function", "impl block", "3 items") when it was
+    /// produced by the tree-sitter-backed syntax-aware chunker in
+    /// `chunk_code_syntax_aware`. `None` for the line-heuristic fallback,
+    /// which has no parse tree to draw a label from.
+    pub node_kind: Option<String>,
 }
 
 /// Result of explaining a single chunk
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ChunkExplanation {
     pub start_line: usize,
     pub end_line: usize,
     pub explanation: String,
 }
 
+/// The dimension a follow-up question probes — kept as a closed enum rather
+/// than free text so downstream tooling (and the JSON output mode) can group
+/// or filter questions without parsing English sentences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FollowUpCategory {
+    Architecture,
+    Testing,
+    Security,
+    Performance,
+    Maintainability,
+}
+
+impl std::fmt::Display for FollowUpCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FollowUpCategory::Architecture => "Architecture",
+            FollowUpCategory::Testing => "Testing",
+            FollowUpCategory::Security => "Security",
+            FollowUpCategory::Performance => "Performance",
+            FollowUpCategory::Maintainability => "Maintainability",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One follow-up question, tagged with the dimension it probes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowUpQuestion {
+    pub category: FollowUpCategory,
+    pub question: String,
+}
+
 /// Full explanation result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ExplainResult {
     pub total_lines: usize,
     pub total_chunks: usize,
     pub chunk_explanations: Vec<ChunkExplanation>,
     pub overall_summary: String,
-    pub follow_up_questions: Vec<String>,
+    pub follow_up_questions: Vec<FollowUpQuestion>,
+    #[serde(with = "duration_secs_f64")]
     pub elapsed: std::time::Duration,
+    /// Token usage accumulated across every chunk and synthesis call in this
+    /// run (all-zero for providers whose API doesn't report it).
+    pub usage: Usage,
+    /// Name of the provider the run used — needed alongside `usage` to look
+    /// up per-model pricing in `estimate_cost_usd`.
+    pub provider_name: String,
+}
+
+/// Serializes a `Duration` as seconds (fractional) instead of serde's
+/// default `{secs, nanos}` struct — friendlier for `--json` consumers.
+mod duration_secs_f64 {
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(d: &std::time::Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_f64(d.as_secs_f64())
+    }
+}
+
+/// Render `result` as a single JSON object — machine-readable counterpart to
+/// `ui::display_explanation`, for scripting and editor integrations that
+/// want structured data instead of markdown.
+pub fn to_json(result: &ExplainResult) -> Result<String> {
+    Ok(serde_json::to_string_pretty(result)?)
+}
+
+/// Per-million-token USD pricing for providers whose API surfaces real
+/// token usage. Providers not listed (e.g. local models via Ollama) always
+/// report zero usage, so an absent price is never misleading.
+fn price_per_million_tokens(provider_name: &str) -> Option<(f64, f64)> {
+    match provider_name {
+        "claude" => Some((3.0, 15.0)),
+        _ => None,
+    }
+}
+
+/// Estimate the USD cost of `usage` tokens on `provider_name`, using a small
+/// built-in price table. Returns 0.0 for providers we have no pricing for.
+pub fn estimate_cost_usd(provider_name: &str, usage: Usage) -> f64 {
+    let Some((input_price, output_price)) = price_per_million_tokens(provider_name) else {
+        return 0.0;
+    };
+    (usage.input_tokens as f64 / 1_000_000.0) * input_price
+        + (usage.output_tokens as f64 / 1_000_000.0) * output_price
+}
+
+/// Count tokens in `text` the way `provider_name` would, using a BPE encoder
+/// when we have one for that provider, falling back to a chars-per-token
+/// estimate otherwise. Good enough for sizing chunks — it doesn't need to be
+/// exact, just consistent enough that we don't blow the context window.
+fn estimate_token_count(text: &str, provider_name: &str) -> usize {
+    match bpe_for_provider(provider_name) {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => (text.len() as f64 / CHARS_PER_TOKEN_FALLBACK).ceil() as usize,
+    }
+}
+
+/// The BPE encoding a provider's models are known to use, if we have one
+/// bundled. Claude doesn't publish its exact tokenizer, but `cl100k_base` is
+/// close enough in token density for sizing purposes; OpenAI's newer models
+/// use `o200k_base`. Providers with no known encoding (local Ollama models,
+/// unrecognized OpenAI-compatible backends) fall back to the chars-per-token
+/// heuristic instead.
+fn bpe_for_provider(provider_name: &str) -> Option<tiktoken_rs::CoreBPE> {
+    match provider_name {
+        "claude" => tiktoken_rs::cl100k_base().ok(),
+        "openai" => tiktoken_rs::o200k_base().ok(),
+        _ => None,
+    }
+}
+
+/// Work out how many lines a chunk can grow to for `provider_name` given its
+/// `context_window` (total tokens, prompt + reply). A chunk may fill
+/// `CONTEXT_WINDOW_BUDGET_FRACTION` of the window minus `CHUNK_MAX_TOKENS`
+/// reserved for the reply; that token budget is then converted to a line
+/// count using `code`'s own measured token density.
+///
+/// Returns the fixed `MAX_CHUNK_LINES` fallback when `context_window` is
+/// `None` — i.e. for providers that don't advertise one.
+fn effective_max_chunk_lines(code: &str, provider_name: &str, context_window: Option<u64>) -> usize {
+    let Some(context_window) = context_window else {
+        return MAX_CHUNK_LINES;
+    };
+
+    let total_lines = code.lines().count().max(1);
+    let total_tokens = estimate_token_count(code, provider_name).max(1);
+    let tokens_per_line = total_tokens as f64 / total_lines as f64;
+
+    let usable_tokens = (context_window as f64 * CONTEXT_WINDOW_BUDGET_FRACTION)
+        - CHUNK_MAX_TOKENS as f64;
+    if usable_tokens <= 0.0 {
+        return MIN_CHUNK_LINES;
+    }
+
+    ((usable_tokens / tokens_per_line) as usize).max(MIN_CHUNK_LINES)
 }
 
 /// Split code into logical chunks with overlap for boundary continuity.
-pub fn chunk_code(code: &str) -> Vec<CodeChunk> {
+///
+/// When `lang_hint` (a file extension or language name) matches a grammar we
+/// bundle via tree-sitter, chunk boundaries are drawn on real top-level item
+/// edges (function/struct/impl/class/module) so a chunk never cuts a
+/// definition in half. Falls back to the line-count heuristic when there's
+/// no hint, no matching grammar, or the source fails to parse cleanly.
+///
+/// Sizes chunks against the fixed `MAX_CHUNK_LINES` fallback; use
+/// `chunk_code_with_budget` to size against a provider's actual context
+/// window instead.
+pub fn chunk_code(code: &str, lang_hint: Option<&str>) -> Vec<CodeChunk> {
+    chunk_code_with_budget(code, lang_hint, "", None)
+}
+
+/// Like `chunk_code`, but grows chunks to fit as much of `context_window` (the
+/// provider's advertised total context length, in tokens) as
+/// `CONTEXT_WINDOW_BUDGET_FRACTION` allows, estimating token density with
+/// `provider_name`'s tokenizer. Pass `None` for `context_window` — e.g. a
+/// provider that doesn't advertise one — to get the same fixed-size
+/// behavior as `chunk_code`.
+pub fn chunk_code_with_budget(
+    code: &str,
+    lang_hint: Option<&str>,
+    provider_name: &str,
+    context_window: Option<u64>,
+) -> Vec<CodeChunk> {
+    let max_lines = effective_max_chunk_lines(code, provider_name, context_window);
+    if let Some(hint) = lang_hint {
+        if let Some(chunks) = chunk_code_syntax_aware(code, hint, max_lines) {
+            return chunks;
+        }
+    }
+    chunk_code_line_heuristic(code, 0, max_lines)
+}
+
+/// Map a file extension or language name to its tree-sitter grammar.
+fn language_for_hint(hint: &str) -> Option<tree_sitter::Language> {
+    match hint.trim_start_matches('.').to_lowercase().as_str() {
+        "rs" | "rust" => Some(tree_sitter_rust::LANGUAGE.into()),
+        "py" | "pyi" | "python" => Some(tree_sitter_python::LANGUAGE.into()),
+        "js" | "jsx" | "mjs" | "cjs" | "javascript" => Some(tree_sitter_javascript::LANGUAGE.into()),
+        "ts" | "mts" | "typescript" => Some(tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()),
+        "tsx" => Some(tree_sitter_typescript::LANGUAGE_TSX.into()),
+        "go" | "golang" => Some(tree_sitter_go::LANGUAGE.into()),
+        _ => None,
+    }
+}
+
+/// Translate a tree-sitter node kind (e.g. `function_item`, `class_declaration`)
+/// into the short label the system prompt shows the LLM for this chunk.
+fn describe_node_kind(kind: &str) -> String {
+    match kind {
+        "function_item" | "function_definition" | "function_declaration" | "method_definition" => {
+            "function".to_string()
+        }
+        "impl_item" => "impl block".to_string(),
+        "struct_item" => "struct".to_string(),
+        "enum_item" => "enum".to_string(),
+        "trait_item" => "trait".to_string(),
+        "class_declaration" | "class_definition" => "class".to_string(),
+        "mod_item" => "module".to_string(),
+        "interface_declaration" => "interface".to_string(),
+        other => other.replace('_', " "),
+    }
+}
+
+/// Syntax-aware chunker: parse `code` once with the grammar for `lang_hint`,
+/// then greedily pack whole top-level named nodes into chunks until adding
+/// the next node would exceed `max_lines`. A single node that alone exceeds
+/// the budget is handed to the line-heuristic splitter restricted to that
+/// node's own byte range, so it never silently overflows.
+///
+/// Returns `None` (letting the caller fall back) when there's no grammar for
+/// `lang_hint`, the source fails to parse, or there isn't enough top-level
+/// structure to chunk meaningfully.
+fn chunk_code_syntax_aware(code: &str, lang_hint: &str, max_lines: usize) -> Option<Vec<CodeChunk>> {
+    let language = language_for_hint(lang_hint)?;
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(code, None)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return None;
+    }
+
+    let mut cursor = root.walk();
+    let top_level: Vec<tree_sitter::Node> = root.named_children(&mut cursor).collect();
+    if top_level.len() < 2 {
+        return None;
+    }
+
+    let line_of = |byte: usize| code[..byte].matches('\n').count() + 1;
+
+    let mut groups: Vec<Vec<tree_sitter::Node>> = Vec::new();
+    let mut current: Vec<tree_sitter::Node> = Vec::new();
+    let mut current_lines = 0usize;
+
+    for node in top_level {
+        let node_lines = line_of(node.end_byte()) - line_of(node.start_byte()) + 1;
+        if !current.is_empty() && current_lines + node_lines > max_lines {
+            groups.push(std::mem::take(&mut current));
+            current_lines = 0;
+        }
+        current_lines += node_lines;
+        current.push(node);
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+
+    let mut chunks = Vec::new();
+    for group in &groups {
+        let start_byte = group.first().unwrap().start_byte();
+        let end_byte = group.last().unwrap().end_byte();
+        let start_line = line_of(start_byte);
+        let end_line = line_of(end_byte);
+
+        if group.len() == 1 && end_line - start_line + 1 > max_lines {
+            let kind = describe_node_kind(group[0].kind());
+            for mut sub in chunk_code_line_heuristic(&code[start_byte..end_byte], start_line - 1, max_lines) {
+                sub.node_kind = Some(kind.clone());
+                chunks.push(sub);
+            }
+            continue;
+        }
+
+        let context_prefix = if !chunks.is_empty() {
+            let ctx_start = start_line.saturating_sub(CONTEXT_OVERLAP_LINES + 1);
+            let ctx_lines: Vec<&str> = code.lines().skip(ctx_start).take(start_line - 1 - ctx_start).collect();
+            format!(
+                "// [context: preceding lines {}-{} shown for continuity]\n{}\n// [chunk starts here]\n",
+                ctx_start + 1,
+                start_line - 1,
+                ctx_lines.join("\n")
+            )
+        } else {
+            String::new()
+        };
+
+        let node_kind = if group.len() == 1 {
+            describe_node_kind(group[0].kind())
+        } else {
+            format!("{} items", group.len())
+        };
+
+        chunks.push(CodeChunk {
+            start_line,
+            end_line,
+            content: code[start_byte..end_byte].to_string(),
+            context_prefix,
+            node_kind: Some(node_kind),
+        });
+    }
+
+    Some(chunks)
+}
+
+/// Naive line-count chunker: split `code` every `max_lines` lines, preferring
+/// to break on a blank line, a closing brace, or the start of a new
+/// definition. `line_offset` lets callers number a sub-range of a larger
+/// file correctly (used by `chunk_code_syntax_aware`'s oversized-node
+/// fallback); pass `0` when `code` starts at line 1.
+fn chunk_code_line_heuristic(code: &str, line_offset: usize, max_lines: usize) -> Vec<CodeChunk> {
     let lines: Vec<&str> = code.lines().collect();
     let total = lines.len();
 
@@ -52,12 +365,13 @@ pub fn chunk_code(code: &str) -> Vec<CodeChunk> {
         return vec![];
     }
 
-    if total <= MAX_CHUNK_LINES {
+    if total <= max_lines {
         return vec![CodeChunk {
-            start_line: 1,
-            end_line: total,
+            start_line: line_offset + 1,
+            end_line: line_offset + total,
             content: code.to_string(),
             context_prefix: String::new(),
+            node_kind: None,
         }];
     }
 
@@ -65,7 +379,7 @@ pub fn chunk_code(code: &str) -> Vec<CodeChunk> {
     let mut start = 0;
 
     while start < total {
-        let mut end = (start + MAX_CHUNK_LINES).min(total);
+        let mut end = (start + max_lines).min(total);
 
         if end < total {
             let search_start = if end > 30 { end - 30 } else { start };
@@ -90,7 +404,7 @@ pub fn chunk_code(code: &str) -> Vec<CodeChunk> {
         }
 
         if end <= start {
-            end = (start + MAX_CHUNK_LINES).min(total);
+            end = (start + max_lines).min(total);
         }
 
         let context_prefix = if !chunks.is_empty() {
@@ -105,10 +419,11 @@ pub fn chunk_code(code: &str) -> Vec<CodeChunk> {
         };
 
         chunks.push(CodeChunk {
-            start_line: start + 1,
-            end_line: end,
+            start_line: line_offset + start + 1,
+            end_line: line_offset + end,
             content: lines[start..end].join("\n"),
             context_prefix,
+            node_kind: None,
         });
         start = end;
     }
@@ -147,102 +462,252 @@ fn is_definition_start(line: &str) -> bool {
     prefixes.iter().any(|p| line.starts_with(p))
 }
 
+/// Default cap on in-flight chunk analyses when the caller doesn't pass an
+/// explicit `max_concurrency` — one per available core, but never so many
+/// that a huge machine hammers the provider's rate limits for no benefit.
+fn default_max_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(8)
+}
+
+/// Build the system/user messages for chunk `i` of `total_chunks`. Shared by
+/// the concurrent dispatcher in `analyze_chunks` so every worker constructs
+/// prompts identically to the old sequential loop.
+fn build_chunk_messages(chunk: &CodeChunk, i: usize, total_chunks: usize, role_prompt: Option<&str>) -> Vec<llm::Message> {
+    // The system prompt is identical across every chunk call in this run
+    // (chunk number/line range live in the user prompt instead), so
+    // Claude's prompt cache can serve it from cache after the first hit.
+    let mut system_prompt = build_chunk_system_prompt(total_chunks);
+    if let Some(rp) = role_prompt {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(rp);
+    }
+
+    let mut chunk_header = if total_chunks > 1 {
+        format!("Chunk {} of {}.\n", i + 1, total_chunks)
+    } else {
+        String::new()
+    };
+    if let Some(kind) = &chunk.node_kind {
+        chunk_header.push_str(&format!("This chunk is exactly: {}.\n", kind));
+    }
+
+    let user_prompt = if chunk.context_prefix.is_empty() {
+        format!(
+            "{}Lines {}-{} ({} lines):\n\n```\n{}\n```",
+            chunk_header,
+            chunk.start_line,
+            chunk.end_line,
+            chunk.end_line - chunk.start_line + 1,
+            chunk.content
+        )
+    } else {
+        format!(
+            "{}{}\nLines {}-{} ({} lines):\n\n```\n{}\n```",
+            chunk_header,
+            chunk.context_prefix,
+            chunk.start_line,
+            chunk.end_line,
+            chunk.end_line - chunk.start_line + 1,
+            chunk.content
+        )
+    };
+
+    vec![llm::Message::system(system_prompt), llm::Message::user(user_prompt)]
+}
+
+/// Analyze every chunk, up to `max_concurrency` at once, and return the
+/// `ChunkExplanation`s in original (line) order plus total usage.
+///
+/// Chunks are handed out from a shared work queue to `max_concurrency`
+/// worker threads, so the wall-clock cost of a multi-chunk run is roughly
+/// `total_chunks / max_concurrency` LLM round trips instead of
+/// `total_chunks`. At `max_concurrency == 1` this is equivalent to the old
+/// strictly sequential loop. When `stream_callback` is set, only the
+/// lowest-indexed chunk still in flight at the moment it starts streams its
+/// tokens live — the rest analyze silently in the background and appear all
+/// at once when they finish, so the callback never interleaves output from
+/// two chunks.
+fn analyze_chunks<F>(
+    provider: &dyn Provider,
+    chunks: &[CodeChunk],
+    total_chunks: usize,
+    role_prompt: Option<&str>,
+    max_concurrency: usize,
+    verbose: bool,
+    stream_callback: Option<F>,
+    abort: Option<&llm::AbortSignal>,
+) -> Result<(Vec<ChunkExplanation>, Usage)>
+where
+    F: FnMut(&str) + Send,
+{
+    let max_concurrency = max_concurrency.max(1).min(total_chunks.max(1));
+
+    let pending: Mutex<VecDeque<usize>> = Mutex::new((0..total_chunks).collect());
+    let in_flight: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+    let results: Mutex<Vec<Option<(ChunkExplanation, Usage)>>> = Mutex::new(vec![None; total_chunks]);
+    let first_err: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+    let callback: Mutex<Option<F>> = Mutex::new(stream_callback);
+    let cancelled: Mutex<bool> = Mutex::new(false);
+
+    thread::scope(|scope| {
+        for _ in 0..max_concurrency {
+            scope.spawn(|| loop {
+                if first_err.lock().unwrap().is_some() {
+                    return;
+                }
+                if abort.is_some_and(|a| a.is_aborted()) {
+                    *cancelled.lock().unwrap() = true;
+                    return;
+                }
+
+                let Some(i) = pending.lock().unwrap().pop_front() else {
+                    return;
+                };
+                in_flight.lock().unwrap().insert(i);
+
+                let chunk = &chunks[i];
+                if verbose {
+                    eprintln!(
+                        "  [debug] Chunk {}/{} (lines {}–{})…",
+                        i + 1,
+                        total_chunks,
+                        chunk.start_line,
+                        chunk.end_line
+                    );
+                }
+
+                let cached = cache::lookup_chunk(&chunk.content, &chunk.context_prefix, provider.name(), provider.model());
+                let outcome: Result<(String, Usage)> = match cached {
+                    Some(explanation) => Ok((explanation, Usage::default())),
+                    None => {
+                        let messages = build_chunk_messages(chunk, i, total_chunks, role_prompt);
+                        let is_lowest_in_flight = *in_flight.lock().unwrap().iter().next().unwrap() == i;
+
+                        if is_lowest_in_flight && callback.lock().unwrap().is_some() {
+                            llm::generate_streaming_usage(provider, &messages, CHUNK_MAX_TOKENS, &mut |tok: &str| {
+                                if let Some(cb) = callback.lock().unwrap().as_mut() {
+                                    cb(tok);
+                                }
+                            })
+                        } else {
+                            llm::generate_with_retry_usage(provider, &messages, CHUNK_MAX_TOKENS)
+                        }
+                    }
+                };
+
+                in_flight.lock().unwrap().remove(&i);
+
+                match outcome {
+                    Ok((explanation, chunk_usage)) => {
+                        let sanitized = explanation.replace('—', "-");
+                        cache::store_chunk(&chunk.content, &chunk.context_prefix, provider.name(), provider.model(), &sanitized);
+                        let explanation = ChunkExplanation {
+                            start_line: chunk.start_line,
+                            end_line: chunk.end_line,
+                            explanation: sanitized,
+                        };
+                        results.lock().unwrap()[i] = Some((explanation, chunk_usage));
+                    }
+                    Err(e) => {
+                        let mut first_err = first_err.lock().unwrap();
+                        if first_err.is_none() {
+                            *first_err = Some(e);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(e) = first_err.into_inner().unwrap() {
+        return Err(e);
+    }
+    if *cancelled.lock().unwrap() {
+        anyhow::bail!("Cancelled.");
+    }
+
+    let mut usage = Usage::default();
+    let chunk_explanations = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| {
+            let (explanation, chunk_usage) = r.expect("every chunk index is populated when first_err is None");
+            usage += chunk_usage;
+            explanation
+        })
+        .collect();
+
+    Ok((chunk_explanations, usage))
+}
+
 /// Process all chunks through the LLM with streaming and retry.
 ///
-/// If `stream_callback` is provided, tokens are passed to it as they arrive.
-/// The synthesis step always uses non-streaming with retry since we need the full response.
+/// Up to `max_concurrency` chunks (default: available CPU cores, capped at
+/// 8; pass `Some(1)` to force the old strictly-sequential behavior) are
+/// analyzed at once. If `stream_callback` is provided, tokens from whichever
+/// chunk is the lowest-indexed one in flight are passed to it as they
+/// arrive. The synthesis step always uses non-streaming with retry since we
+/// need the full response.
 pub fn explain_code<F>(
     code: &str,
+    lang_hint: Option<&str>,
     provider: &dyn Provider,
+    context_window: Option<u64>,
+    max_concurrency: Option<usize>,
     verbose: bool,
-    mut stream_callback: Option<F>,
+    role_prompt: Option<&str>,
+    stream_callback: Option<F>,
+    abort: Option<&llm::AbortSignal>,
 ) -> Result<ExplainResult>
 where
-    F: FnMut(&str),
+    F: FnMut(&str) + Send,
 {
     let start_time = Instant::now();
-    let chunks = chunk_code(code);
+    let max_lines = effective_max_chunk_lines(code, provider.name(), context_window);
+    let chunks = chunk_code_with_budget(code, lang_hint, provider.name(), context_window);
     let total_lines = code.lines().count();
     let total_chunks = chunks.len();
+    let max_concurrency = max_concurrency.unwrap_or_else(default_max_concurrency);
 
     if verbose {
         eprintln!(
-            "  [debug] {} lines → {} chunks (max {} lines/chunk, {} line overlap, stream={})",
+            "  [debug] {} lines → {} chunks (max {} lines/chunk, {} line overlap, max_concurrency={}, stream={})",
             total_lines,
             total_chunks,
-            MAX_CHUNK_LINES,
+            max_lines,
             CONTEXT_OVERLAP_LINES,
+            max_concurrency,
             stream_callback.is_some()
         );
     }
 
-    let mut chunk_explanations = Vec::new();
-
-    for (i, chunk) in chunks.iter().enumerate() {
-        if total_chunks > 1 {
-            // TODO: If we have a callback, maybe we should report progress too?
-            // For now, assume the caller handles progress indication or we print it if verbose.
-            if verbose {
-                eprintln!(
-                    "  [debug] Chunk {}/{} (lines {}–{})…",
-                    i + 1,
-                    total_chunks,
-                    chunk.start_line,
-                    chunk.end_line
-                );
-            }
-        }
-
-        let system_prompt = build_chunk_system_prompt(i + 1, total_chunks);
-
-        let user_prompt = if chunk.context_prefix.is_empty() {
-            format!(
-                "Lines {}-{} ({} lines):\n\n```\n{}\n```",
-                chunk.start_line,
-                chunk.end_line,
-                chunk.end_line - chunk.start_line + 1,
-                chunk.content
-            )
-        } else {
-            format!(
-                "{}\nLines {}-{} ({} lines):\n\n```\n{}\n```",
-                chunk.context_prefix,
-                chunk.start_line,
-                chunk.end_line,
-                chunk.end_line - chunk.start_line + 1,
-                chunk.content
-            )
-        };
-
-        let explanation = if let Some(ref mut callback) = stream_callback {
-            // Stream tokens to callback
-            llm::generate_streaming(
-                provider,
-                &system_prompt,
-                &user_prompt,
-                CHUNK_MAX_TOKENS,
-                callback,
-            )?
-        } else {
-            llm::generate_with_retry(provider, &system_prompt, &user_prompt, CHUNK_MAX_TOKENS)?
-        };
-
-        let sanitized_explanation = explanation.replace('—', "-");
-
-        chunk_explanations.push(ChunkExplanation {
-            start_line: chunk.start_line,
-            end_line: chunk.end_line,
-            explanation: sanitized_explanation,
-        });
-    }
+    let (chunk_explanations, mut usage) = analyze_chunks(
+        provider,
+        &chunks,
+        total_chunks,
+        role_prompt,
+        max_concurrency,
+        verbose,
+        stream_callback,
+        abort,
+    )?;
 
     // Synthesis — always non-streaming with retry (needs full response for parsing)
     // We could stream status updates if we had a status callback, but for now we just run it.
     let (mut summary, mut questions) = if total_chunks > 1 {
-        generate_summary_and_questions(provider, &chunk_explanations, total_lines)?
+        let (summary, questions, synth_usage) =
+            generate_summary_and_questions(provider, &chunk_explanations, total_lines)?;
+        usage += synth_usage;
+        (summary, questions)
     } else {
         let explanation = &chunk_explanations[0].explanation;
-        let questions = generate_follow_up_only(provider, explanation).unwrap_or_default();
+        let (questions, synth_usage) =
+            generate_follow_up_only(provider, explanation).unwrap_or_default();
+        usage += synth_usage;
         (explanation.clone(), questions)
     };
 
@@ -257,10 +722,12 @@ where
         overall_summary: summary,
         follow_up_questions: questions,
         elapsed: start_time.elapsed(),
+        usage,
+        provider_name: provider.name().to_string(),
     })
 }
 
-fn build_chunk_system_prompt(chunk_num: usize, total_chunks: usize) -> String {
+fn build_chunk_system_prompt(total_chunks: usize) -> String {
     if total_chunks == 1 {
         return r#"You are a senior software engineer conducting a thorough code review. Analyze the code and produce a structured explanation in markdown.
 
@@ -299,8 +766,7 @@ For EVERY function, method, struct, enum, trait, and significant constant:
 Be thorough — do NOT skip any function or type definition. Use code-aware language (refer to actual names from the code)."#.to_string();
     }
 
-    format!(
-        r#"You are a senior software engineer analyzing chunk {chunk_num} of {total_chunks} of a larger codebase.
+    r#"You are a senior software engineer analyzing one chunk at a time of a larger codebase, split across multiple requests. The user prompt tells you which chunk this is and its line range.
 
 Some preceding lines may be included for boundary context — focus your analysis on the code after the "[chunk starts here]" marker. Do NOT re-explain the context lines.
 
@@ -327,14 +793,14 @@ For EVERY function, method, struct, enum, trait, and constant in this chunk:
 - Performance, security, or correctness concerns specific to this chunk
 
 Be thorough — capture EVERY definition. Do not omit anything. Use actual names from the code."#
-    )
+        .to_string()
 }
 
 fn generate_summary_and_questions(
     provider: &dyn Provider,
     explanations: &[ChunkExplanation],
     total_lines: usize,
-) -> Result<(String, Vec<String>)> {
+) -> Result<(String, Vec<FollowUpQuestion>, Usage)> {
     let combined = explanations
         .iter()
         .map(|e| {
@@ -346,29 +812,30 @@ fn generate_summary_and_questions(
         .collect::<Vec<_>>()
         .join("\n\n---\n\n");
 
-    let system_prompt = r#"You are a senior software architect synthesizing a multi-chunk code analysis. You have been given individual analyses of each chunk — now produce a unified view.
-
-Your response MUST use this exact format:
+    // `combined` is built from the (possibly cache-hit) per-chunk
+    // explanations, so it's only different from a previous run when at
+    // least one chunk actually changed — making this cache naturally skip
+    // re-synthesizing an unchanged codebase.
+    if let Some((summary, questions)) = cache::lookup_synthesis(&combined, provider.name(), provider.model()) {
+        return Ok((summary, questions, Usage::default()));
+    }
 
-## Summary
-2-3 paragraphs covering:
-- What the entire codebase does (purpose, domain, user-facing behavior)
-- Architecture: how the chunks connect (data flow, call chains, dependency graph)
-- Key design decisions: patterns used, tradeoffs made, and why they matter
-- Quality assessment: code health, consistency, and notable strengths or weaknesses
+    let system_prompt = r#"You are a senior software architect synthesizing a multi-chunk code analysis. You have been given individual analyses of each chunk — now produce a unified view.
 
-## Key Components
-Bullet list of the most important types/functions and their roles across the codebase.
+Respond with ONLY a single JSON object (no markdown, no code fences, no commentary before or after) matching this exact shape:
 
-## Follow-up Questions
-5 targeted questions, one from each category:
-1. [Architecture] — about structure, modularity, or coupling
-2. [Testing] — about test coverage, edge cases, or testability
-3. [Security] — about input validation, secrets, or access control
-4. [Performance] — about bottlenecks, scaling, or resource usage
-5. [Maintainability] — about readability, tech debt, or extensibility
+{
+  "summary": "2-3 paragraphs covering what the codebase does, how the chunks connect architecturally, key design decisions, and a quality assessment",
+  "follow_up_questions": [
+    {"category": "Architecture", "question": "..."},
+    {"category": "Testing", "question": "..."},
+    {"category": "Security", "question": "..."},
+    {"category": "Performance", "question": "..."},
+    {"category": "Maintainability", "question": "..."}
+  ]
+}
 
-Do NOT just summarize each chunk sequentially — synthesize across chunks to show the bigger picture."#;
+The summary must synthesize across chunks to show the bigger picture, not just summarize each chunk sequentially. Each follow-up question must be specific to the actual code (reference real function/type names)."#;
 
     let user_prompt = format!(
         "The codebase has {} total lines across {} chunks:\n\n{}",
@@ -377,105 +844,78 @@ Do NOT just summarize each chunk sequentially — synthesize across chunks to sh
         combined
     );
 
-    let response =
-        llm::generate_with_retry(provider, system_prompt, &user_prompt, SYNTHESIS_MAX_TOKENS)?;
+    let messages = vec![llm::Message::system(system_prompt), llm::Message::user(user_prompt)];
+    let (response, usage) = llm::generate_with_retry_usage(provider, &messages, SYNTHESIS_MAX_TOKENS)?;
     let (summary, questions) = parse_summary_response(&response);
-    Ok((summary, questions))
+    cache::store_synthesis(&combined, provider.name(), provider.model(), &summary, &questions);
+    Ok((summary, questions, usage))
 }
 
-fn generate_follow_up_only(provider: &dyn Provider, explanation: &str) -> Result<Vec<String>> {
-    let system_prompt = r#"Based on the code analysis below, generate exactly 5 follow-up questions that would help someone deeply understand and improve this code. One question from each category:
+fn generate_follow_up_only(provider: &dyn Provider, explanation: &str) -> Result<(Vec<FollowUpQuestion>, Usage)> {
+    if let Some((_, questions)) = cache::lookup_synthesis(explanation, provider.name(), provider.model()) {
+        return Ok((questions, Usage::default()));
+    }
 
-1. [Architecture] — How the code is structured, modularity, coupling, or design patterns
-2. [Testing] — Test coverage gaps, edge cases to test, or testability improvements
-3. [Security] — Input validation, secret handling, injection risks, or access control
-4. [Performance] — Potential bottlenecks, unnecessary allocations, or scaling concerns
-5. [Maintainability] — Readability, tech debt, documentation, or future extensibility
+    let system_prompt = r#"Based on the code analysis below, generate exactly 5 follow-up questions that would help someone deeply understand and improve this code, one from each of these categories: Architecture, Testing, Security, Performance, Maintainability.
 
-Make each question specific to the actual code (reference real function/type names). Do NOT ask generic questions."#;
+Respond with ONLY a single JSON object (no markdown, no code fences, no commentary before or after) matching this exact shape:
 
-    let response =
-        llm::generate_with_retry(provider, system_prompt, explanation, FOLLOWUP_MAX_TOKENS)?;
-
-    Ok(response
-        .lines()
-        .filter(|line| {
-            let t = line.trim();
-            !t.is_empty()
-                && t.len() > 3
-                && (t.starts_with("1.")
-                    || t.starts_with("2.")
-                    || t.starts_with("3.")
-                    || t.starts_with("4.")
-                    || t.starts_with("5."))
-        })
-        .map(|line| {
-            let t = line.trim();
-            if let Some(rest) = t.strip_prefix(|c: char| c.is_ascii_digit()) {
-                rest.trim_start_matches('.').trim().to_string()
-            } else {
-                t.to_string()
-            }
-        })
-        .take(5)
-        .collect())
+{
+  "follow_up_questions": [
+    {"category": "Architecture", "question": "..."},
+    {"category": "Testing", "question": "..."},
+    {"category": "Security", "question": "..."},
+    {"category": "Performance", "question": "..."},
+    {"category": "Maintainability", "question": "..."}
+  ]
 }
 
-fn parse_summary_response(response: &str) -> (String, Vec<String>) {
-    let mut summary = String::new();
-    let mut questions = Vec::new();
-    let mut in_summary = false;
-    let mut in_questions = false;
+Make each question specific to the actual code (reference real function/type names). Do NOT ask generic questions."#;
 
-    for line in response.lines() {
-        let trimmed = line.trim();
+    let messages = vec![llm::Message::system(system_prompt), llm::Message::user(explanation)];
+    let (response, usage) = llm::generate_with_retry_usage(provider, &messages, FOLLOWUP_MAX_TOKENS)?;
+    let (_, questions) = parse_summary_response(&response);
 
-        if trimmed.starts_with("## Summary")
-            || trimmed.starts_with("**Summary**")
-            || trimmed.starts_with("# Summary")
-        {
-            in_summary = true;
-            in_questions = false;
-            continue;
-        }
-        if trimmed.starts_with("## Follow-up")
-            || trimmed.starts_with("**Follow-up")
-            || trimmed.starts_with("# Follow-up")
-            || trimmed.starts_with("## Questions")
-        {
-            in_summary = false;
-            in_questions = true;
-            continue;
-        }
+    cache::store_synthesis(explanation, provider.name(), provider.model(), "", &questions);
+    Ok((questions, usage))
+}
 
-        if in_summary {
-            summary.push_str(line);
-            summary.push('\n');
-        }
+/// Wire shape the synthesis/follow-up prompts above ask the model to
+/// respond with — kept private since `FollowUpQuestion` is the type the
+/// rest of the crate actually works with.
+#[derive(Debug, Deserialize)]
+struct SummaryResponseWire {
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    follow_up_questions: Vec<FollowUpQuestion>,
+}
 
-        if in_questions && questions.len() < 5 {
-            let t = trimmed.to_string();
-            if !t.is_empty()
-                && (t.starts_with("1.")
-                    || t.starts_with("2.")
-                    || t.starts_with("3.")
-                    || t.starts_with("4.")
-                    || t.starts_with("5."))
-            {
-                if let Some(rest) = t.strip_prefix(|c: char| c.is_ascii_digit()) {
-                    let q = rest.trim_start_matches('.').trim().to_string();
-                    if !q.is_empty() {
-                        questions.push(q);
-                    }
-                }
-            }
+/// Parse a (hopefully) JSON synthesis/follow-up response into a summary and
+/// its follow-up questions. LLMs occasionally wrap JSON in a ```json code
+/// fence despite being told not to, so that's stripped defensively before
+/// parsing. Falls back to treating the whole response as the summary with
+/// no questions if it isn't valid JSON at all.
+fn parse_summary_response(response: &str) -> (String, Vec<FollowUpQuestion>) {
+    let trimmed = response.trim();
+    let stripped = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .map(|s| s.strip_suffix("```").unwrap_or(s))
+        .unwrap_or(trimmed)
+        .trim();
+
+    match serde_json::from_str::<SummaryResponseWire>(stripped) {
+        Ok(wire) if !wire.summary.trim().is_empty() || !wire.follow_up_questions.is_empty() => {
+            let summary = if wire.summary.trim().is_empty() {
+                response.trim().to_string()
+            } else {
+                wire.summary.trim().to_string()
+            };
+            (summary, wire.follow_up_questions)
         }
+        _ => (response.trim().to_string(), Vec::new()),
     }
-
-    if summary.trim().is_empty() {
-        summary = response.to_string();
-    }
-    (summary.trim().to_string(), questions)
 }
 
 #[cfg(test)]
@@ -485,7 +925,7 @@ mod tests {
     #[test]
     fn test_chunk_small_code() {
         let code = "fn main() {\n    println!(\"hello\");\n}\n";
-        let chunks = chunk_code(code);
+        let chunks = chunk_code(code, None);
         assert_eq!(chunks.len(), 1);
         assert_eq!(chunks[0].start_line, 1);
         assert_eq!(chunks[0].end_line, 3);
@@ -498,7 +938,7 @@ mod tests {
         for i in 0..500 {
             code.push_str(&format!("let x{} = {};\n", i, i));
         }
-        let chunks = chunk_code(&code);
+        let chunks = chunk_code(&code, None);
         assert!(chunks.len() > 1);
         for i in 1..chunks.len() {
             assert_eq!(chunks[i].start_line, chunks[i - 1].end_line + 1);
@@ -511,20 +951,70 @@ mod tests {
 
     #[test]
     fn test_chunk_empty_code() {
-        assert_eq!(chunk_code("").len(), 0);
+        assert_eq!(chunk_code("", None).len(), 0);
+    }
+
+    #[test]
+    fn test_chunk_syntax_aware_rust_boundaries() {
+        let code = "fn alpha() -> i32 {\n    1\n}\n\nstruct Beta {\n    x: i32,\n}\n\nimpl Beta {\n    fn new() -> Self {\n        Beta { x: 0 }\n    }\n}\n";
+        let chunks = chunk_code(code, Some("rs"));
+        assert!(chunks.iter().any(|c| c.node_kind.is_some()));
+        for chunk in &chunks {
+            assert!(!chunk.content.trim().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_chunk_code_with_budget_falls_back_without_context_window() {
+        let code = "fn main() {}\n";
+        assert_eq!(chunk_code(code, None).len(), chunk_code_with_budget(code, None, "claude", None).len());
+    }
+
+    #[test]
+    fn test_chunk_code_with_budget_grows_chunk_for_large_context_window() {
+        let mut code = String::new();
+        for i in 0..500 {
+            code.push_str(&format!("let x{} = {};\n", i, i));
+        }
+        let bounded = chunk_code(&code, None);
+        let unbounded = chunk_code_with_budget(&code, None, "ollama", Some(1_000_000));
+        assert!(unbounded.len() <= bounded.len());
+    }
+
+    #[test]
+    fn test_chunk_syntax_aware_falls_back_on_unknown_lang() {
+        let code = "fn main() {\n    println!(\"hello\");\n}\n";
+        let chunks = chunk_code(code, Some("cobol"));
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].node_kind.is_none());
     }
 
     #[test]
     fn test_parse_summary_response() {
-        let response = "## Summary\nTest summary.\n\n## Follow-up Questions\n1. Q1?\n2. Q2?\n3. Q3?\n4. Q4?\n5. Q5?\n";
+        let response = r#"{"summary": "Test summary.", "follow_up_questions": [
+            {"category": "Architecture", "question": "Q1?"},
+            {"category": "Testing", "question": "Q2?"},
+            {"category": "Security", "question": "Q3?"},
+            {"category": "Performance", "question": "Q4?"},
+            {"category": "Maintainability", "question": "Q5?"}
+        ]}"#;
         let (summary, questions) = parse_summary_response(response);
         assert_eq!(summary, "Test summary.");
         assert_eq!(questions.len(), 5);
+        assert_eq!(questions[0].category, FollowUpCategory::Architecture);
+    }
+
+    #[test]
+    fn test_parse_summary_response_strips_code_fence() {
+        let response = "```json\n{\"summary\": \"Fenced.\", \"follow_up_questions\": []}\n```";
+        let (summary, questions) = parse_summary_response(response);
+        assert_eq!(summary, "Fenced.");
+        assert!(questions.is_empty());
     }
 
     #[test]
     fn test_parse_summary_fallback() {
-        let response = "No headers here.\nLine two.";
+        let response = "No JSON here.\nLine two.";
         let (summary, questions) = parse_summary_response(response);
         assert_eq!(summary, response);
         assert!(questions.is_empty());
@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use regex::Regex;
+
+/// Directories to check for a zsh completion file (`_<tool>`), beyond
+/// whatever's on `$FPATH`.
+const ZSH_COMPLETION_DIRS: &[&str] = &[
+    "/usr/share/zsh/site-functions",
+    "/usr/local/share/zsh/site-functions",
+];
+
+/// Directories to check for a bash-completion script (`<tool>`).
+const BASH_COMPLETION_DIRS: &[&str] = &[
+    "/usr/share/bash-completion/completions",
+    "/etc/bash_completion.d",
+];
+
+/// A parsed `'name:description'` entry from a zsh `_describe` array.
+struct SubcommandEntry {
+    name: String,
+    description: String,
+}
+
+/// A parsed `_arguments` option spec: the flag itself, an optional arg
+/// placeholder, and its description.
+struct FlagEntry {
+    flag: String,
+    arg: Option<String>,
+    description: String,
+}
+
+/// Looks up a structured tool/subcommand reference from an installed shell
+/// completion file — far denser and more reliable than `--help` text (tools
+/// like `docker` ship 500-1400 line completions covering every flag).
+/// Returns `None` if no completion file was found or nothing useful parsed
+/// out of it, so the caller can fall back to `--help`.
+pub fn lookup(tool: &str, subcommand: Option<&str>) -> Option<String> {
+    if let Some(path) = find_zsh_completion(tool) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            if let Some(text) = format_zsh_reference(tool, subcommand, &content) {
+                return Some(text);
+            }
+        }
+    }
+
+    if let Some(path) = find_bash_completion(tool) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            if let Some(text) = format_bash_reference(tool, &content) {
+                return Some(text);
+            }
+        }
+    }
+
+    None
+}
+
+fn find_zsh_completion(tool: &str) -> Option<PathBuf> {
+    let filename = format!("_{}", tool);
+
+    if let Ok(fpath) = env::var("FPATH") {
+        for dir in fpath.split(':') {
+            let candidate = PathBuf::from(dir).join(&filename);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+
+    ZSH_COMPLETION_DIRS
+        .iter()
+        .map(|dir| PathBuf::from(dir).join(&filename))
+        .find(|candidate| candidate.is_file())
+}
+
+fn find_bash_completion(tool: &str) -> Option<PathBuf> {
+    BASH_COMPLETION_DIRS
+        .iter()
+        .map(|dir| PathBuf::from(dir).join(tool))
+        .find(|candidate| candidate.is_file())
+}
+
+fn format_zsh_reference(tool: &str, subcommand: Option<&str>, content: &str) -> Option<String> {
+    let subcommands = parse_zsh_subcommands(content);
+
+    if let Some(sub) = subcommand {
+        let description = subcommands.iter().find(|s| s.name == sub).map(|s| s.description.clone());
+        let scope = find_subcommand_scope(tool, sub, content);
+        let flags = parse_zsh_flags(scope.unwrap_or(content));
+
+        if description.is_none() && flags.is_empty() {
+            return None;
+        }
+
+        let description = description.unwrap_or_else(|| format!("run `{} {}`", tool, sub));
+        let flag_str = format_flags(&flags);
+        return Some(if flag_str.is_empty() {
+            format!("{} {}: {}", tool, sub, description)
+        } else {
+            format!("{} {}: {} | flags: {}", tool, sub, description, flag_str)
+        });
+    }
+
+    let flags = parse_zsh_flags(content);
+    if subcommands.is_empty() && flags.is_empty() {
+        return None;
+    }
+
+    let mut lines: Vec<String> = subcommands
+        .iter()
+        .take(40)
+        .map(|entry| format!("{} {}: {}", tool, entry.name, entry.description))
+        .collect();
+
+    if !flags.is_empty() {
+        lines.push(format!("{} flags: {}", tool, format_flags(&flags)));
+    }
+
+    Some(lines.join("\n"))
+}
+
+fn format_flags(flags: &[FlagEntry]) -> String {
+    flags
+        .iter()
+        .take(60)
+        .map(|f| match (&f.arg, f.description.is_empty()) {
+            (Some(arg), false) => format!("{} {} ({})", f.flag, arg, f.description),
+            (Some(arg), true) => format!("{} {}", f.flag, arg),
+            (None, false) => format!("{} ({})", f.flag, f.description),
+            (None, true) => f.flag.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Extracts `'name:description'` literals the way `_describe`'s backing
+/// arrays are written, skipping anything that looks like a flag spec.
+fn parse_zsh_subcommands(content: &str) -> Vec<SubcommandEntry> {
+    let re = Regex::new(r"'([A-Za-z][A-Za-z0-9_.-]*):([^'\[\]]+)'").unwrap();
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+
+    for cap in re.captures_iter(content) {
+        let name = cap[1].to_string();
+        let description = cap[2].trim().to_string();
+        if description.is_empty() || !seen.insert(name.clone()) {
+            continue;
+        }
+        out.push(SubcommandEntry { name, description });
+    }
+
+    out
+}
+
+/// Extracts `_arguments` option specs: `-x[desc]`, `--long-opt[desc]`, and
+/// `--opt=[desc]:arg`.
+fn parse_zsh_flags(content: &str) -> Vec<FlagEntry> {
+    let re = Regex::new(r"(-{1,2}[A-Za-z][A-Za-z0-9-]*)(=)?\[([^\]]*)\](?::([A-Za-z_][A-Za-z0-9_-]*))?").unwrap();
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+
+    for cap in re.captures_iter(content) {
+        let flag = cap[1].to_string();
+        let has_eq = cap.get(2).is_some();
+        let description = cap[3].trim().to_string();
+        let arg = cap.get(4).map(|m| {
+            if has_eq {
+                format!("=<{}>", m.as_str())
+            } else {
+                format!("<{}>", m.as_str())
+            }
+        });
+
+        let key = format!("{}{}", flag, arg.clone().unwrap_or_default());
+        if !seen.insert(key) {
+            continue;
+        }
+        out.push(FlagEntry { flag, arg, description });
+    }
+
+    out
+}
+
+/// Best-effort: many completions define a dedicated function per subcommand
+/// (git's `_git-commit`, `_git-push`, ...). If one exists, scope flag
+/// parsing to just that function's body instead of the whole file, so
+/// e.g. a `git commit` query doesn't surface every flag `git` has.
+fn find_subcommand_scope<'a>(tool: &str, subcommand: &str, content: &'a str) -> Option<&'a str> {
+    for sep in ['-', '_'] {
+        let marker = format!("_{}{}{}", tool, sep, subcommand);
+        let Some(start) = content.find(&marker) else { continue };
+        let Some(brace_offset) = content[start..].find('{') else { continue };
+        let body_start = start + brace_offset;
+        if let Some(body_len) = braced_block_len(&content[body_start..]) {
+            return Some(&content[body_start..body_start + body_len]);
+        }
+    }
+    None
+}
+
+/// Returns the length of the balanced `{ ... }` block starting at `text`'s
+/// first character, which must be `{`.
+fn braced_block_len(text: &str) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Bash-completion scripts are imperative shell code, not declarative data,
+/// so there's no name/description structure to recover — just the distinct
+/// long-flag literals that appear anywhere in the script.
+fn format_bash_reference(tool: &str, content: &str) -> Option<String> {
+    let re = Regex::new(r"--[A-Za-z][A-Za-z0-9-]*").unwrap();
+    let mut seen = HashSet::new();
+    let mut flags = Vec::new();
+
+    for m in re.find_iter(content) {
+        let flag = m.as_str().to_string();
+        if seen.insert(flag.clone()) {
+            flags.push(flag);
+        }
+    }
+
+    if flags.len() < 3 {
+        return None;
+    }
+
+    flags.truncate(80);
+    Some(format!("{} flags: {}", tool, flags.join(", ")))
+}
@@ -24,6 +24,16 @@ pub struct Config {
 
     /// UI preferences
     pub ui: UiConfig,
+
+    /// Named presets invoked with `niko --role <name> ...` — map of role
+    /// name → role config
+    pub roles: HashMap<String, RoleConfig>,
+
+    /// Persistent system prompt prepended ahead of `niko cmd`'s own system
+    /// message, set via `niko settings set system_message "..."`; the other
+    /// modes do not use it. A role's own `system_prompt` is a per-session
+    /// override and takes precedence over this instead of stacking with it.
+    pub default_system_message: Option<String>,
 }
 
 /// A single provider configuration — fully dynamic
@@ -44,6 +54,16 @@ pub struct ProviderConfig {
 
     /// Additional provider-specific options
     pub options: HashMap<String, String>,
+
+    /// Ordered list of provider names to fall back to if this one exhausts
+    /// its retries on a retryable error or is reported unavailable
+    pub fallbacks: Vec<String>,
+
+    /// Max context length in tokens, set via `niko settings configure` or
+    /// `niko settings set <provider>.context_window <n>`. Ollama has no API
+    /// to report a model's built-in size, so this is threaded through as
+    /// `options.num_ctx`; `None` leaves the provider's own default in place.
+    pub context_window: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +71,7 @@ pub struct ProviderConfig {
 pub struct SafetyConfig {
     pub require_confirm_dangerous: bool,
     pub blocked_commands: Vec<String>,
+    pub sandbox: SandboxConfig,
 }
 
 impl Default for SafetyConfig {
@@ -66,10 +87,68 @@ impl Default for SafetyConfig {
                 "> /dev/sda".into(),
                 "chmod -R 777 /".into(),
             ],
+            sandbox: SandboxConfig::default(),
         }
     }
 }
 
+/// Opt-in confinement applied to `/run`/`/approve`'d shell commands, on top
+/// of `blocked_commands`'s denylist. The denylist alone can't stop a command
+/// that was never anticipated (an alias, env indirection, a novel destructive
+/// flag) — this constrains what an *approved* command can actually reach,
+/// regardless of what it turns out to be.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /// Off by default: real namespace/seccomp confinement needs `bwrap`
+    /// installed and only applies on Linux, so turning this on is an
+    /// explicit opt-in rather than a silent no-op on unsupported setups.
+    pub enabled: bool,
+    /// If false (default), the command's network namespace is unshared —
+    /// no network access at all. Set true for commands that legitimately
+    /// need it (package installs, `curl`, ...).
+    pub allow_network: bool,
+    /// Extra paths (beyond the command's cwd) to bind read-write inside the
+    /// sandbox; everything else is read-only.
+    pub extra_read_write: Vec<String>,
+    /// Hard wall-clock limit in seconds before a sandboxed or unsandboxed
+    /// command is sent the same termination sequence `/stop` uses. `0`
+    /// disables the timer.
+    pub max_wall_clock_secs: u64,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_network: false,
+            extra_read_write: Vec::new(),
+            max_wall_clock_secs: 600,
+        }
+    }
+}
+
+/// A named preset bundling a reusable system prompt, an optional
+/// provider/model override, and a default `max_tokens`, so users can save
+/// specialized personas (e.g. a terse "shell-only" role or a verbose
+/// "teaching" explain role) and invoke them with `niko --role <name> ...`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RoleConfig {
+    /// Reusable system prompt, merged alongside the mode's own system prompt
+    pub system_prompt: String,
+
+    /// Provider to use for this role, overriding the active provider (but
+    /// not an explicit `--provider` flag)
+    pub provider: Option<String>,
+
+    /// Model to use for this role, overriding the provider's configured model
+    pub model: Option<String>,
+
+    /// Max tokens to request, overriding the mode's own default
+    pub max_tokens: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct UiConfig {
@@ -153,6 +232,8 @@ pub fn default_config() -> Config {
         base_url: "http://127.0.0.1:11434".into(),
         model: String::new(), // will be selected dynamically
         options: HashMap::new(),
+        fallbacks: Vec::new(),
+        context_window: None,
     });
 
     Config {
@@ -160,6 +241,8 @@ pub fn default_config() -> Config {
         providers,
         safety: SafetyConfig::default(),
         ui: UiConfig::default(),
+        roles: HashMap::new(),
+        default_system_message: None,
     }
 }
 
@@ -236,6 +319,13 @@ pub fn set_active_provider(name: &str) -> Result<()> {
     save(&cfg)
 }
 
+/// Set or clear the persistent default system message
+pub fn set_default_system_message(value: &str) -> Result<()> {
+    let mut cfg = load()?;
+    cfg.default_system_message = if value.is_empty() { None } else { Some(value.to_string()) };
+    save(&cfg)
+}
+
 /// Add or update a provider
 pub fn upsert_provider(name: &str, pcfg: ProviderConfig) -> Result<()> {
     let mut cfg = load()?;
@@ -253,6 +343,20 @@ pub fn set_provider_field(provider: &str, field: &str, value: &str) -> Result<()
         "base_url" => p.base_url = value.into(),
         "model" => p.model = value.into(),
         "kind" => p.kind = value.into(),
+        "context_window" => {
+            p.context_window = if value.is_empty() {
+                None
+            } else {
+                Some(value.parse().context("context_window must be a number")?)
+            };
+        }
+        "fallbacks" => {
+            p.fallbacks = value
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
         _ => {
             p.options.insert(field.to_string(), value.to_string());
         }
@@ -261,6 +365,35 @@ pub fn set_provider_field(provider: &str, field: &str, value: &str) -> Result<()
     save(&cfg)
 }
 
+// ─── Roles ──────────────────────────────────────────────────────────────────
+
+/// Add or update a named role preset
+pub fn upsert_role(name: &str, role: RoleConfig) -> Result<()> {
+    let mut cfg = load()?;
+    cfg.roles.insert(name.to_string(), role);
+    save(&cfg)
+}
+
+/// Remove a named role preset
+pub fn delete_role(name: &str) -> Result<()> {
+    let mut cfg = load()?;
+    if cfg.roles.remove(name).is_none() {
+        anyhow::bail!("Role '{}' not found.", name);
+    }
+    save(&cfg)
+}
+
+/// Look up a named role preset
+pub fn get_role(name: &str) -> Result<RoleConfig> {
+    let cfg = load()?;
+    cfg.roles.get(name).cloned().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Role '{}' not found.\nRun 'niko settings role list' to see available roles.",
+            name
+        )
+    })
+}
+
 /// Get the active provider config
 pub fn active_provider() -> Result<(String, ProviderConfig)> {
     let cfg = load()?;
@@ -4,7 +4,7 @@ use std::time::Duration;
 use anyhow::{bail, Context, Result};
 use serde::Deserialize;
 
-use crate::llm::{estimate_param_billions, ModelInfo, Provider};
+use crate::llm::{estimate_param_billions, AgentTurn, Message, ModelInfo, Provider, Role, ToolCall, ToolSpec, Usage};
 
 /// Anthropic Claude Messages API provider with SSE streaming
 pub struct ClaudeProvider {
@@ -20,11 +20,143 @@ struct MessagesResponse {
     stop_reason: Option<String>,
     #[serde(default)]
     error: Option<ApiError>,
+    #[serde(default)]
+    usage: Option<UsageWire>,
+}
+
+/// Anthropic's `usage` object, as reported on the final `MessagesResponse`
+/// and streamed piecemeal across `message_start`/`message_delta` events.
+/// `cache_creation_input_tokens`/`cache_read_input_tokens` are only present
+/// when the request used a `cache_control` system block (see `system_block`).
+#[derive(Deserialize, Default, Clone, Copy)]
+struct UsageWire {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    cache_creation_input_tokens: u64,
+    #[serde(default)]
+    cache_read_input_tokens: u64,
+}
+
+impl From<UsageWire> for Usage {
+    fn from(u: UsageWire) -> Self {
+        Usage {
+            input_tokens: u.input_tokens,
+            output_tokens: u.output_tokens,
+            cache_creation_input_tokens: u.cache_creation_input_tokens,
+            cache_read_input_tokens: u.cache_read_input_tokens,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct ContentBlock {
+    #[serde(rename = "type", default)]
+    block_type: Option<String>,
     text: Option<String>,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    input: Option<serde_json::Value>,
+}
+
+impl ContentBlock {
+    /// `None` unless this is a `tool_use` block, in which case it's the
+    /// `ToolCall` the model is requesting.
+    fn as_tool_use(&self) -> Option<ToolCall> {
+        if self.block_type.as_deref() != Some("tool_use") {
+            return None;
+        }
+        Some(ToolCall {
+            id: self.id.clone().unwrap_or_default(),
+            name: self.name.clone().unwrap_or_default(),
+            arguments: self.input.clone().unwrap_or(serde_json::Value::Null),
+        })
+    }
+}
+
+/// Anthropic's `anthropic-beta` header value enabling prompt caching.
+const PROMPT_CACHING_BETA: &str = "prompt-caching-2024-07-31";
+
+/// Build the `system` field as a cacheable content block when non-empty, so
+/// repeated calls that share the same system prompt (e.g. per-chunk calls in
+/// `chunker::explain_code`) only pay full input-token cost on the first one —
+/// subsequent calls hit Anthropic's prompt cache. A plain string is sent for
+/// an empty system prompt since there's nothing worth caching.
+fn system_block(system: &str) -> serde_json::Value {
+    if system.is_empty() {
+        serde_json::Value::String(String::new())
+    } else {
+        serde_json::json!([{
+            "type": "text",
+            "text": system,
+            "cache_control": { "type": "ephemeral" },
+        }])
+    }
+}
+
+/// Tag a retryable error message with the upstream `Retry-After` header (in
+/// seconds), when the response carried one, so the shared retry loop in
+/// `llm::generate_with_retry` can honor it instead of guessing with backoff.
+fn retry_after_suffix(resp: &reqwest::blocking::Response) -> String {
+    resp.headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(|secs| format!(" [retry_after={}]", secs))
+        .unwrap_or_default()
+}
+
+/// Splits a `Message` slice into Claude's top-level `system` string and its
+/// `messages` turns, folding tool calls/results into the `tool_use`/
+/// `tool_result` content blocks Claude expects (it has no `tool` role).
+fn build_conversation(messages: &[Message]) -> (String, Vec<serde_json::Value>) {
+    let mut system = String::new();
+    let mut turns = Vec::new();
+
+    for m in messages {
+        match m.role {
+            Role::System => {
+                if !system.is_empty() {
+                    system.push('\n');
+                }
+                system.push_str(&m.content);
+            }
+            Role::User => turns.push(serde_json::json!({ "role": "user", "content": m.content })),
+            Role::Assistant if m.tool_calls.is_empty() => {
+                turns.push(serde_json::json!({ "role": "assistant", "content": m.content }))
+            }
+            Role::Assistant => {
+                let mut blocks = Vec::new();
+                if !m.content.is_empty() {
+                    blocks.push(serde_json::json!({ "type": "text", "text": m.content }));
+                }
+                for call in &m.tool_calls {
+                    blocks.push(serde_json::json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.name,
+                        "input": call.arguments,
+                    }));
+                }
+                turns.push(serde_json::json!({ "role": "assistant", "content": blocks }));
+            }
+            Role::Tool => turns.push(serde_json::json!({
+                "role": "user",
+                "content": [{
+                    "type": "tool_result",
+                    "tool_use_id": m.tool_call_id.clone().unwrap_or_default(),
+                    "content": m.content,
+                }],
+            })),
+        }
+    }
+
+    (system, turns)
 }
 
 #[derive(Deserialize, Default)]
@@ -46,6 +178,18 @@ struct StreamEvent {
     event_type: String,
     #[serde(default)]
     delta: Option<StreamDelta>,
+    #[serde(default)]
+    message: Option<StreamMessage>,
+    #[serde(default)]
+    usage: Option<UsageWire>,
+}
+
+/// The `message` object on a `message_start` event — only its `usage` (input
+/// token count for the whole request) matters here.
+#[derive(Deserialize)]
+struct StreamMessage {
+    #[serde(default)]
+    usage: Option<UsageWire>,
 }
 
 #[derive(Deserialize)]
@@ -108,18 +252,27 @@ impl Provider for ClaudeProvider {
         "claude"
     }
 
+    fn model(&self) -> &str {
+        &self.model
+    }
+
     fn is_available(&self) -> bool {
         !self.api_key.is_empty()
     }
 
-    fn generate(&self, system_prompt: &str, user_prompt: &str, max_tokens: u32) -> Result<String> {
+    fn generate(&self, messages: &[Message], max_tokens: u32) -> Result<String> {
+        self.generate_with_usage(messages, max_tokens).map(|(text, _)| text)
+    }
+
+    fn generate_with_usage(&self, messages: &[Message], max_tokens: u32) -> Result<(String, Usage)> {
         self.validate()?;
 
+        let (system, turns) = build_conversation(messages);
         let body = serde_json::json!({
             "model": self.model,
             "max_tokens": max_tokens,
-            "system": system_prompt,
-            "messages": [{ "role": "user", "content": user_prompt }],
+            "system": system_block(&system),
+            "messages": turns,
             "temperature": 0.1,
         });
 
@@ -128,6 +281,7 @@ impl Provider for ClaudeProvider {
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
+            .header("anthropic-beta", PROMPT_CACHING_BETA)
             .header("Content-Type", "application/json")
             .json(&body)
             .send()
@@ -135,20 +289,22 @@ impl Provider for ClaudeProvider {
 
         let status = resp.status();
         if !status.is_success() {
+            let retry_after = retry_after_suffix(&resp);
             let text = resp.text().unwrap_or_default();
             if let Ok(err_resp) = serde_json::from_str::<ErrorResponse>(&text) {
                 if let Some(err) = err_resp.error {
                     let err_type = err.error_type.unwrap_or_default();
                     let msg = err.message.unwrap_or_default();
                     bail!(
-                        "Claude API error ({} {}): {}",
+                        "Claude API error ({} {}): {}{}",
                         status.as_u16(),
                         err_type,
-                        msg
+                        msg,
+                        retry_after
                     );
                 }
             }
-            bail!("Claude API error ({}): {}", status.as_u16(), text);
+            bail!("Claude API error ({}): {}{}", status.as_u16(), text, retry_after);
         }
 
         let msg: MessagesResponse = resp.json().context("Failed to parse Claude response")?;
@@ -163,6 +319,8 @@ impl Provider for ClaudeProvider {
             eprintln!("  ⚠ Response truncated (hit max_tokens)");
         }
 
+        let usage = msg.usage.map(Usage::from).unwrap_or_default();
+
         let content = msg
             .content
             .map(|blocks| {
@@ -179,23 +337,33 @@ impl Provider for ClaudeProvider {
             bail!("Claude returned empty response");
         }
 
-        Ok(trimmed.to_string())
+        Ok((trimmed.to_string(), usage))
     }
 
     fn generate_stream(
         &self,
-        system_prompt: &str,
-        user_prompt: &str,
+        messages: &[Message],
         max_tokens: u32,
         on_token: &mut dyn FnMut(&str),
     ) -> Result<String> {
+        self.generate_stream_with_usage(messages, max_tokens, on_token)
+            .map(|(text, _)| text)
+    }
+
+    fn generate_stream_with_usage(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<(String, Usage)> {
         self.validate()?;
 
+        let (system, turns) = build_conversation(messages);
         let body = serde_json::json!({
             "model": self.model,
             "max_tokens": max_tokens,
-            "system": system_prompt,
-            "messages": [{ "role": "user", "content": user_prompt }],
+            "system": system_block(&system),
+            "messages": turns,
             "temperature": 0.1,
             "stream": true,
         });
@@ -205,6 +373,7 @@ impl Provider for ClaudeProvider {
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
+            .header("anthropic-beta", PROMPT_CACHING_BETA)
             .header("Content-Type", "application/json")
             .json(&body)
             .send()
@@ -212,12 +381,14 @@ impl Provider for ClaudeProvider {
 
         let status = resp.status();
         if !status.is_success() {
+            let retry_after = retry_after_suffix(&resp);
             let text = resp.text().unwrap_or_default();
-            bail!("Claude API error ({}): {}", status.as_u16(), text);
+            bail!("Claude API error ({}): {}{}", status.as_u16(), text, retry_after);
         }
 
         let reader = BufReader::new(resp);
         let mut accumulated = String::new();
+        let mut usage = Usage::default();
 
         for line in reader.lines() {
             let line = match line {
@@ -239,6 +410,15 @@ impl Provider for ClaudeProvider {
             if let Some(data) = line.strip_prefix("data: ") {
                 if let Ok(event) = serde_json::from_str::<StreamEvent>(data) {
                     match event.event_type.as_str() {
+                        "message_start" => {
+                            if let Some(start_usage) =
+                                event.message.and_then(|m| m.usage).map(Usage::from)
+                            {
+                                usage.input_tokens = start_usage.input_tokens;
+                                usage.cache_creation_input_tokens = start_usage.cache_creation_input_tokens;
+                                usage.cache_read_input_tokens = start_usage.cache_read_input_tokens;
+                            }
+                        }
                         "content_block_delta" => {
                             if let Some(delta) = event.delta {
                                 if delta.delta_type.as_deref() == Some("text_delta") {
@@ -257,9 +437,12 @@ impl Provider for ClaudeProvider {
                                     eprintln!("\n  ⚠ Response truncated (hit max_tokens)");
                                 }
                             }
+                            if let Some(output_usage) = event.usage.map(Usage::from) {
+                                usage.output_tokens = output_usage.output_tokens;
+                            }
                         }
                         "message_stop" => break,
-                        _ => {} // Skip ping, message_start, content_block_start, etc.
+                        _ => {} // Skip ping, content_block_start, etc.
                     }
                 }
             }
@@ -269,7 +452,98 @@ impl Provider for ClaudeProvider {
             bail!("Claude returned empty streaming response");
         }
 
-        Ok(accumulated.trim().to_string())
+        Ok((accumulated.trim().to_string(), usage))
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: &[Message],
+        tools: &[ToolSpec],
+        max_tokens: u32,
+    ) -> Result<AgentTurn> {
+        self.validate()?;
+
+        let (system, turns) = build_conversation(messages);
+        let tool_defs: Vec<serde_json::Value> = tools
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "name": t.name,
+                    "description": t.description,
+                    "input_schema": t.parameters,
+                })
+            })
+            .collect();
+
+        let body = serde_json::json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "system": system_block(&system),
+            "messages": turns,
+            "temperature": 0.1,
+            "tools": tool_defs,
+        });
+
+        let resp = self
+            .client
+            .post("https://api.anthropic.com/v1/messages")
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("anthropic-beta", PROMPT_CACHING_BETA)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .context("Failed to call Claude API")?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let retry_after = retry_after_suffix(&resp);
+            let text = resp.text().unwrap_or_default();
+            if let Ok(err_resp) = serde_json::from_str::<ErrorResponse>(&text) {
+                if let Some(err) = err_resp.error {
+                    let err_type = err.error_type.unwrap_or_default();
+                    let msg = err.message.unwrap_or_default();
+                    bail!(
+                        "Claude API error ({} {}): {}{}",
+                        status.as_u16(),
+                        err_type,
+                        msg,
+                        retry_after
+                    );
+                }
+            }
+            bail!("Claude API error ({}): {}{}", status.as_u16(), text, retry_after);
+        }
+
+        let msg: MessagesResponse = resp.json().context("Failed to parse Claude response")?;
+
+        if let Some(err) = msg.error {
+            if let Some(emsg) = err.message {
+                bail!("Claude API error: {}", emsg);
+            }
+        }
+
+        if msg.stop_reason.as_deref() == Some("max_tokens") {
+            eprintln!("  ⚠ Response truncated (hit max_tokens)");
+        }
+
+        let blocks = msg.content.unwrap_or_default();
+        let tool_calls: Vec<ToolCall> = blocks.iter().filter_map(ContentBlock::as_tool_use).collect();
+
+        if tool_calls.is_empty() {
+            let content = blocks
+                .into_iter()
+                .filter_map(|b| b.text)
+                .collect::<Vec<_>>()
+                .join("\n");
+            let trimmed = content.trim();
+            if trimmed.is_empty() {
+                bail!("Claude returned empty response");
+            }
+            Ok(AgentTurn::Final(trimmed.to_string()))
+        } else {
+            Ok(AgentTurn::ToolCalls(tool_calls))
+        }
     }
 
     fn list_models(&self) -> Result<Vec<ModelInfo>> {
@@ -2,6 +2,9 @@ pub mod claude;
 pub mod ollama;
 pub mod openai_compat;
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
@@ -15,17 +18,156 @@ const MAX_RETRIES: u32 = 3;
 const RETRY_BASE_DELAY_MS: u64 = 500;
 const RETRY_MAX_DELAY_MS: u64 = 8000;
 
+/// Cooperative cancellation flag for a streaming generation. Cheap to clone
+/// and share between the thread driving a request and whatever triggers the
+/// cancellation (a Ctrl+C handler, an Esc keypress in the TUI, ...) — the
+/// streaming loop checks `is_aborted()` between tokens and unwinds cleanly
+/// with whatever text it has accumulated so far, rather than needing to kill
+/// the thread or the connection from the outside.
+#[derive(Debug, Clone, Default)]
+pub struct AbortSignal(Arc<AtomicBool>);
+
+impl AbortSignal {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Request that the in-flight stream stop at its next token check.
+    pub fn abort(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Installs a process-wide SIGINT (Ctrl+C) handler and returns the
+/// `AbortSignal` it sets on every press, so a long-running `niko explain`,
+/// `watch`, or `cmd` invocation can unwind its in-flight generation instead
+/// of relying solely on the 120s provider timeout.
+///
+/// Installing this handler replaces the default "exit immediately" Ctrl+C
+/// behavior with a cooperative one, so a *second* Ctrl+C forces an
+/// immediate exit (std::process::exit) rather than leaving the process
+/// stuck for however long a non-cancellable blocking call (tool-calling
+/// turns, a plain `generate_with_fallback`) takes to return on its own.
+///
+/// Safe to call once per process; if `ctrlc::set_handler` fails (a handler
+/// is already installed) the returned signal simply never gets aborted by
+/// this path.
+pub fn install_ctrlc_abort_handler() -> AbortSignal {
+    let abort = AbortSignal::new();
+    let abort_for_handler = abort.clone();
+    let _ = ctrlc::set_handler(move || {
+        if abort_for_handler.is_aborted() {
+            std::process::exit(130);
+        }
+        abort_for_handler.abort();
+    });
+    abort
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Role {
     System,
     User,
     Assistant,
+    /// The local result of executing a `ToolCall`, paired back to it via
+    /// `Message.tool_call_id`
+    Tool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Message {
     pub role: Role,
     pub content: String,
+    /// Set on an assistant message that requested tool calls, so a
+    /// follow-up turn can replay them verbatim — providers like Claude and
+    /// OpenAI correlate each `Role::Tool` result with one of these by id.
+    pub tool_calls: Vec<ToolCall>,
+    /// Set on a `Role::Tool` message: the `ToolCall.id` it's answering.
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: Role::System, content: content.into(), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: Role::User, content: content.into(), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: Role::Assistant, content: content.into(), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    /// An assistant turn that requested tool calls instead of (or alongside)
+    /// a text reply.
+    pub fn assistant_tool_calls(tool_calls: Vec<ToolCall>) -> Self {
+        Self { role: Role::Assistant, content: String::new(), tool_calls, tool_call_id: None }
+    }
+
+    /// The local result of running `tool_call_id`, fed back as the next turn.
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Tool,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: Some(tool_call_id.into()),
+        }
+    }
+}
+
+/// A local tool/function the model may invoke mid-conversation, described as
+/// a JSON schema for its arguments (OpenAI function-calling shape). Passed to
+/// `Provider::generate_with_tools`; the caller executes whatever `ToolCall`s
+/// come back and feeds the results in as `Message::tool_result`s.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// A tool invocation the model asked for.
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// Outcome of one `Provider::generate_with_tools` turn: either the model
+/// settled on a final answer, or it wants one or more local tools run before
+/// it can continue.
+#[derive(Debug, Clone)]
+pub enum AgentTurn {
+    Final(String),
+    ToolCalls(Vec<ToolCall>),
+}
+
+/// Token usage reported by a provider for one `generate`/`generate_stream`
+/// call. Not every provider's API surfaces this — those report all-zero
+/// counts via the `Provider` trait's default `generate_with_usage`. The
+/// `cache_*` fields are Anthropic prompt-caching specific and stay zero for
+/// providers (or requests) that don't use a cached system prompt.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
 }
 
 /// Trait for all LLM providers
@@ -33,6 +175,10 @@ pub trait Provider: Send + Sync {
     /// Provider name
     fn name(&self) -> &str;
 
+    /// The configured model this provider talks to — used e.g. by
+    /// `cache` to key cached LLM answers so a model change invalidates them.
+    fn model(&self) -> &str;
+
     /// Generate a response (non-streaming)
     fn generate(&self, messages: &[Message], max_tokens: u32) -> Result<String>;
 
@@ -49,6 +195,53 @@ pub trait Provider: Send + Sync {
         Ok(result)
     }
 
+    /// Like `generate`, but offers `tools` to the model and lets it request
+    /// local tool execution (a `ToolCall`) instead of a final answer. The
+    /// default implementation bails with a clear message, so callers can
+    /// gracefully fall back to plain `generate` for providers that don't
+    /// advertise this capability.
+    fn generate_with_tools(
+        &self,
+        _messages: &[Message],
+        _tools: &[ToolSpec],
+        _max_tokens: u32,
+    ) -> Result<AgentTurn> {
+        bail!("{} does not support tool calling", self.name())
+    }
+
+    /// Like `generate`, but also returns token usage when the provider's API
+    /// reports it. Default: calls `generate` and reports zero usage, for
+    /// providers whose API doesn't surface token counts.
+    fn generate_with_usage(&self, messages: &[Message], max_tokens: u32) -> Result<(String, Usage)> {
+        Ok((self.generate(messages, max_tokens)?, Usage::default()))
+    }
+
+    /// Streaming counterpart of `generate_with_usage`.
+    fn generate_stream_with_usage(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<(String, Usage)> {
+        Ok((self.generate_stream(messages, max_tokens, on_token)?, Usage::default()))
+    }
+
+    /// Like `generate_stream`, but checks `abort` before each token is
+    /// delivered and, once set, stops reading and returns the text
+    /// accumulated so far instead of running the response to completion.
+    /// Default: ignores `abort` and runs `generate_stream` uninterruptibly,
+    /// for providers that haven't wired up a cancellable read loop.
+    fn generate_stream_cancellable(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        abort: &AbortSignal,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        let _ = abort;
+        self.generate_stream(messages, max_tokens, on_token)
+    }
+
     /// Check if the provider is available
     fn is_available(&self) -> bool;
 
@@ -56,6 +249,14 @@ pub trait Provider: Send + Sync {
     fn list_models(&self) -> Result<Vec<ModelInfo>>;
 }
 
+/// Capability for providers that can produce text embeddings. Not every
+/// `Provider` supports this (most hosted chat APIs this crate talks to
+/// don't), so it's a separate trait rather than a method on `Provider`.
+pub trait EmbeddingProvider {
+    /// Embed each input text, returning one vector per input in input order.
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
 /// Information about an available model
 #[derive(Debug, Clone)]
 pub struct ModelInfo {
@@ -80,7 +281,7 @@ impl std::fmt::Display for ModelInfo {
 
 // ─── Retry wrapper (non-streaming) ──────────────────────────────────────────
 
-fn is_retryable_error(err: &anyhow::Error) -> bool {
+pub(crate) fn is_retryable_error(err: &anyhow::Error) -> bool {
     let msg = format!("{:#}", err).to_lowercase();
     msg.contains("connection")
         || msg.contains("timeout")
@@ -94,13 +295,30 @@ fn is_retryable_error(err: &anyhow::Error) -> bool {
         || msg.contains("502")
         || msg.contains("503")
         || msg.contains("504")
+        || msg.contains("529")
         || msg.contains("429")
         || msg.contains("rate limit")
         || msg.contains("too many requests")
+        || msg.contains("overloaded")
         || msg.contains("model is loading")
         || msg.contains("model loading")
 }
 
+/// Marker a `Provider` impl can append to a retryable error's message (see
+/// `retry_after_suffix` helpers in e.g. `claude.rs`) when the upstream API
+/// sent a `Retry-After` header, so the retry loop can honor it instead of
+/// guessing with exponential backoff.
+const RETRY_AFTER_MARKER: &str = "[retry_after=";
+
+/// Parse a `retry_after_suffix`-style `" [retry_after=<seconds>]"` tag off
+/// the end of an error message, if one is present.
+fn retry_after_hint(err: &anyhow::Error) -> Option<Duration> {
+    let msg = format!("{:#}", err);
+    let start = msg.find(RETRY_AFTER_MARKER)? + RETRY_AFTER_MARKER.len();
+    let end = start + msg[start..].find(']')?;
+    msg[start..end].parse::<u64>().ok().map(Duration::from_secs)
+}
+
 /// Non-streaming generate with retry — used for cmd mode and synthesis steps
 pub fn generate_with_retry(
     provider: &dyn Provider,
@@ -134,7 +352,7 @@ pub fn generate_with_retry(
             }
             Err(e) => {
                 if attempt < MAX_RETRIES && is_retryable_error(&e) {
-                    let delay = retry_delay(attempt);
+                    let delay = retry_after_hint(&e).unwrap_or_else(|| retry_delay(attempt));
                     eprintln!(
                         "  ↻ {}, retrying in {:.1}s… ({}/{})",
                         summarize_error(&e),
@@ -154,6 +372,87 @@ pub fn generate_with_retry(
     Err(last_err.unwrap_or_else(|| anyhow::anyhow!("All retry attempts exhausted")))
 }
 
+/// Like `generate_with_retry`, but also returns the provider's reported
+/// token usage (all-zero for providers that don't surface it) — used by
+/// `chunker::explain_code` to total up cost across a multi-chunk run.
+pub fn generate_with_retry_usage(
+    provider: &dyn Provider,
+    messages: &[Message],
+    max_tokens: u32,
+) -> Result<(String, Usage)> {
+    let mut last_err = None;
+
+    for attempt in 0..=MAX_RETRIES {
+        match provider.generate_with_usage(messages, max_tokens) {
+            Ok((response, usage)) => {
+                let trimmed = response.trim();
+                if trimmed.is_empty() {
+                    if attempt < MAX_RETRIES {
+                        let delay = retry_delay(attempt);
+                        eprintln!(
+                            "  ↻ Empty response, retrying in {:.1}s… ({}/{})",
+                            delay.as_secs_f64(),
+                            attempt + 1,
+                            MAX_RETRIES
+                        );
+                        thread::sleep(delay);
+                        continue;
+                    }
+                    bail!(
+                        "Provider returned empty response after {} attempts",
+                        MAX_RETRIES + 1
+                    );
+                }
+                return Ok((trimmed.to_string(), usage));
+            }
+            Err(e) => {
+                if attempt < MAX_RETRIES && is_retryable_error(&e) {
+                    let delay = retry_after_hint(&e).unwrap_or_else(|| retry_delay(attempt));
+                    eprintln!(
+                        "  ↻ {}, retrying in {:.1}s… ({}/{})",
+                        summarize_error(&e),
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        MAX_RETRIES
+                    );
+                    thread::sleep(delay);
+                    last_err = Some(e);
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("All retry attempts exhausted")))
+}
+
+/// Usage-returning counterpart of `generate_streaming`.
+pub fn generate_streaming_usage(
+    provider: &dyn Provider,
+    messages: &[Message],
+    max_tokens: u32,
+    on_token: &mut dyn FnMut(&str),
+) -> Result<(String, Usage)> {
+    match provider.generate_stream_with_usage(messages, max_tokens, on_token) {
+        Ok((result, usage)) => {
+            let trimmed = result.trim();
+            if trimmed.is_empty() {
+                bail!("Provider returned empty response");
+            }
+            Ok((trimmed.to_string(), usage))
+        }
+        Err(e) => {
+            if is_retryable_error(&e) {
+                eprintln!("  ↻ Stream failed, retrying without streaming…");
+                generate_with_retry_usage(provider, messages, max_tokens)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
 /// Streaming generate — no retry once tokens start flowing.
 /// Retries only on initial connection failure (before any tokens arrive).
 pub fn generate_streaming(
@@ -183,6 +482,31 @@ pub fn generate_streaming(
     }
 }
 
+/// Cancellable counterpart of `generate_streaming`. Same initial-connection
+/// retry behavior; additionally, an `abort()` called mid-stream unwinds the
+/// read loop and returns whatever text had accumulated — that's a deliberate
+/// stop, not a failure, so it's returned as `Ok` rather than an error, same
+/// as a normal completed stream.
+pub fn generate_streaming_cancellable(
+    provider: &dyn Provider,
+    messages: &[Message],
+    max_tokens: u32,
+    abort: &AbortSignal,
+    on_token: &mut dyn FnMut(&str),
+) -> Result<String> {
+    match provider.generate_stream_cancellable(messages, max_tokens, abort, on_token) {
+        Ok(result) => Ok(result.trim().to_string()),
+        Err(e) => {
+            if is_retryable_error(&e) && !abort.is_aborted() {
+                eprintln!("  ↻ Stream failed, retrying without streaming…");
+                generate_with_retry(provider, messages, max_tokens)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
 fn retry_delay(attempt: u32) -> Duration {
     let base_ms = RETRY_BASE_DELAY_MS * 2u64.pow(attempt);
     let delay_ms = base_ms.min(RETRY_MAX_DELAY_MS);
@@ -217,7 +541,7 @@ pub fn from_config(name: &str, pcfg: &ProviderConfig) -> Result<Box<dyn Provider
             Ok(Box::new(ollama::OllamaProvider::new(
                 base_url,
                 &pcfg.model,
-                pcfg.options.clone(),
+                ollama::options_from_config(pcfg),
             )?))
         }
         "openai_compat" => Ok(Box::new(openai_compat::OpenAICompatProvider::new(
@@ -225,6 +549,8 @@ pub fn from_config(name: &str, pcfg: &ProviderConfig) -> Result<Box<dyn Provider
             &pcfg.api_key,
             &pcfg.base_url,
             &pcfg.model,
+            pcfg.context_window,
+            &pcfg.options,
         ))),
         "anthropic" => Ok(Box::new(claude::ClaudeProvider::new(
             &pcfg.api_key,
@@ -262,6 +588,165 @@ pub fn get_provider(override_name: Option<&str>) -> Result<Box<dyn Provider>> {
     }
 }
 
+/// Resolve the provider name `get_provider_for_role` would pick, without
+/// constructing it — used by callers that need the name up front (e.g. to
+/// drive `generate_with_fallback`'s fallback chain).
+pub fn provider_name_for_role(
+    provider_override: Option<&str>,
+    role: Option<&config::RoleConfig>,
+) -> Result<String> {
+    let cfg = config::load()?;
+    Ok(provider_override
+        .map(String::from)
+        .or_else(|| role.and_then(|r| r.provider.clone()))
+        .unwrap_or(cfg.active_provider))
+}
+
+/// Resolve a provider the way `modes::cmd`/`modes::explain` need to when a
+/// `--role` preset is in play: an explicit `--provider` always wins, then the
+/// role's own provider override, then the active provider — with the role's
+/// `model` override (if any) layered onto whichever provider config is picked.
+pub fn get_provider_for_role(
+    provider_override: Option<&str>,
+    role: Option<&config::RoleConfig>,
+) -> Result<Box<dyn Provider>> {
+    let cfg = config::load()?;
+    let name = provider_override
+        .map(String::from)
+        .or_else(|| role.and_then(|r| r.provider.clone()))
+        .unwrap_or_else(|| cfg.active_provider.clone());
+
+    let mut pcfg = cfg.providers.get(&name).cloned().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Provider '{}' not configured.\nRun 'niko settings configure' to add it.",
+            name
+        )
+    })?;
+
+    if let Some(model) = role.and_then(|r| r.model.clone()) {
+        pcfg.model = model;
+    }
+
+    from_config(&name, &pcfg)
+}
+
+/// Build the ordered provider-name chain starting at `name` and followed by
+/// its configured `fallbacks` (deduplicated), as used by both
+/// `generate_with_fallback` and `resolve_available_provider`.
+fn fallback_chain(name: &str, providers: &HashMap<String, ProviderConfig>) -> Vec<String> {
+    let mut chain = vec![name.to_string()];
+    if let Some(pcfg) = providers.get(name) {
+        for fallback in &pcfg.fallbacks {
+            if !chain.contains(fallback) {
+                chain.push(fallback.clone());
+            }
+        }
+    }
+    chain
+}
+
+/// Resolve the first available provider in `name`'s fallback chain, without
+/// performing any generation — used by callers (like `modes::explain`) that
+/// need a live `Provider` up front for multiple subsequent calls, rather than
+/// wrapping a single `generate_with_retry` call.
+pub fn resolve_available_provider(name: &str) -> Result<(String, Box<dyn Provider>)> {
+    let cfg = config::load()?;
+    let chain = fallback_chain(name, &cfg.providers);
+
+    let mut last_err = None;
+    for (i, pname) in chain.iter().enumerate() {
+        let pcfg = match cfg.providers.get(pname) {
+            Some(pcfg) => pcfg,
+            None => {
+                last_err = Some(anyhow::anyhow!("Provider '{}' not configured", pname));
+                continue;
+            }
+        };
+        let provider = match from_config(pname, pcfg) {
+            Ok(p) => p,
+            Err(e) => {
+                last_err = Some(e);
+                continue;
+            }
+        };
+        if !provider.is_available() {
+            last_err = Some(anyhow::anyhow!("Provider '{}' not available", pname));
+            continue;
+        }
+        if i > 0 {
+            eprintln!("  ↻ '{}' unavailable, switched to fallback provider '{}'", name, pname);
+        }
+        return Ok((pname.clone(), provider));
+    }
+
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("Provider '{}' not configured", name)))
+}
+
+/// Non-streaming generate with automatic provider fallback: tries `name`,
+/// and on a retryable error (per `is_retryable_error`) or unavailability
+/// (per `Provider::is_available`), walks `name`'s configured `fallbacks` in
+/// order, constructing each via `from_config` and retrying there. Returns
+/// the first success, or the last error if every provider in the chain is
+/// exhausted.
+pub fn generate_with_fallback(
+    name: &str,
+    messages: &[Message],
+    max_tokens: u32,
+    verbose: bool,
+) -> Result<String> {
+    let cfg = config::load()?;
+    let chain = fallback_chain(name, &cfg.providers);
+
+    let mut last_err = None;
+
+    for (i, pname) in chain.iter().enumerate() {
+        let pcfg = match cfg.providers.get(pname) {
+            Some(pcfg) => pcfg,
+            None => {
+                last_err = Some(anyhow::anyhow!("Provider '{}' not configured", pname));
+                continue;
+            }
+        };
+
+        let provider = match from_config(pname, pcfg) {
+            Ok(p) => p,
+            Err(e) => {
+                last_err = Some(e);
+                continue;
+            }
+        };
+
+        if !provider.is_available() {
+            if verbose && i > 0 {
+                eprintln!("  ↻ fallback '{}' not available, trying next…", pname);
+            }
+            last_err = Some(anyhow::anyhow!("Provider '{}' not available", pname));
+            continue;
+        }
+
+        if i > 0 {
+            if verbose {
+                eprintln!("  ↻ switched to fallback provider '{}'", pname);
+            } else {
+                eprintln!("  ↻ '{}' unavailable, falling back to '{}'…", name, pname);
+            }
+        }
+
+        match generate_with_retry(provider.as_ref(), messages, max_tokens) {
+            Ok(text) => return Ok(text),
+            Err(e) => {
+                let retryable = is_retryable_error(&e);
+                last_err = Some(e);
+                if !retryable {
+                    break;
+                }
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("No providers available")))
+}
+
 // ─── Helpers ────────────────────────────────────────────────────────────────
 
 pub fn estimate_param_billions(model_name: &str, size_bytes: u64) -> f64 {
@@ -310,4 +795,26 @@ mod tests {
         assert!(model_fits_in_ram(0.0));
         assert!(model_fits_in_ram(-1.0));
     }
+
+    #[test]
+    fn tool_result_message_carries_its_call_id() {
+        let msg = Message::tool_result("call_1", "42 files");
+        assert_eq!(msg.role, Role::Tool);
+        assert_eq!(msg.tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(msg.content, "42 files");
+        assert!(msg.tool_calls.is_empty());
+    }
+
+    #[test]
+    fn assistant_tool_calls_message_has_empty_text_content() {
+        let calls = vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "list_files".to_string(),
+            arguments: serde_json::json!({ "path": "." }),
+        }];
+        let msg = Message::assistant_tool_calls(calls);
+        assert_eq!(msg.role, Role::Assistant);
+        assert!(msg.content.is_empty());
+        assert_eq!(msg.tool_calls.len(), 1);
+    }
 }
@@ -1,16 +1,78 @@
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
 use std::process::Command;
-use std::time::Duration;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, bail};
 use serde::Deserialize;
 
-use crate::llm::{Provider, ModelInfo, estimate_param_billions};
+use crate::llm::{
+    AgentTurn, EmbeddingProvider, Message, ModelInfo, Provider, Role, ToolCall as LlmToolCall,
+    ToolSpec, estimate_param_billions,
+};
+
+/// Bounded retry attempts for transient Ollama HTTP failures, separate from
+/// `crate::llm`'s generic provider-level retry wrapper — this one guards the
+/// raw HTTP call itself, before a response ever reaches that layer.
+const OLLAMA_MAX_RETRIES: u32 = 3;
+const OLLAMA_RETRY_BASE_DELAY_MS: u64 = 300;
+const OLLAMA_RETRY_MAX_DELAY_MS: u64 = 4000;
+
+/// How long `generate_stream_with_status` waits with no first token before
+/// reporting "loading model into memory…" — Ollama stays silent the whole
+/// time it's loading a cold model, so this is purely a UX signal.
+const MODEL_LOAD_STATUS_DELAY: Duration = Duration::from_secs(3);
+
+fn ollama_retry_delay(attempt: u32) -> Duration {
+    let base_ms = OLLAMA_RETRY_BASE_DELAY_MS * 2u64.pow(attempt);
+    let delay_ms = base_ms.min(OLLAMA_RETRY_MAX_DELAY_MS);
+    let jitter = delay_ms / 10;
+    Duration::from_millis(delay_ms + jitter)
+}
 
 pub struct OllamaProvider {
     base_url: String,
     model: String,
     client: reqwest::blocking::Client,
+    /// Bearer token for Ollama instances sitting behind an authenticating
+    /// reverse proxy or tunnel, set via the `auth_token` provider option
+    auth_token: Option<String>,
+    /// Arbitrary extra headers, set via `header:<Name>` provider options
+    extra_headers: Vec<(String, String)>,
+    /// Minimum gap between outbound requests, set via the
+    /// `max_requests_per_second` provider option (`None` disables throttling)
+    min_request_interval: Option<Duration>,
+    last_request_at: Mutex<Instant>,
+    /// User overrides for `num_ctx` and sampling parameters, set via the
+    /// `num_ctx`/`temperature`/`top_p`/`top_k`/`repeat_penalty` provider
+    /// options; any left unset fall back to the adaptive defaults
+    sampling: SamplingOptions,
+}
+
+/// User-overridable generation options, parsed from `ProviderConfig.options`.
+/// Every field falls back to `build_chat_body`'s adaptive default when unset,
+/// since Ollama exposes no API to discover a model's real context window.
+#[derive(Debug, Clone, Default)]
+struct SamplingOptions {
+    num_ctx: Option<u32>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    top_k: Option<u32>,
+    repeat_penalty: Option<f64>,
+}
+
+impl SamplingOptions {
+    fn from_options(options: &HashMap<String, String>) -> Self {
+        Self {
+            num_ctx: options.get("num_ctx").and_then(|v| v.parse().ok()),
+            temperature: options.get("temperature").and_then(|v| v.parse().ok()),
+            top_p: options.get("top_p").and_then(|v| v.parse().ok()),
+            top_k: options.get("top_k").and_then(|v| v.parse().ok()),
+            repeat_penalty: options.get("repeat_penalty").and_then(|v| v.parse().ok()),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -18,9 +80,24 @@ struct ChatResponse {
     message: Option<ChatMessage>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct ChatMessage {
+    #[serde(default)]
     content: String,
+    #[serde(default)]
+    tool_calls: Vec<RawToolCall>,
+}
+
+#[derive(Deserialize)]
+struct RawToolCall {
+    function: RawFunctionCall,
+}
+
+#[derive(Deserialize)]
+struct RawFunctionCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
 }
 
 /// Streaming response — one JSON object per line
@@ -49,6 +126,11 @@ struct OllamaModel {
     size: u64,
 }
 
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    embedding: Option<Vec<f32>>,
+}
+
 #[derive(Deserialize)]
 struct PullProgress {
     status: Option<String>,
@@ -56,8 +138,66 @@ struct PullProgress {
     total: Option<u64>,
 }
 
+/// Converts a `Message` slice into Ollama's `/api/chat` `messages` array,
+/// including assistant `tool_calls` and `tool`-role results. Ollama's
+/// `tool`-role entries expect the calling function's `name`, which
+/// `Message::tool_result` doesn't carry directly — so we track it from the
+/// preceding assistant turn's `tool_calls` by id as we go.
+fn messages_to_json(messages: &[Message]) -> serde_json::Value {
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    let out: Vec<serde_json::Value> = messages
+        .iter()
+        .map(|m| match m.role {
+            Role::System => serde_json::json!({ "role": "system", "content": m.content }),
+            Role::User => serde_json::json!({ "role": "user", "content": m.content }),
+            Role::Assistant if m.tool_calls.is_empty() => {
+                serde_json::json!({ "role": "assistant", "content": m.content })
+            }
+            Role::Assistant => {
+                let calls: Vec<serde_json::Value> = m
+                    .tool_calls
+                    .iter()
+                    .map(|c| {
+                        call_names.insert(c.id.clone(), c.name.clone());
+                        serde_json::json!({ "function": { "name": c.name, "arguments": c.arguments } })
+                    })
+                    .collect();
+                serde_json::json!({ "role": "assistant", "content": m.content, "tool_calls": calls })
+            }
+            Role::Tool => {
+                let name = m
+                    .tool_call_id
+                    .as_ref()
+                    .and_then(|id| call_names.get(id))
+                    .cloned()
+                    .unwrap_or_default();
+                serde_json::json!({ "role": "tool", "name": name, "content": m.content })
+            }
+        })
+        .collect();
+    serde_json::Value::Array(out)
+}
+
+/// Builds the options map `OllamaProvider::new` expects from a stored
+/// `ProviderConfig`, folding in the structured fields it doesn't carry as
+/// generic `options` itself: `api_key` becomes the `auth_token` option (so a
+/// key saved via `niko settings configure` authenticates the same way a
+/// manually-set `auth_token` option already does, for Ollama servers behind
+/// an authenticating reverse proxy or hosted endpoint), and `context_window`
+/// becomes `num_ctx` (Ollama has no API to report a model's built-in size).
+pub fn options_from_config(pcfg: &crate::config::ProviderConfig) -> HashMap<String, String> {
+    let mut options = pcfg.options.clone();
+    if !pcfg.api_key.is_empty() {
+        options.insert("auth_token".to_string(), pcfg.api_key.clone());
+    }
+    if let Some(ctx) = pcfg.context_window {
+        options.insert("num_ctx".to_string(), ctx.to_string());
+    }
+    options
+}
+
 impl OllamaProvider {
-    pub fn new(base_url: &str, model: &str) -> Result<Self> {
+    pub fn new(base_url: &str, model: &str, options: HashMap<String, String>) -> Result<Self> {
         let client = reqwest::blocking::Client::builder()
             .timeout(Duration::from_secs(300))
             .connect_timeout(Duration::from_secs(5))
@@ -67,20 +207,121 @@ impl OllamaProvider {
             .build()
             .context("Failed to create HTTP client")?;
 
+        let auth_token = options.get("auth_token").filter(|t| !t.is_empty()).cloned();
+        let extra_headers = options
+            .iter()
+            .filter_map(|(k, v)| {
+                k.strip_prefix("header:")
+                    .map(|name| (name.to_string(), v.clone()))
+            })
+            .collect();
+        let min_request_interval = options
+            .get("max_requests_per_second")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(1.0 / rate));
+        let sampling = SamplingOptions::from_options(&options);
+
         Ok(Self {
             base_url: base_url.trim_end_matches('/').to_string(),
             model: model.to_string(),
             client,
+            auth_token,
+            extra_headers,
+            min_request_interval,
+            last_request_at: Mutex::new(Instant::now() - Duration::from_secs(3600)),
+            sampling,
         })
     }
 
+    /// Block until at least `min_request_interval` has elapsed since the
+    /// last outbound call, to avoid hammering shared/remote Ollama servers.
+    /// A no-op when throttling is disabled (`max_requests_per_second <= 0`).
+    fn throttle(&self) {
+        let Some(interval) = self.min_request_interval else {
+            return;
+        };
+        let mut last = self.last_request_at.lock().unwrap();
+        let elapsed = last.elapsed();
+        if elapsed < interval {
+            std::thread::sleep(interval - elapsed);
+        }
+        *last = Instant::now();
+    }
+
+    /// Attach the bearer token (if configured) and any extra headers to an
+    /// outgoing request so `/api/tags`, `/api/chat`, and `/api/pull` all
+    /// authenticate the same way against a remote/proxied Ollama instance.
+    fn with_auth(
+        &self,
+        mut builder: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        if let Some(token) = &self.auth_token {
+            builder = builder.bearer_auth(token);
+        }
+        for (name, value) in &self.extra_headers {
+            builder = builder.header(name, value);
+        }
+        builder
+    }
+
+    /// Send a freshly-built request with bounded exponential-backoff retry
+    /// on connection/timeout errors and 5xx/429 responses, plus jitter.
+    /// 4xx responses are returned immediately for the caller to report, and
+    /// once a response comes back (retryable-exhausted or not) it's handed
+    /// back as-is so existing status/body handling is unchanged.
+    fn send_with_retry(
+        &self,
+        mut make_request: impl FnMut() -> reqwest::blocking::RequestBuilder,
+        unavailable_msg: &str,
+    ) -> Result<reqwest::blocking::Response> {
+        for attempt in 0..=OLLAMA_MAX_RETRIES {
+            self.throttle();
+            match make_request().send() {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let retryable = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    if status.is_success() || !retryable || attempt == OLLAMA_MAX_RETRIES {
+                        return Ok(resp);
+                    }
+                    let delay = ollama_retry_delay(attempt);
+                    eprintln!(
+                        "  ↻ Ollama returned {}, retrying in {:.1}s… ({}/{})",
+                        status,
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        OLLAMA_MAX_RETRIES
+                    );
+                    std::thread::sleep(delay);
+                }
+                Err(e) if (e.is_connect() || e.is_timeout()) && attempt < OLLAMA_MAX_RETRIES => {
+                    let delay = ollama_retry_delay(attempt);
+                    eprintln!(
+                        "  ↻ {}, retrying in {:.1}s… ({}/{})",
+                        unavailable_msg,
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        OLLAMA_MAX_RETRIES
+                    );
+                    std::thread::sleep(delay);
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => bail!("{}", unavailable_msg),
+                Err(e) => bail!("Failed to call Ollama: {}", e),
+            }
+        }
+        unreachable!("loop always returns or bails by the final attempt")
+    }
+
     fn is_server_running(&self) -> bool {
-        self.client
-            .get(format!("{}/api/tags", self.base_url))
-            .timeout(Duration::from_secs(2))
-            .send()
-            .map(|r| r.status().is_success())
-            .unwrap_or(false)
+        self.with_auth(
+            self.client
+                .get(format!("{}/api/tags", self.base_url))
+                .timeout(Duration::from_secs(2)),
+        )
+        .send()
+        .map(|r| r.status().is_success())
+        .unwrap_or(false)
     }
 
     fn has_model(&self, model: &str) -> bool {
@@ -92,11 +333,16 @@ impl OllamaProvider {
     }
 
     fn fetch_local_models(&self) -> Result<Vec<ModelInfo>> {
-        let resp = self.client
-            .get(format!("{}/api/tags", self.base_url))
-            .timeout(Duration::from_secs(5))
-            .send()
-            .context("Failed to connect to Ollama")?;
+        let resp = self.send_with_retry(
+            || {
+                self.with_auth(
+                    self.client
+                        .get(format!("{}/api/tags", self.base_url))
+                        .timeout(Duration::from_secs(5)),
+                )
+            },
+            "Failed to connect to Ollama",
+        )?;
 
         if !resp.status().is_success() {
             bail!("Ollama API returned status: {}", resp.status());
@@ -117,12 +363,16 @@ impl OllamaProvider {
 
     pub fn pull_model(&self, model: &str) -> Result<()> {
         eprintln!("  Downloading '{}'...", model);
+        self.throttle();
 
         let body = serde_json::json!({ "name": model, "stream": true });
-        let resp = self.client
-            .post(format!("{}/api/pull", self.base_url))
-            .json(&body)
-            .timeout(Duration::from_secs(7200))
+        let resp = self
+            .with_auth(
+                self.client
+                    .post(format!("{}/api/pull", self.base_url))
+                    .json(&body)
+                    .timeout(Duration::from_secs(7200)),
+            )
             .send()
             .context("Failed to start model download")?;
 
@@ -172,48 +422,266 @@ impl OllamaProvider {
     }
 
     /// Build the request body with performance optimizations
-    fn build_request_body(&self, system_prompt: &str, user_prompt: &str, max_tokens: u32, stream: bool) -> serde_json::Value {
-        // Adaptive context window based on input size
-        let total_chars = system_prompt.len() + user_prompt.len();
-        let num_ctx = if total_chars > 50_000 {
-            16384
-        } else if total_chars > 20_000 {
-            8192
-        } else {
-            4096
-        };
+    fn build_request_body(&self, messages: &[Message], max_tokens: u32, stream: bool) -> serde_json::Value {
+        let total_chars: usize = messages.iter().map(|m| m.content.len()).sum();
+        self.build_chat_body(messages_to_json(messages), &[], total_chars, max_tokens, stream)
+    }
+
+    /// Shared request-body builder for both the plain `generate*` methods and
+    /// `generate_with_tools`. `messages` is a pre-built JSON array (rather
+    /// than `&[Message]`) so `continue`-style follow-up turns can splice in
+    /// entries that `messages_to_json` already encoded. `tools` is only
+    /// included in the body when non-empty, since Ollama rejects some older
+    /// models' chat endpoint if `tools` is present but empty.
+    fn build_chat_body(
+        &self,
+        messages: serde_json::Value,
+        tools: &[ToolSpec],
+        total_chars: usize,
+        max_tokens: u32,
+        stream: bool,
+    ) -> serde_json::Value {
+        // Adaptive context window based on input size, unless the user has
+        // set an explicit override (the only way to use the full window of
+        // a large-context model, since Ollama exposes no max-context API).
+        let num_ctx = self.sampling.num_ctx.unwrap_or_else(|| {
+            if total_chars > 50_000 {
+                16384
+            } else if total_chars > 20_000 {
+                8192
+            } else {
+                4096
+            }
+        });
 
-        serde_json::json!({
+        let mut body = serde_json::json!({
             "model": self.model,
-            "messages": [
-                { "role": "system", "content": system_prompt },
-                { "role": "user", "content": user_prompt }
-            ],
+            "messages": messages,
             "stream": stream,
             "keep_alive": "30m",
             "options": {
-                "temperature": 0.1,
+                "temperature": self.sampling.temperature.unwrap_or(0.1),
                 "num_predict": max_tokens,
                 "num_ctx": num_ctx,
-                "top_p": 0.7,
-                "top_k": 20,
-                "repeat_penalty": 1.2,
+                "top_p": self.sampling.top_p.unwrap_or(0.7),
+                "top_k": self.sampling.top_k.unwrap_or(20),
+                "repeat_penalty": self.sampling.repeat_penalty.unwrap_or(1.2),
                 "flash_attn": true
             }
-        })
+        });
+
+        if !tools.is_empty() {
+            let tool_defs: Vec<serde_json::Value> = tools
+                .iter()
+                .map(|t| {
+                    serde_json::json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.parameters,
+                        }
+                    })
+                })
+                .collect();
+            body["tools"] = serde_json::json!(tool_defs);
+        }
+
+        body
+    }
+
+    /// One-shot, non-streaming chat turn that offers `tools` to the model and
+    /// returns either its final text or the tool calls it asked for. The
+    /// caller is expected to execute those calls locally, append the results
+    /// as `Message::tool_result`s, and call this again.
+    fn chat_with_tools(
+        &self,
+        messages: &[Message],
+        tools: &[ToolSpec],
+        max_tokens: u32,
+    ) -> Result<AgentTurn> {
+        self.ensure_model_available().map_err(|e| {
+            anyhow::anyhow!("{}", e)
+        })?;
+
+        let total_chars: usize = messages.iter().map(|m| m.content.len()).sum();
+        let body = self.build_chat_body(messages_to_json(messages), tools, total_chars, max_tokens, false);
+        let unavailable_msg = format!(
+            "Ollama is not running at {}.\nStart it with: ollama serve",
+            self.base_url
+        );
+        let resp = self.send_with_retry(
+            || self.with_auth(self.client.post(format!("{}/api/chat", self.base_url)).json(&body)),
+            &unavailable_msg,
+        )?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().unwrap_or_default();
+            bail!("Ollama returned {}: {}", status, text);
+        }
+
+        let chat: ChatResponse = resp.json().context("Failed to parse Ollama response")?;
+        let message = chat.message.unwrap_or_default();
+
+        if message.tool_calls.is_empty() {
+            let trimmed = message.content.trim();
+            if trimmed.is_empty() {
+                bail!("Ollama returned empty response");
+            }
+            Ok(AgentTurn::Final(trimmed.to_string()))
+        } else {
+            let calls = message
+                .tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, raw)| LlmToolCall {
+                    // Ollama's /api/chat doesn't assign tool-call ids, so we
+                    // synthesize one stable enough to round-trip through a
+                    // single `generate_with_tools` turn.
+                    id: format!("call_{}", i),
+                    name: raw.function.name,
+                    arguments: raw.function.arguments,
+                })
+                .collect();
+            Ok(AgentTurn::ToolCalls(calls))
+        }
+    }
+
+    /// Like the `Provider::generate_stream` impl below, but also reports a
+    /// "loading model into memory…" status through `on_status` if
+    /// `MODEL_LOAD_STATUS_DELAY` elapses with no first token. Ollama gives no
+    /// feedback while a cold model loads into memory, so without this the
+    /// first request after startup looks hung. `on_status("")` is called to
+    /// clear the message once the first token (or stream end) arrives.
+    pub fn generate_stream_with_status(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        on_token: &mut dyn FnMut(&str),
+        on_status: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        self.ensure_model_available().map_err(|e| {
+            if format!("{:#}", e).contains("connect") {
+                anyhow::anyhow!(
+                    "Ollama is not running at {}.\nStart it with: ollama serve",
+                    self.base_url
+                )
+            } else {
+                e
+            }
+        })?;
+
+        let body = self.build_request_body(messages, max_tokens, true);
+
+        // Retries only cover the initial connect/headers — nothing has been
+        // emitted to `on_token` yet at this point, so a retry here can never
+        // duplicate streamed output.
+        let unavailable_msg = format!(
+            "Ollama is not running at {}.\nStart it with: ollama serve",
+            self.base_url
+        );
+        let resp = self.send_with_retry(
+            || {
+                self.with_auth(
+                    self.client
+                        .post(format!("{}/api/chat", self.base_url))
+                        .json(&body),
+                )
+            },
+            &unavailable_msg,
+        )?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().unwrap_or_default();
+            bail!("Ollama error ({}): {}", status, text);
+        }
+
+        // Read the stream on a background thread so we can still poll for
+        // "no token yet" on a timer without blocking on the next line.
+        let mut reader = BufReader::new(resp);
+        let (tx, rx) = mpsc::channel::<Option<String>>();
+        thread::spawn(move || {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        if tx.send(Some(line.clone())).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            let _ = tx.send(None);
+        });
+
+        let mut accumulated = String::new();
+        let mut showing_loading_status = false;
+
+        loop {
+            match rx.recv_timeout(MODEL_LOAD_STATUS_DELAY) {
+                Ok(Some(line)) => {
+                    if showing_loading_status {
+                        on_status("");
+                        showing_loading_status = false;
+                    }
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<StreamChunk>(&line) {
+                        Ok(chunk) => {
+                            if let Some(msg) = chunk.message {
+                                if !msg.content.is_empty() {
+                                    on_token(&msg.content);
+                                    accumulated.push_str(&msg.content);
+                                }
+                            }
+                            if chunk.done {
+                                break;
+                            }
+                        }
+                        Err(_) => continue, // Skip malformed lines
+                    }
+                }
+                Ok(None) => break,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if accumulated.is_empty() && !showing_loading_status {
+                        on_status("Loading model into memory…");
+                        showing_loading_status = true;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        if showing_loading_status {
+            on_status("");
+        }
+
+        if accumulated.trim().is_empty() {
+            bail!("Ollama returned empty streaming response");
+        }
+
+        Ok(accumulated.trim().to_string())
     }
 }
 
 impl Provider for OllamaProvider {
     fn name(&self) -> &str { "ollama" }
 
+    fn model(&self) -> &str { &self.model }
+
     fn is_available(&self) -> bool {
         self.is_server_running()
     }
 
-    fn generate(&self, system_prompt: &str, user_prompt: &str, max_tokens: u32) -> Result<String> {
+    fn generate(&self, messages: &[Message], max_tokens: u32) -> Result<String> {
         // No pre-check — just attempt the request, handle errors directly
-        let body = self.build_request_body(system_prompt, user_prompt, max_tokens, false);
+        let body = self.build_request_body(messages, max_tokens, false);
 
         // Ensure model is pulled (only checks on first call, then server has it cached)
         self.ensure_model_available().map_err(|e| {
@@ -229,21 +697,20 @@ impl Provider for OllamaProvider {
             }
         })?;
 
-        let resp = self.client
-            .post(format!("{}/api/chat", self.base_url))
-            .json(&body)
-            .send()
-            .map_err(|e| {
-                if e.is_connect() || e.is_timeout() {
-                    anyhow::anyhow!(
-                        "Ollama is not running at {}.\n\
-                         Start it with: ollama serve",
-                        self.base_url
-                    )
-                } else {
-                    anyhow::anyhow!("Failed to call Ollama: {}", e)
-                }
-            })?;
+        let unavailable_msg = format!(
+            "Ollama is not running at {}.\nStart it with: ollama serve",
+            self.base_url
+        );
+        let resp = self.send_with_retry(
+            || {
+                self.with_auth(
+                    self.client
+                        .post(format!("{}/api/chat", self.base_url))
+                        .json(&body),
+                )
+            },
+            &unavailable_msg,
+        )?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -264,87 +731,91 @@ impl Provider for OllamaProvider {
 
     fn generate_stream(
         &self,
-        system_prompt: &str,
-        user_prompt: &str,
+        messages: &[Message],
         max_tokens: u32,
         on_token: &mut dyn FnMut(&str),
     ) -> Result<String> {
-        self.ensure_model_available().map_err(|e| {
-            if format!("{:#}", e).contains("connect") {
-                anyhow::anyhow!(
-                    "Ollama is not running at {}.\nStart it with: ollama serve",
-                    self.base_url
-                )
-            } else {
-                e
-            }
-        })?;
-
-        let body = self.build_request_body(system_prompt, user_prompt, max_tokens, true);
+        self.generate_stream_with_status(messages, max_tokens, on_token, &mut |_| {})
+    }
 
-        let resp = self.client
-            .post(format!("{}/api/chat", self.base_url))
-            .json(&body)
-            .send()
-            .map_err(|e| {
-                if e.is_connect() || e.is_timeout() {
-                    anyhow::anyhow!(
-                        "Ollama is not running at {}.\nStart it with: ollama serve",
-                        self.base_url
-                    )
-                } else {
-                    anyhow::anyhow!("Failed to call Ollama: {}", e)
-                }
-            })?;
+    fn generate_with_tools(
+        &self,
+        messages: &[Message],
+        tools: &[ToolSpec],
+        max_tokens: u32,
+    ) -> Result<AgentTurn> {
+        self.chat_with_tools(messages, tools, max_tokens)
+    }
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().unwrap_or_default();
-            bail!("Ollama error ({}): {}", status, text);
+    fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        if !self.is_server_running() {
+            bail!("Ollama is not running. Start it with: ollama serve");
         }
+        self.fetch_local_models()
+    }
+}
 
-        let reader = BufReader::new(resp);
-        let mut accumulated = String::new();
+impl EmbeddingProvider for OllamaProvider {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if self.model.is_empty() {
+            bail!(
+                "No model selected for Ollama.\n\
+                 Run 'niko settings configure' to select an embedding model."
+            );
+        }
 
-        for line in reader.lines() {
-            let line = match line {
-                Ok(l) => l,
-                Err(e) => {
-                    if accumulated.is_empty() {
-                        bail!("Stream read error: {}", e);
+        let mut vectors = Vec::with_capacity(texts.len());
+        let mut dimensions: Option<usize> = None;
+
+        for (i, text) in texts.iter().enumerate() {
+            self.throttle();
+            let body = serde_json::json!({ "model": self.model, "prompt": text });
+            let resp = self
+                .with_auth(
+                    self.client
+                        .post(format!("{}/api/embeddings", self.base_url))
+                        .json(&body),
+                )
+                .send()
+                .map_err(|e| {
+                    if e.is_connect() || e.is_timeout() {
+                        anyhow::anyhow!(
+                            "Ollama is not running at {}.\nStart it with: ollama serve",
+                            self.base_url
+                        )
+                    } else {
+                        anyhow::anyhow!("Failed to call Ollama: {}", e)
                     }
-                    break; // Return what we have
-                }
-            };
+                })?;
 
-            if line.trim().is_empty() { continue; }
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let text = resp.text().unwrap_or_default();
+                bail!("Ollama embeddings error ({}): {}", status, text);
+            }
 
-            match serde_json::from_str::<StreamChunk>(&line) {
-                Ok(chunk) => {
-                    if let Some(msg) = chunk.message {
-                        if !msg.content.is_empty() {
-                            on_token(&msg.content);
-                            accumulated.push_str(&msg.content);
-                        }
-                    }
-                    if chunk.done { break; }
-                }
-                Err(_) => continue, // Skip malformed lines
+            let parsed: EmbeddingResponse =
+                resp.json().context("Failed to parse Ollama embeddings response")?;
+            let embedding = parsed
+                .embedding
+                .filter(|e| !e.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("Ollama returned an empty embedding"))?;
+
+            match dimensions {
+                None => dimensions = Some(embedding.len()),
+                Some(expected) if expected != embedding.len() => bail!(
+                    "Embedding dimension mismatch: input {} returned {} dims, expected {}",
+                    i,
+                    embedding.len(),
+                    expected
+                ),
+                _ => {}
             }
-        }
 
-        if accumulated.trim().is_empty() {
-            bail!("Ollama returned empty streaming response");
+            vectors.push(embedding);
         }
 
-        Ok(accumulated.trim().to_string())
-    }
-
-    fn list_models(&self) -> Result<Vec<ModelInfo>> {
-        if !self.is_server_running() {
-            bail!("Ollama is not running. Start it with: ollama serve");
-        }
-        self.fetch_local_models()
+        Ok(vectors)
     }
 }
 
@@ -415,6 +886,10 @@ pub fn search_ollama_models(query: &str) -> Result<Vec<ModelInfo>> {
         ("phi3:3.8b", 3.8), ("phi3:14b", 14.0),
         ("deepseek-r1:1.5b", 1.5), ("deepseek-r1:7b", 7.0), ("deepseek-r1:8b", 8.0),
         ("deepseek-r1:14b", 14.0), ("deepseek-r1:32b", 32.0), ("deepseek-r1:70b", 70.0),
+        // Embedding models, for local semantic search / RAG via `EmbeddingProvider::embed`
+        ("nomic-embed-text", 0.137),
+        ("mxbai-embed-large", 0.334),
+        ("all-minilm", 0.023),
     ];
 
     let query_lower = query.to_lowercase();
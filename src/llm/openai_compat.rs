@@ -1,10 +1,41 @@
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
 use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
 use serde::Deserialize;
 
-use crate::llm::{Provider, ModelInfo, estimate_param_billions};
+use crate::llm::{estimate_param_billions, AbortSignal, AgentTurn, EmbeddingProvider, Message, ModelInfo, Provider, Role, ToolCall, ToolSpec};
+
+/// Default bound on `send_with_retry`'s attempts, overridable via the
+/// `max_retries` provider option.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default starting backoff, overridable via the `retry_base_delay_ms`
+/// provider option; doubles each attempt up to `RETRY_MAX_DELAY_MS`.
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+const RETRY_MAX_DELAY_MS: u64 = 8000;
+
+/// Request style used against the provider's base URL: a chat-templated
+/// `/chat/completions` call, or the raw `/completions` endpoint some
+/// backends (llama.cpp server, TGI, vLLM) expose for base/instruct models
+/// served without a chat template. Set via the `completion_mode` provider
+/// option (`"completion"`/`"completions"`); anything else (including unset)
+/// stays on `Chat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompletionMode {
+    #[default]
+    Chat,
+    Completion,
+}
+
+impl CompletionMode {
+    fn from_option(value: Option<&String>) -> Self {
+        match value.map(String::as_str) {
+            Some("completion") | Some("completions") => CompletionMode::Completion,
+            _ => CompletionMode::Chat,
+        }
+    }
+}
 
 /// OpenAI-compatible provider with SSE streaming support
 pub struct OpenAICompatProvider {
@@ -13,6 +44,44 @@ pub struct OpenAICompatProvider {
     base_url: String,
     model: String,
     client: reqwest::blocking::Client,
+
+    /// Caps outgoing `max_tokens` so a per-call request can't exceed the
+    /// model's actual context window; `None` leaves the caller's value as-is
+    /// since most endpoints don't expose a way to ask a model its size.
+    context_window: Option<u32>,
+
+    /// Whether to talk `/chat/completions` or raw `/completions`.
+    completion_mode: CompletionMode,
+    /// Stop sequences sent with `/completions` requests, set via the
+    /// comma-separated `stop` provider option — `/chat/completions` handles
+    /// turn-taking itself, so this only applies in `CompletionMode::Completion`.
+    stop: Vec<String>,
+
+    /// Cohere-style embedding intent (`search_document`, `search_query`,
+    /// `classification`, ...), set via the `embedding_input_type` provider
+    /// option. Only sent when set, since OpenAI's own `/embeddings` doesn't
+    /// recognize it.
+    embedding_input_type: Option<String>,
+    /// OpenAI-style `encoding_format` (`"float"`/`"base64"`), set via the
+    /// `embedding_encoding_format` provider option.
+    embedding_encoding_format: Option<String>,
+
+    /// Retry tuning for `send_with_retry`, set via the `max_retries`/
+    /// `retry_base_delay_ms` provider options.
+    max_retries: u32,
+    retry_base_delay_ms: u64,
+
+    /// Arbitrary extra headers attached to every request, set via
+    /// `header:<Name>` provider options (e.g. `X-Api-Version`, org IDs for
+    /// API gateways sitting in front of the real backend).
+    extra_headers: Vec<(String, String)>,
+
+    /// Deep-merged into the outgoing request body (overriding or adding
+    /// keys) before every `generate`/`generate_stream` call, set via a
+    /// `body_patch` provider option holding a JSON object. For backends that
+    /// require or reject specific fields — e.g. reasoning models that forbid
+    /// `temperature`, or ones needing a custom `repetition_penalty`.
+    body_patch: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
@@ -32,6 +101,22 @@ struct Choice {
 #[derive(Deserialize)]
 struct ChoiceMessage {
     content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<RawToolCall>,
+}
+
+#[derive(Deserialize)]
+struct RawToolCall {
+    id: String,
+    function: RawFunctionCall,
+}
+
+#[derive(Deserialize)]
+struct RawFunctionCall {
+    name: String,
+    /// The API sends this as a JSON-encoded string, not an object.
+    #[serde(default)]
+    arguments: String,
 }
 
 /// SSE streaming chunk
@@ -53,6 +138,35 @@ struct StreamDelta {
     content: Option<String>,
 }
 
+/// Response shape for the raw `/completions` endpoint: `choices[].text`
+/// instead of `choices[].message.content`.
+#[derive(Deserialize)]
+struct CompletionResponse {
+    choices: Option<Vec<CompletionChoice>>,
+    #[serde(default)]
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChoice {
+    text: Option<String>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+/// SSE streaming chunk for `/completions`.
+#[derive(Deserialize)]
+struct CompletionStreamChunk {
+    choices: Option<Vec<CompletionStreamChoice>>,
+}
+
+#[derive(Deserialize)]
+struct CompletionStreamChoice {
+    text: Option<String>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
 #[derive(Deserialize, Default)]
 struct ApiError {
     message: Option<String>,
@@ -68,16 +182,187 @@ struct ApiModel {
     id: String,
 }
 
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Option<Vec<EmbeddingData>>,
+    #[serde(default)]
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+/// Converts a `Message` slice into the `messages` array this API expects,
+/// including assistant `tool_calls` and `tool`-role results.
+fn build_messages(messages: &[Message]) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|m| match m.role {
+            Role::System => serde_json::json!({ "role": "system", "content": m.content }),
+            Role::User => serde_json::json!({ "role": "user", "content": m.content }),
+            Role::Assistant if m.tool_calls.is_empty() => {
+                serde_json::json!({ "role": "assistant", "content": m.content })
+            }
+            Role::Assistant => {
+                let calls: Vec<serde_json::Value> = m
+                    .tool_calls
+                    .iter()
+                    .map(|c| {
+                        serde_json::json!({
+                            "id": c.id,
+                            "type": "function",
+                            "function": { "name": c.name, "arguments": c.arguments.to_string() },
+                        })
+                    })
+                    .collect();
+                serde_json::json!({ "role": "assistant", "content": m.content, "tool_calls": calls })
+            }
+            Role::Tool => serde_json::json!({
+                "role": "tool",
+                "tool_call_id": m.tool_call_id.clone().unwrap_or_default(),
+                "content": m.content,
+            }),
+        })
+        .collect()
+}
+
+/// Recursively merges `patch` into `base` in place: matching object keys
+/// merge recursively, anything else in `patch` (a scalar, an array, or a key
+/// absent from `base`) replaces/adds to `base` outright.
+fn deep_merge_json(base: &mut serde_json::Value, patch: &serde_json::Value) {
+    match (base, patch) {
+        (serde_json::Value::Object(base_map), serde_json::Value::Object(patch_map)) => {
+            for (key, patch_value) in patch_map {
+                match base_map.get_mut(key) {
+                    Some(base_value) => deep_merge_json(base_value, patch_value),
+                    None => {
+                        base_map.insert(key.clone(), patch_value.clone());
+                    }
+                }
+            }
+        }
+        (base_value, patch_value) => {
+            *base_value = patch_value.clone();
+        }
+    }
+}
+
+/// Parses a numeric `Retry-After` header (seconds) off a response, if
+/// present — takes priority over `send_with_retry`'s computed backoff when
+/// the server tells us exactly how long to wait.
+fn retry_after_header(resp: &reqwest::blocking::Response) -> Option<Duration> {
+    resp.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Resolves the proxy URL to hand `reqwest::Proxy::all` (a single proxy
+/// covering both schemes, matching what a blocking client needs for this
+/// provider's HTTPS-only traffic): the `https_proxy`/`http_proxy` provider
+/// options first, then the `HTTPS_PROXY`/`ALL_PROXY` environment variables
+/// reqwest itself would otherwise only pick up automatically on feature
+/// builds that enable its own env-proxy detection.
+fn proxy_url_from(options: &HashMap<String, String>) -> Option<String> {
+    options
+        .get("https_proxy")
+        .or_else(|| options.get("http_proxy"))
+        .cloned()
+        .or_else(|| std::env::var("HTTPS_PROXY").ok())
+        .or_else(|| std::env::var("ALL_PROXY").ok())
+        .filter(|s| !s.is_empty())
+}
+
+/// Flattens a `Message` slice into a single raw prompt for
+/// `CompletionMode::Completion` backends, which apply no chat template of
+/// their own and just continue whatever text they're given.
+fn build_prompt(messages: &[Message]) -> String {
+    let mut prompt = String::new();
+    for m in messages {
+        let role = match m.role {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::Tool => "Tool",
+        };
+        if !m.content.is_empty() {
+            prompt.push_str(role);
+            prompt.push_str(": ");
+            prompt.push_str(&m.content);
+            prompt.push_str("\n\n");
+        }
+    }
+    prompt.push_str("Assistant:");
+    prompt
+}
+
 impl OpenAICompatProvider {
-    pub fn new(provider_name: &str, api_key: &str, base_url: &str, model: &str) -> Self {
-        let client = reqwest::blocking::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .connect_timeout(Duration::from_secs(10))
+    pub fn new(
+        provider_name: &str,
+        api_key: &str,
+        base_url: &str,
+        model: &str,
+        context_window: Option<u32>,
+        options: &HashMap<String, String>,
+    ) -> Self {
+        let timeout_secs = options
+            .get("timeout_secs")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(120);
+        let connect_timeout_secs = options
+            .get("connect_timeout_secs")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .connect_timeout(Duration::from_secs(connect_timeout_secs))
             .pool_max_idle_per_host(4)
             .pool_idle_timeout(Duration::from_secs(90))
-            .tcp_keepalive(Duration::from_secs(30))
-            .build()
-            .unwrap_or_else(|_| reqwest::blocking::Client::new());
+            .tcp_keepalive(Duration::from_secs(30));
+
+        if let Some(proxy_url) = proxy_url_from(options) {
+            if let Ok(proxy) = reqwest::Proxy::all(&proxy_url) {
+                builder = builder.proxy(proxy);
+            } else {
+                eprintln!("  ⚠ Ignoring invalid proxy URL for '{}': {}", provider_name, proxy_url);
+            }
+        }
+
+        let client = builder.build().unwrap_or_else(|_| reqwest::blocking::Client::new());
+
+        let stop = options
+            .get("stop")
+            .map(|s| s.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+            .unwrap_or_default();
+
+        let extra_headers = options
+            .iter()
+            .filter_map(|(k, v)| {
+                k.strip_prefix("header:")
+                    .map(|name| (name.to_string(), v.clone()))
+            })
+            .collect();
+
+        let body_patch = options.get("body_patch").and_then(|raw| {
+            match serde_json::from_str::<serde_json::Value>(raw) {
+                Ok(v) if v.is_object() => Some(v),
+                Ok(_) => {
+                    eprintln!(
+                        "  ⚠ Ignoring body_patch for '{}': must be a JSON object",
+                        provider_name
+                    );
+                    None
+                }
+                Err(e) => {
+                    eprintln!("  ⚠ Ignoring invalid body_patch for '{}': {}", provider_name, e);
+                    None
+                }
+            }
+        });
 
         Self {
             provider_name: provider_name.to_string(),
@@ -85,51 +370,129 @@ impl OpenAICompatProvider {
             base_url: base_url.trim_end_matches('/').to_string(),
             model: model.to_string(),
             client,
+            context_window,
+            completion_mode: CompletionMode::from_option(options.get("completion_mode")),
+            stop,
+            embedding_input_type: options.get("embedding_input_type").cloned(),
+            embedding_encoding_format: options.get("embedding_encoding_format").cloned(),
+            max_retries: options
+                .get("max_retries")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+            retry_base_delay_ms: options
+                .get("retry_base_delay_ms")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS),
+            extra_headers,
+            body_patch,
         }
     }
 
-    fn validate(&self) -> Result<()> {
-        if self.api_key.is_empty() {
-            bail!(
-                "API key not configured for '{}'.\nRun 'niko settings configure' to set it up.",
-                self.provider_name
-            );
-        }
-        if self.model.is_empty() {
-            bail!(
-                "No model selected for '{}'.\nRun 'niko settings configure' to select a model.",
-                self.provider_name
-            );
+    /// Clamps a requested `max_tokens` against the configured context
+    /// window, if one is set.
+    fn clamp_max_tokens(&self, max_tokens: u32) -> u32 {
+        self.context_window.map_or(max_tokens, |c| max_tokens.min(c))
+    }
+
+    /// Deep-merges `body_patch` (if configured) into an outgoing request
+    /// body: object keys are merged recursively, any other value (including
+    /// whole-array replacement) overwrites the base value outright.
+    fn apply_body_patch(&self, body: &mut serde_json::Value) {
+        if let Some(patch) = &self.body_patch {
+            deep_merge_json(body, patch);
         }
-        Ok(())
     }
-}
 
-impl Provider for OpenAICompatProvider {
-    fn name(&self) -> &str { &self.provider_name }
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        let base_ms = self.retry_base_delay_ms * 2u64.pow(attempt);
+        let delay_ms = base_ms.min(RETRY_MAX_DELAY_MS);
+        let jitter = delay_ms / 10;
+        Duration::from_millis(delay_ms + jitter)
+    }
 
-    fn is_available(&self) -> bool { !self.api_key.is_empty() }
+    /// Attaches the bearer token and any `header:<Name>`-configured extra
+    /// headers to an outgoing request, so every endpoint this provider calls
+    /// authenticates against gateways the same way.
+    fn with_headers(
+        &self,
+        mut builder: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        builder = builder.header("Authorization", format!("Bearer {}", self.api_key));
+        for (name, value) in &self.extra_headers {
+            builder = builder.header(name, value);
+        }
+        builder
+    }
 
-    fn generate(&self, system_prompt: &str, user_prompt: &str, max_tokens: u32) -> Result<String> {
-        self.validate()?;
+    /// Sends a freshly-built request with bounded exponential-backoff retry
+    /// on connection/timeout errors and 5xx/429 responses, honoring a
+    /// `Retry-After` header (in seconds) over the computed backoff when the
+    /// server sends one. 4xx other than 429 is returned immediately for the
+    /// caller's existing status/body handling; once a response comes back
+    /// (retryable-exhausted or not) it's handed back as-is.
+    fn send_with_retry(
+        &self,
+        mut make_request: impl FnMut() -> reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response> {
+        for attempt in 0..=self.max_retries {
+            match make_request().send() {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let retryable = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    if status.is_success() || !retryable || attempt == self.max_retries {
+                        return Ok(resp);
+                    }
+                    let delay = retry_after_header(&resp).unwrap_or_else(|| self.retry_delay(attempt));
+                    eprintln!(
+                        "  ↻ {} returned {}, retrying in {:.1}s… ({}/{})",
+                        self.provider_name,
+                        status,
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        self.max_retries
+                    );
+                    std::thread::sleep(delay);
+                }
+                Err(e) if (e.is_connect() || e.is_timeout()) && attempt < self.max_retries => {
+                    let delay = self.retry_delay(attempt);
+                    eprintln!(
+                        "  ↻ Failed to call {} API ({}), retrying in {:.1}s… ({}/{})",
+                        self.provider_name,
+                        e,
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        self.max_retries
+                    );
+                    std::thread::sleep(delay);
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to call {} API", self.provider_name))
+                }
+            }
+        }
+        unreachable!("loop always returns or bails by the final attempt")
+    }
 
-        let body = serde_json::json!({
+    /// `CompletionMode::Completion` counterpart of `generate`: raw prompt in,
+    /// raw `text` out, against `/completions` instead of `/chat/completions`.
+    fn generate_completion(&self, messages: &[Message], max_tokens: u32) -> Result<String> {
+        let mut body = serde_json::json!({
             "model": self.model,
-            "messages": [
-                { "role": "system", "content": system_prompt },
-                { "role": "user", "content": user_prompt }
-            ],
+            "prompt": build_prompt(messages),
             "temperature": 0.1,
-            "max_tokens": max_tokens,
+            "max_tokens": self.clamp_max_tokens(max_tokens),
         });
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::json!(self.stop);
+        }
+        self.apply_body_patch(&mut body);
 
-        let resp = self.client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .with_context(|| format!("Failed to call {} API", self.provider_name))?;
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.post(format!("{}/completions", self.base_url)))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -137,7 +500,7 @@ impl Provider for OpenAICompatProvider {
             bail!("{} API error ({}): {}", self.provider_name, status.as_u16(), text);
         }
 
-        let completion: ChatCompletionResponse = resp.json()
+        let completion: CompletionResponse = resp.json()
             .with_context(|| format!("Failed to parse {} response", self.provider_name))?;
 
         if let Some(err) = completion.error {
@@ -148,17 +511,17 @@ impl Provider for OpenAICompatProvider {
 
         let choice = completion.choices.and_then(|c| c.into_iter().next());
 
-        let content = match choice {
+        let text = match choice {
             Some(c) => {
                 if c.finish_reason.as_deref() == Some("length") {
                     eprintln!("  ⚠ Response truncated (hit max_tokens)");
                 }
-                c.message.content.unwrap_or_default()
+                c.text.unwrap_or_default()
             }
             None => bail!("{} returned no choices", self.provider_name),
         };
 
-        let trimmed = content.trim();
+        let trimmed = text.trim();
         if trimmed.is_empty() {
             bail!("{} returned empty response", self.provider_name);
         }
@@ -166,33 +529,116 @@ impl Provider for OpenAICompatProvider {
         Ok(trimmed.to_string())
     }
 
-    fn generate_stream(
+    /// `CompletionMode::Completion` counterpart of `generate_stream`.
+    /// `abort`, when given, is checked before each token is delivered — see
+    /// `generate_stream_chat` for the shared abort-then-return semantics.
+    fn generate_completion_stream(
         &self,
-        system_prompt: &str,
-        user_prompt: &str,
+        messages: &[Message],
         max_tokens: u32,
+        abort: Option<&AbortSignal>,
         on_token: &mut dyn FnMut(&str),
     ) -> Result<String> {
-        self.validate()?;
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "prompt": build_prompt(messages),
+            "temperature": 0.1,
+            "max_tokens": self.clamp_max_tokens(max_tokens),
+            "stream": true,
+        });
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::json!(self.stop);
+        }
+        self.apply_body_patch(&mut body);
 
-        let body = serde_json::json!({
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.post(format!("{}/completions", self.base_url)))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().unwrap_or_default();
+            bail!("{} API error ({}): {}", self.provider_name, status.as_u16(), text);
+        }
+
+        let reader = BufReader::new(resp);
+        let mut accumulated = String::new();
+
+        for line in reader.lines() {
+            if abort.map(|a| a.is_aborted()).unwrap_or(false) {
+                break;
+            }
+
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => {
+                    if accumulated.is_empty() {
+                        bail!("Stream read error: {}", e);
+                    }
+                    break;
+                }
+            };
+
+            let line = line.trim().to_string();
+            if line.is_empty() { continue; }
+
+            if let Some(data) = line.strip_prefix("data: ") {
+                if data == "[DONE]" { break; }
+
+                if let Ok(chunk) = serde_json::from_str::<CompletionStreamChunk>(data) {
+                    if let Some(choices) = chunk.choices {
+                        for choice in choices {
+                            if let Some(text) = choice.text {
+                                if !text.is_empty() {
+                                    on_token(&text);
+                                    accumulated.push_str(&text);
+                                }
+                            }
+                            if choice.finish_reason.as_deref() == Some("length") {
+                                eprintln!("\n  ⚠ Response truncated (hit max_tokens)");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if accumulated.trim().is_empty() {
+            bail!("{} returned empty streaming response", self.provider_name);
+        }
+
+        Ok(accumulated.trim().to_string())
+    }
+
+    /// Chat-mode counterpart of `generate_completion_stream` — the body
+    /// shared by `generate_stream` and `generate_stream_cancellable` once
+    /// `completion_mode` has ruled out the raw-prompt path. `abort`, when
+    /// given, is checked before each token is delivered; once set, the loop
+    /// breaks and returns whatever text has accumulated so far instead of
+    /// reading the response to completion.
+    fn generate_stream_chat(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        abort: Option<&AbortSignal>,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        let mut body = serde_json::json!({
             "model": self.model,
-            "messages": [
-                { "role": "system", "content": system_prompt },
-                { "role": "user", "content": user_prompt }
-            ],
+            "messages": build_messages(messages),
             "temperature": 0.1,
-            "max_tokens": max_tokens,
+            "max_tokens": self.clamp_max_tokens(max_tokens),
             "stream": true,
         });
+        self.apply_body_patch(&mut body);
 
-        let resp = self.client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .with_context(|| format!("Failed to call {} API", self.provider_name))?;
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.post(format!("{}/chat/completions", self.base_url)))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -204,6 +650,10 @@ impl Provider for OpenAICompatProvider {
         let mut accumulated = String::new();
 
         for line in reader.lines() {
+            if abort.map(|a| a.is_aborted()).unwrap_or(false) {
+                break;
+            }
+
             let line = match line {
                 Ok(l) => l,
                 Err(e) => {
@@ -248,6 +698,218 @@ impl Provider for OpenAICompatProvider {
         Ok(accumulated.trim().to_string())
     }
 
+    fn validate(&self) -> Result<()> {
+        if self.api_key.is_empty() {
+            bail!(
+                "API key not configured for '{}'.\nRun 'niko settings configure' to set it up.",
+                self.provider_name
+            );
+        }
+        if self.model.is_empty() {
+            bail!(
+                "No model selected for '{}'.\nRun 'niko settings configure' to select a model.",
+                self.provider_name
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Provider for OpenAICompatProvider {
+    fn name(&self) -> &str { &self.provider_name }
+
+    fn model(&self) -> &str { &self.model }
+
+    fn is_available(&self) -> bool { !self.api_key.is_empty() }
+
+    fn generate(&self, messages: &[Message], max_tokens: u32) -> Result<String> {
+        self.validate()?;
+
+        if self.completion_mode == CompletionMode::Completion {
+            return self.generate_completion(messages, max_tokens);
+        }
+
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": build_messages(messages),
+            "temperature": 0.1,
+            "max_tokens": self.clamp_max_tokens(max_tokens),
+        });
+        self.apply_body_patch(&mut body);
+
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.post(format!("{}/chat/completions", self.base_url)))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().unwrap_or_default();
+            bail!("{} API error ({}): {}", self.provider_name, status.as_u16(), text);
+        }
+
+        let completion: ChatCompletionResponse = resp.json()
+            .with_context(|| format!("Failed to parse {} response", self.provider_name))?;
+
+        if let Some(err) = completion.error {
+            if let Some(msg) = err.message {
+                bail!("{} API error: {}", self.provider_name, msg);
+            }
+        }
+
+        let choice = completion.choices.and_then(|c| c.into_iter().next());
+
+        let content = match choice {
+            Some(c) => {
+                if c.finish_reason.as_deref() == Some("length") {
+                    eprintln!("  ⚠ Response truncated (hit max_tokens)");
+                }
+                c.message.content.unwrap_or_default()
+            }
+            None => bail!("{} returned no choices", self.provider_name),
+        };
+
+        let trimmed = content.trim();
+        if trimmed.is_empty() {
+            bail!("{} returned empty response", self.provider_name);
+        }
+
+        Ok(trimmed.to_string())
+    }
+
+    fn generate_stream(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        self.validate()?;
+
+        if self.completion_mode == CompletionMode::Completion {
+            return self.generate_completion_stream(messages, max_tokens, None, on_token);
+        }
+
+        self.generate_stream_chat(messages, max_tokens, None, on_token)
+    }
+
+    /// Cancellable counterpart of `generate_stream` — same request, but the
+    /// SSE read loop checks `abort` before each token so a caller can stop a
+    /// runaway response without waiting for it to finish or killing the
+    /// connection from the outside.
+    fn generate_stream_cancellable(
+        &self,
+        messages: &[Message],
+        max_tokens: u32,
+        abort: &AbortSignal,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        self.validate()?;
+
+        if self.completion_mode == CompletionMode::Completion {
+            return self.generate_completion_stream(messages, max_tokens, Some(abort), on_token);
+        }
+
+        self.generate_stream_chat(messages, max_tokens, Some(abort), on_token)
+    }
+
+    fn generate_with_tools(
+        &self,
+        messages: &[Message],
+        tools: &[ToolSpec],
+        max_tokens: u32,
+    ) -> Result<AgentTurn> {
+        self.validate()?;
+
+        let tool_defs: Vec<serde_json::Value> = tools
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.name,
+                        "description": t.description,
+                        "parameters": t.parameters,
+                    }
+                })
+            })
+            .collect();
+
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": build_messages(messages),
+            "temperature": 0.1,
+            "max_tokens": self.clamp_max_tokens(max_tokens),
+            "tools": tool_defs,
+        });
+
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.post(format!("{}/chat/completions", self.base_url)))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().unwrap_or_default();
+            bail!("{} API error ({}): {}", self.provider_name, status.as_u16(), text);
+        }
+
+        let completion: ChatCompletionResponse = resp.json()
+            .with_context(|| format!("Failed to parse {} response", self.provider_name))?;
+
+        if let Some(err) = completion.error {
+            if let Some(msg) = err.message {
+                bail!("{} API error: {}", self.provider_name, msg);
+            }
+        }
+
+        let choice = completion
+            .choices
+            .and_then(|c| c.into_iter().next())
+            .ok_or_else(|| anyhow::anyhow!("{} returned no choices", self.provider_name))?;
+
+        if choice.finish_reason.as_deref() == Some("length") {
+            eprintln!("  ⚠ Response truncated (hit max_tokens)");
+        }
+
+        // `finish_reason` is the authoritative signal for which branch this
+        // is: a server that reports "tool_calls" but sends an empty array is
+        // malformed, not a plain-text reply, so surface that distinctly
+        // rather than silently falling through to an "empty response" error.
+        if choice.finish_reason.as_deref() == Some("tool_calls") && choice.message.tool_calls.is_empty() {
+            bail!(
+                "{} reported finish_reason \"tool_calls\" but sent no tool_calls",
+                self.provider_name
+            );
+        }
+
+        if choice.message.tool_calls.is_empty() {
+            let content = choice.message.content.unwrap_or_default();
+            let trimmed = content.trim();
+            if trimmed.is_empty() {
+                bail!("{} returned empty response", self.provider_name);
+            }
+            Ok(AgentTurn::Final(trimmed.to_string()))
+        } else {
+            let calls = choice
+                .message
+                .tool_calls
+                .into_iter()
+                .map(|raw| {
+                    let arguments = serde_json::from_str(&raw.function.arguments)
+                        .unwrap_or(serde_json::Value::Null);
+                    ToolCall {
+                        id: raw.id,
+                        name: raw.function.name,
+                        arguments,
+                    }
+                })
+                .collect();
+            Ok(AgentTurn::ToolCalls(calls))
+        }
+    }
+
     fn list_models(&self) -> Result<Vec<ModelInfo>> {
         if self.api_key.is_empty() {
             bail!(
@@ -256,12 +918,10 @@ impl Provider for OpenAICompatProvider {
             );
         }
 
-        let resp = self.client
-            .get(format!("{}/models", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .timeout(Duration::from_secs(15))
-            .send()
-            .with_context(|| format!("Failed to fetch models from {}", self.provider_name))?;
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.get(format!("{}/models", self.base_url)))
+                .timeout(Duration::from_secs(15))
+        })?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -281,3 +941,52 @@ impl Provider for OpenAICompatProvider {
             .collect())
     }
 }
+
+impl EmbeddingProvider for OpenAICompatProvider {
+    /// Calls the OpenAI-style `/embeddings` endpoint in one batched request
+    /// (unlike Ollama's `/api/embeddings`, which only accepts one prompt at a
+    /// time) — most OpenAI-compatible APIs accept an `input` array.
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.validate()?;
+
+        let mut body = serde_json::json!({ "model": self.model, "input": texts });
+        if let Some(input_type) = &self.embedding_input_type {
+            body["input_type"] = serde_json::json!(input_type);
+        }
+        if let Some(encoding_format) = &self.embedding_encoding_format {
+            body["encoding_format"] = serde_json::json!(encoding_format);
+        }
+        let resp = self.send_with_retry(|| {
+            self.with_headers(self.client.post(format!("{}/embeddings", self.base_url)))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().unwrap_or_default();
+            bail!("{} embeddings API error ({}): {}", self.provider_name, status.as_u16(), text);
+        }
+
+        let parsed: EmbeddingsResponse = resp.json()
+            .with_context(|| format!("Failed to parse {} embeddings response", self.provider_name))?;
+
+        if let Some(err) = parsed.error {
+            if let Some(msg) = err.message {
+                bail!("{} embeddings API error: {}", self.provider_name, msg);
+            }
+        }
+
+        let data = parsed.data.unwrap_or_default();
+        if data.len() != texts.len() {
+            bail!(
+                "{} returned {} embeddings for {} inputs",
+                self.provider_name,
+                data.len(),
+                texts.len()
+            );
+        }
+
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+}
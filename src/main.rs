@@ -1,4 +1,6 @@
+mod cache;
 mod chunker;
+mod completions;
 mod config;
 mod llm;
 mod modes;
@@ -29,10 +31,23 @@ struct Cli {
     #[arg(short, long, global = true)]
     provider: Option<String>,
 
+    /// Apply a saved role preset (see `niko settings role list`)
+    #[arg(short, long, global = true)]
+    role: Option<String>,
+
     /// Show debug information
     #[arg(short, long, global = true)]
     verbose: bool,
 
+    /// Force tool-help caches to rebuild instead of reusing cached --help
+    /// output or completion-file parses
+    #[arg(long = "refresh-tools", global = true)]
+    refresh_tools: bool,
+
+    /// Control colored output
+    #[arg(long, global = true, default_value = "auto", value_parser = ["auto", "always", "never"])]
+    color: String,
+
     /// Default mode: remaining args are treated as a command query
     #[arg(trailing_var_arg = true)]
     query: Vec<String>,
@@ -50,6 +65,10 @@ enum Commands {
         #[arg(short, long)]
         provider: Option<String>,
 
+        /// Apply a saved role preset (see `niko settings role list`)
+        #[arg(short, long)]
+        role: Option<String>,
+
         /// Show debug information
         #[arg(short, long)]
         verbose: bool,
@@ -65,9 +84,49 @@ enum Commands {
         #[arg(short, long)]
         provider: Option<String>,
 
+        /// Apply a saved role preset (see `niko settings role list`)
+        #[arg(short, long)]
+        role: Option<String>,
+
+        /// Show debug information
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Run an arena comparison across multiple providers/models instead
+        /// of the default single-provider flow. Repeatable, each as
+        /// `provider` or `provider:model` (e.g. `--arena claude --arena
+        /// ollama:llama3`). Needs at least 2 to trigger arena mode.
+        #[arg(long = "arena")]
+        arena: Vec<String>,
+
+        /// Print the result as a single JSON object instead of the
+        /// formatted report — for scripting and editor integrations.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Watch a file and re-explain it on every save
+    Watch {
+        /// File to watch
+        #[arg(short, long)]
+        file: String,
+
+        /// Override the default LLM provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Apply a saved role preset (see `niko settings role list`)
+        #[arg(short, long)]
+        role: Option<String>,
+
         /// Show debug information
         #[arg(short, long)]
         verbose: bool,
+
+        /// Print each re-analysis as a single JSON object instead of the
+        /// formatted report.
+        #[arg(long)]
+        json: bool,
     },
 
     /// View and manage configuration
@@ -76,6 +135,28 @@ enum Commands {
         action: Option<SettingsAction>,
     },
 
+    /// Expose the active provider as an OpenAI-compatible HTTP endpoint
+    Serve {
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8787)]
+        port: u16,
+
+        /// Override the default LLM provider
+        #[arg(short = 'P', long)]
+        provider: Option<String>,
+
+        /// Bind to all interfaces (0.0.0.0) instead of localhost only. There
+        /// is no authentication on this endpoint, so anyone who can reach the
+        /// bound address can make requests on your configured provider's
+        /// credentials — only pass this on a trusted network.
+        #[arg(long)]
+        bind_all: bool,
+
+        /// Show debug information
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
     /// Print version information
     Version,
 }
@@ -92,29 +173,93 @@ enum SettingsAction {
     Init,
     /// Print the config file path
     Path,
+    /// Warm up the active provider's model so the first real request doesn't stall
+    Preload,
+    /// Check connectivity/auth for a provider (defaults to the active one)
+    Test {
+        /// Provider name to test (defaults to the active provider)
+        provider: Option<String>,
+    },
+    /// Create, list, or delete saved role presets
+    Role {
+        #[command(subcommand)]
+        action: Option<RoleAction>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RoleAction {
+    /// List saved roles
+    List,
+    /// Create or update a role
+    Create {
+        name: String,
+        /// System prompt text for this role
+        #[arg(short, long)]
+        system_prompt: String,
+        /// Provider override for this role
+        #[arg(short, long)]
+        provider: Option<String>,
+        /// Model override for this role
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Max tokens override for this role
+        #[arg(short = 't', long)]
+        max_tokens: Option<u32>,
+    },
+    /// Delete a role
+    Delete { name: String },
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    let color_choice = cli.color.parse().unwrap_or(ui::ColorChoice::Auto);
+    ui::init_color(color_choice);
+    let abort = llm::install_ctrlc_abort_handler();
+
     let result = match cli.command {
         Some(Commands::Cmd {
             query,
             provider,
+            role,
             verbose,
         }) => {
             let provider_ref = provider.as_deref().or(cli.provider.as_deref());
+            let role_ref = role.as_deref().or(cli.role.as_deref());
             let query_str = query.join(" ");
-            modes::cmd::run(&query_str, provider_ref, verbose || cli.verbose)
+            modes::cmd::run(&query_str, provider_ref, role_ref, verbose || cli.verbose, cli.refresh_tools, &abort)
         }
 
         Some(Commands::Explain {
             file,
             provider,
+            role,
             verbose,
+            arena,
+            json,
         }) => {
             let provider_ref = provider.as_deref().or(cli.provider.as_deref());
-            modes::explain::run(file.as_deref(), provider_ref, verbose || cli.verbose)
+            let role_ref = role.as_deref().or(cli.role.as_deref());
+            if arena.len() >= 2 {
+                let entries: Vec<modes::arena::ArenaEntry> =
+                    arena.iter().map(|s| modes::arena::parse_spec(s)).collect();
+                modes::arena::run(file.as_deref(), &entries, role_ref, verbose || cli.verbose)
+            } else {
+                modes::explain::run(file.as_deref(), provider_ref, role_ref, verbose || cli.verbose, json, &abort)
+            }
+        }
+
+        Some(Commands::Watch {
+            file,
+            provider,
+            role,
+            verbose,
+            json,
+        }) => {
+            let provider_ref = provider.as_deref().or(cli.provider.as_deref());
+            let role_ref = role.as_deref().or(cli.role.as_deref());
+            modes::watch::run(&file, provider_ref, role_ref, verbose || cli.verbose, json, &abort)
         }
 
         Some(Commands::Settings { action }) => {
@@ -126,11 +271,44 @@ fn main() {
                 }
                 Some(SettingsAction::Init) => Some(modes::settings::Action::Init),
                 Some(SettingsAction::Path) => Some(modes::settings::Action::Path),
+                Some(SettingsAction::Preload) => Some(modes::settings::Action::Preload),
+                Some(SettingsAction::Test { provider }) => {
+                    Some(modes::settings::Action::Test { provider })
+                }
+                Some(SettingsAction::Role { action }) => Some(match action {
+                    Some(RoleAction::List) | None => modes::settings::Action::RoleList,
+                    Some(RoleAction::Create {
+                        name,
+                        system_prompt,
+                        provider,
+                        model,
+                        max_tokens,
+                    }) => modes::settings::Action::RoleCreate {
+                        name,
+                        system_prompt,
+                        provider,
+                        model,
+                        max_tokens,
+                    },
+                    Some(RoleAction::Delete { name }) => {
+                        modes::settings::Action::RoleDelete { name }
+                    }
+                }),
                 None => None,
             };
             modes::settings::run(settings_action)
         }
 
+        Some(Commands::Serve {
+            port,
+            provider,
+            bind_all,
+            verbose,
+        }) => {
+            let provider_ref = provider.as_deref().or(cli.provider.as_deref());
+            modes::serve::run(port, provider_ref, bind_all, verbose || cli.verbose)
+        }
+
         Some(Commands::Version) => {
             println!("niko {}", env!("CARGO_PKG_VERSION"));
             Ok(())
@@ -140,7 +318,7 @@ fn main() {
             // Default mode: if args provided, treat as cmd
             if !cli.query.is_empty() {
                 let query_str = cli.query.join(" ");
-                modes::cmd::run(&query_str, cli.provider.as_deref(), cli.verbose)
+                modes::cmd::run(&query_str, cli.provider.as_deref(), cli.role.as_deref(), cli.verbose, cli.refresh_tools, &abort)
             } else {
                 // No args — show help
                 use clap::CommandFactory;
@@ -0,0 +1,143 @@
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::{bail, Result};
+use colored::*;
+
+use crate::{chunker, config, llm, ui};
+
+/// One `--arena provider[:model]` entry: which configured provider to run,
+/// and an optional model override (falling back to that provider's
+/// configured default model when absent).
+pub struct ArenaEntry {
+    pub provider: String,
+    pub model: Option<String>,
+}
+
+/// Parse a `--arena` flag value of the form `provider` or `provider:model`.
+pub fn parse_spec(spec: &str) -> ArenaEntry {
+    match spec.split_once(':') {
+        Some((provider, model)) => ArenaEntry {
+            provider: provider.to_string(),
+            model: Some(model.to_string()),
+        },
+        None => ArenaEntry {
+            provider: spec.to_string(),
+            model: None,
+        },
+    }
+}
+
+/// Run the same code through every entry's provider/model concurrently and
+/// render each explanation sequentially, with a header per provider, so
+/// users can directly compare e.g. Claude vs. a local Ollama model. One
+/// provider failing (misconfigured, unreachable, rate-limited) doesn't stop
+/// the others from completing.
+pub fn run(file_path: Option<&str>, entries: &[ArenaEntry], role: Option<&str>, verbose: bool) -> Result<()> {
+    if entries.len() < 2 {
+        bail!("Arena mode needs at least 2 --arena provider[:model] entries");
+    }
+
+    let code = super::explain::read_code_input(file_path)?;
+    if code.is_empty() {
+        bail!(
+            "No code provided.\n\n\
+             Usage:\n\
+             \x20 niko explain -f <file> --arena claude --arena ollama:llama3"
+        );
+    }
+
+    let lang_hint = file_path
+        .and_then(|p| std::path::Path::new(p).extension())
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_string());
+
+    let role_cfg = role.map(config::get_role).transpose()?;
+    let role_prompt = role_cfg
+        .as_ref()
+        .map(|r| r.system_prompt.clone())
+        .filter(|p| !p.trim().is_empty());
+
+    eprintln!();
+    eprintln!(
+        "  {} Running {} providers on {} lines...",
+        "⚔".to_string(),
+        entries.len().to_string().cyan(),
+        code.lines().count().to_string().cyan()
+    );
+
+    let cfg = config::load()?;
+    let (tx, rx) = mpsc::channel();
+    let mut handles = Vec::new();
+
+    for (idx, entry) in entries.iter().enumerate() {
+        let provider_name = entry.provider.clone();
+        let tx = tx.clone();
+
+        let mut pcfg = match cfg.providers.get(&provider_name).cloned() {
+            Some(p) => p,
+            None => {
+                let _ = tx.send((
+                    idx,
+                    provider_name.clone(),
+                    Err(anyhow::anyhow!("Provider '{}' not configured", provider_name)),
+                ));
+                continue;
+            }
+        };
+        if let Some(model) = &entry.model {
+            pcfg.model = model.clone();
+        }
+
+        let code = code.clone();
+        let role_prompt = role_prompt.clone();
+        let lang_hint = lang_hint.clone();
+
+        let handle = thread::spawn(move || {
+            let result = (|| -> Result<chunker::ExplainResult> {
+                let provider = llm::from_config(&provider_name, &pcfg)?;
+                if !provider.is_available() {
+                    bail!("Provider '{}' not ready", provider_name);
+                }
+                chunker::explain_code(
+                    &code,
+                    lang_hint.as_deref(),
+                    provider.as_ref(),
+                    None,
+                    None,
+                    verbose,
+                    role_prompt.as_deref(),
+                    None::<fn(&str)>,
+                    None,
+                )
+            })();
+            let _ = tx.send((idx, provider_name, result));
+        });
+        handles.push(handle);
+    }
+    drop(tx);
+
+    let mut results: Vec<(usize, String, Result<chunker::ExplainResult>)> = rx.iter().collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    // Arrival order is nondeterministic (fastest provider first) — restore
+    // the order the user passed `--arena` flags in for a stable display.
+    results.sort_by_key(|(idx, _, _)| *idx);
+
+    for (_, name, result) in results {
+        eprintln!();
+        ui::print_rule();
+        println!("  {} {}", "Provider:".dimmed(), name.bold().magenta());
+        match result {
+            Ok(explanation) => ui::display_explanation(&explanation),
+            Err(e) => {
+                ui::print_error(&format!("{} failed", name));
+                ui::print_dim(&format!("  {}", e));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,42 +1,292 @@
 use anyhow::{bail, Result};
 use colored::*;
 
-use crate::{llm, prompt, safety, ui};
+use crate::{config, llm, prompt, safety, ui};
 
 /// Max tokens for command generation — commands are short, 512 is plenty
 const CMD_MAX_TOKENS: u32 = 512;
 
+/// Max agent turns before the agent loop gives up with an error
+const AGENT_MAX_STEPS: usize = 6;
+
+/// Cap tool output so a runaway command can't blow the context window
+const AGENT_TOOL_MAX_OUTPUT: usize = 4000;
+
+/// Local tools offered to the model for agentic command generation
+fn local_tools() -> Vec<llm::ToolSpec> {
+    vec![
+        llm::ToolSpec {
+            name: "run_shell_command".to_string(),
+            description: "Run a read-only shell command to inspect the environment (e.g. ls, \
+                cat, grep, git status). Commands that could modify state are refused."
+                .to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "command": { "type": "string", "description": "The shell command to run" }
+                },
+                "required": ["command"],
+            }),
+        },
+        llm::ToolSpec {
+            name: "list_files".to_string(),
+            description: "List files in a directory (non-recursive).".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Directory to list, defaults to '.'" }
+                },
+            }),
+        },
+        llm::ToolSpec {
+            name: "read_file".to_string(),
+            description: "Read the contents of a text file.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path of the file to read" }
+                },
+                "required": ["path"],
+            }),
+        },
+        llm::ToolSpec {
+            name: "may_run_command".to_string(),
+            description: "Run a shell command that may modify files or system state \
+                (installing a package, writing a file, a git commit, etc). Unlike \
+                `run_shell_command`, this is allowed to have side effects — anything \
+                beyond a read-only command pauses for the user to confirm before it \
+                actually runs."
+                .to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "command": { "type": "string", "description": "The shell command to run" }
+                },
+                "required": ["command"],
+            }),
+        },
+    ]
+}
+
+/// Ask the user before `execute_tool_call` runs a side-effecting (non-`Safe`)
+/// command on the model's behalf.
+fn confirm_side_effecting_command(command: &str, risk: safety::RiskLevel) -> bool {
+    ui::print_warning(&format!("Model wants to run ({}): {}", risk, command));
+    match ui::prompt_input("  Allow? [y/N]: ") {
+        Ok(answer) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+/// Truncate tool output so it can't blow past the context window
+fn truncate_tool_output(output: String) -> String {
+    if output.len() > AGENT_TOOL_MAX_OUTPUT {
+        let mut truncated = output;
+        truncated.truncate(AGENT_TOOL_MAX_OUTPUT);
+        truncated.push_str("\n... (truncated)");
+        truncated
+    } else {
+        output
+    }
+}
+
+/// Execute a single tool call requested by the model. `run_shell_command` only
+/// runs read-only commands; `may_run_command` refuses blocked patterns and asks
+/// the user before running anything beyond a read-only command.
+fn execute_tool_call(call: &llm::ToolCall) -> String {
+    match call.name.as_str() {
+        "run_shell_command" => {
+            let command = call
+                .arguments
+                .get("command")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            if command.trim().is_empty() {
+                return "Error: no command provided".to_string();
+            }
+            if safety::assess_risk(command) > safety::RiskLevel::Safe {
+                return format!(
+                    "Refused: '{}' is not a read-only command, so it can't be run automatically.",
+                    command
+                );
+            }
+            match std::process::Command::new("sh").arg("-c").arg(command).output() {
+                Ok(out) => {
+                    let mut combined = String::from_utf8_lossy(&out.stdout).to_string();
+                    if !out.status.success() {
+                        combined.push_str(&String::from_utf8_lossy(&out.stderr));
+                    }
+                    truncate_tool_output(combined)
+                }
+                Err(e) => format!("Error running command: {}", e),
+            }
+        }
+        "list_files" => {
+            let path = call
+                .arguments
+                .get("path")
+                .and_then(|v| v.as_str())
+                .unwrap_or(".");
+            match std::fs::read_dir(path) {
+                Ok(entries) => {
+                    let names: Vec<String> = entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.file_name().to_string_lossy().to_string())
+                        .collect();
+                    truncate_tool_output(names.join("\n"))
+                }
+                Err(e) => format!("Error listing '{}': {}", path, e),
+            }
+        }
+        "read_file" => {
+            let path = call.arguments.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            if path.is_empty() {
+                return "Error: no path provided".to_string();
+            }
+            match std::fs::read_to_string(path) {
+                Ok(contents) => truncate_tool_output(contents),
+                Err(e) => format!("Error reading '{}': {}", path, e),
+            }
+        }
+        "may_run_command" => {
+            let command = call
+                .arguments
+                .get("command")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            if command.trim().is_empty() {
+                return "Error: no command provided".to_string();
+            }
+            if safety::is_blocked(command) {
+                return format!("Refused: '{}' matches a blocked command pattern.", command);
+            }
+            let risk = safety::assess_risk(command);
+            if risk > safety::RiskLevel::Safe && !confirm_side_effecting_command(command, risk) {
+                return format!("Declined by user: '{}' was not run.", command);
+            }
+            match std::process::Command::new("sh").arg("-c").arg(command).output() {
+                Ok(out) => {
+                    let mut combined = String::from_utf8_lossy(&out.stdout).to_string();
+                    if !out.status.success() {
+                        combined.push_str(&String::from_utf8_lossy(&out.stderr));
+                    }
+                    truncate_tool_output(combined)
+                }
+                Err(e) => format!("Error running command: {}", e),
+            }
+        }
+        other => format!("Error: unknown tool '{}'", other),
+    }
+}
+
+/// True if an error came from `Provider::generate_with_tools`'s default fallback,
+/// i.e. the provider simply doesn't support tool calling.
+fn is_unsupported_tools_error(err: &anyhow::Error) -> bool {
+    err.to_string().contains("does not support tool calling")
+}
+
+/// Drive a multi-step tool-calling agent loop until the model returns a final
+/// answer or `AGENT_MAX_STEPS` is hit. Each turn is a blocking, non-streaming
+/// call that can't be interrupted mid-flight, so `abort` is only checked
+/// between turns — enough to stop a multi-step plan at the next tool-call
+/// boundary instead of waiting for all `AGENT_MAX_STEPS` to play out.
+fn run_agent_loop(
+    provider: &dyn llm::Provider,
+    messages: &mut Vec<llm::Message>,
+    max_tokens: u32,
+    verbose: bool,
+    abort: &llm::AbortSignal,
+) -> Result<String> {
+    let tools = local_tools();
+
+    for _ in 0..AGENT_MAX_STEPS {
+        if abort.is_aborted() {
+            bail!("Cancelled.");
+        }
+        match provider.generate_with_tools(messages, &tools, max_tokens)? {
+            llm::AgentTurn::Final(text) => return Ok(text),
+            llm::AgentTurn::ToolCalls(calls) => {
+                messages.push(llm::Message::assistant_tool_calls(calls.clone()));
+                for call in &calls {
+                    if verbose {
+                        eprintln!("{} tool call: {}({})", "debug".dimmed(), call.name, call.arguments);
+                    }
+                    let result = execute_tool_call(call);
+                    messages.push(llm::Message::tool_result(call.id.clone(), result));
+                }
+            }
+        }
+    }
+
+    bail!("Agent did not reach a final answer within {} steps.", AGENT_MAX_STEPS)
+}
+
 /// Generate a command string from a natural language query (Pure logic, no UI)
 pub fn generate_command(
     query: &str,
     provider_override: Option<&str>,
+    role: Option<&str>,
     verbose: bool,
+    refresh_tools: bool,
+    abort: &llm::AbortSignal,
 ) -> Result<String> {
     if query.trim().is_empty() {
         bail!("Please provide a query.");
     }
 
-    let provider = llm::get_provider(provider_override)?;
-
-    if !provider.is_available() {
-        bail!(
-            "Provider '{}' not ready. Run 'niko settings configure'.",
-            provider.name()
-        );
-    }
+    let role = role.map(config::get_role).transpose()?;
+    let name = llm::provider_name_for_role(provider_override, role.as_ref())?;
+    let provider = llm::get_provider_for_role(provider_override, role.as_ref())?;
 
     let ctx = prompt::gather_context();
     let mut system_prompt = prompt::cmd_system_prompt(&ctx);
 
     // Dynamic help discovery: run --help for tools mentioned in query
-    let help_context = prompt::discover_tool_help(query, verbose);
+    let help_context = prompt::discover_tool_help(query, verbose, refresh_tools);
     if !help_context.is_empty() {
         system_prompt.push_str(&help_context);
     }
 
-    // Non-streaming with retry — we need the full command to extract it safely
-    let response =
-        llm::generate_with_retry(provider.as_ref(), &system_prompt, query, CMD_MAX_TOKENS)?;
+    let mut messages = vec![llm::Message::system(&system_prompt)];
+    if let Some(r) = &role {
+        if !r.system_prompt.trim().is_empty() {
+            messages.push(llm::Message::system(&r.system_prompt));
+        }
+    } else if let Some(default_msg) = &config::get().default_system_message {
+        if !default_msg.trim().is_empty() {
+            messages.insert(0, llm::Message::system(default_msg));
+        }
+    }
+    messages.push(llm::Message::user(query));
+
+    let max_tokens = role
+        .as_ref()
+        .and_then(|r| r.max_tokens)
+        .unwrap_or(CMD_MAX_TOKENS);
+
+    if !provider.is_available() {
+        // The primary may still be reachable through one of its configured
+        // fallbacks; `generate_with_fallback` walks the chain and only fails
+        // once every provider in it has been tried.
+        let response = llm::generate_with_fallback(&name, &messages, max_tokens, verbose)?;
+        let command = safety::extract_command(&response);
+        if command.is_empty() {
+            bail!("Could not generate a command. Try being more specific.");
+        }
+        return Ok(command);
+    }
+
+    // Agentic path: let the model inspect the environment via tools before
+    // committing to a command. Providers that don't support tool calling, or
+    // that fail with a retryable error, fall back to a plain single-shot
+    // request against the provider's fallback chain.
+    let response = match run_agent_loop(provider.as_ref(), &mut messages, max_tokens, verbose, abort) {
+        Ok(text) => text,
+        Err(e) if is_unsupported_tools_error(&e) || llm::is_retryable_error(&e) => {
+            llm::generate_with_fallback(&name, &messages, max_tokens, verbose)?
+        }
+        Err(e) => return Err(e),
+    };
 
     let command = safety::extract_command(&response);
     if command.is_empty() {
@@ -47,17 +297,34 @@ pub fn generate_command(
 }
 
 /// Run the /cmd mode — translate natural language to shell commands (CLI wrapper)
-pub fn run(query: &str, provider_override: Option<&str>, verbose: bool) -> Result<()> {
+pub fn run(
+    query: &str,
+    provider_override: Option<&str>,
+    role: Option<&str>,
+    verbose: bool,
+    refresh_tools: bool,
+    abort: &llm::AbortSignal,
+) -> Result<()> {
     if query.trim().is_empty() {
         bail!("Please provide a query.\nUsage: niko cmd \"find all large files\"");
     }
 
-    // Check provider availability early to give specific UI feedback
-    let provider = llm::get_provider(provider_override)?;
+    let role_cfg = role.map(config::get_role).transpose()?;
+    let name = llm::provider_name_for_role(provider_override, role_cfg.as_ref())?;
+    let provider = llm::get_provider_for_role(provider_override, role_cfg.as_ref())?;
+
+    // Warn early if the primary is down, but don't bail — `generate_command`
+    // transparently falls back to the provider's configured `fallbacks`.
     if !provider.is_available() {
-        ui::print_warning(&format!("Provider '{}' not ready", provider.name()));
-        eprintln!("  Run: {}", "niko settings configure".cyan());
-        return Ok(());
+        let cfg = config::load()?;
+        let has_fallback = cfg.providers.get(&name).is_some_and(|p| !p.fallbacks.is_empty());
+        if has_fallback {
+            ui::print_dim(&format!("  '{}' not ready, trying fallbacks…", name));
+        } else {
+            ui::print_warning(&format!("Provider '{}' not ready", provider.name()));
+            eprintln!("  Run: {}", "niko settings configure".cyan());
+            return Ok(());
+        }
     }
 
     if verbose {
@@ -72,7 +339,7 @@ pub fn run(query: &str, provider_override: Option<&str>, verbose: bool) -> Resul
     item.start();
 
     let start = std::time::Instant::now();
-    let result = generate_command(query, provider_override, verbose);
+    let result = generate_command(query, provider_override, role, verbose, refresh_tools, abort);
     item.stop();
 
     let elapsed = start.elapsed();
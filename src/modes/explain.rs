@@ -3,11 +3,12 @@ use std::fs;
 use anyhow::{Result, bail};
 use colored::*;
 
-use crate::{chunker, llm, ui};
+use crate::{chunker, config, llm, ui};
 
-/// Run the /explain mode — explain code with chunking for large inputs
-pub fn run(file_path: Option<&str>, provider_override: Option<&str>, verbose: bool) -> Result<()> {
-    // Read the code input
+/// Read the code to explain from a file (printing a "lines loaded" box) or
+/// from stdin, trimmed — shared by the single-provider flow here and the
+/// multi-provider arena mode in `modes::arena`.
+pub(crate) fn read_code_input(file_path: Option<&str>) -> Result<String> {
     let code = if let Some(path) = file_path {
         let content = fs::read_to_string(path)
             .map_err(|e| anyhow::anyhow!("Failed to read file '{}': {}", path, e))?;
@@ -27,7 +28,19 @@ pub fn run(file_path: Option<&str>, provider_override: Option<&str>, verbose: bo
             .map_err(|e| anyhow::anyhow!("Failed to read input: {}", e))?
     };
 
-    let code = code.trim().to_string();
+    Ok(code.trim().to_string())
+}
+
+/// Run the /explain mode — explain code with chunking for large inputs
+pub fn run(
+    file_path: Option<&str>,
+    provider_override: Option<&str>,
+    role: Option<&str>,
+    verbose: bool,
+    json: bool,
+    abort: &llm::AbortSignal,
+) -> Result<()> {
+    let code = read_code_input(file_path)?;
 
     if code.is_empty() {
         bail!(
@@ -39,8 +52,15 @@ pub fn run(file_path: Option<&str>, provider_override: Option<&str>, verbose: bo
         );
     }
 
-    // Show collapsible code preview
-    ui::show_code_preview(&code);
+    // Show collapsible code preview, syntax-highlighted when we can tell
+    // the language from the file extension — skipped in `--json` mode so
+    // stdout carries nothing but the final JSON object.
+    let lang_hint = file_path
+        .and_then(|p| std::path::Path::new(p).extension())
+        .and_then(|e| e.to_str());
+    if !json {
+        ui::show_code_preview(&code, lang_hint);
+    }
 
     let line_count = code.lines().count();
     eprintln!();
@@ -51,13 +71,24 @@ pub fn run(file_path: Option<&str>, provider_override: Option<&str>, verbose: bo
     );
 
     // Get provider
-    let provider = llm::get_provider(provider_override)?;
-
-    if !provider.is_available() {
-        ui::print_warning(&format!("Provider '{}' not ready", provider.name()));
-        eprintln!("  Run: {}", "niko settings configure".cyan());
-        return Ok(());
-    }
+    let role_cfg = role.map(config::get_role).transpose()?;
+    let name = llm::provider_name_for_role(provider_override, role_cfg.as_ref())?;
+    let provider = llm::get_provider_for_role(provider_override, role_cfg.as_ref())?;
+
+    // If the primary isn't available, try its configured fallback chain
+    // before giving up.
+    let provider = if provider.is_available() {
+        provider
+    } else {
+        match llm::resolve_available_provider(&name) {
+            Ok((_, fallback)) => fallback,
+            Err(_) => {
+                ui::print_warning(&format!("Provider '{}' not ready", provider.name()));
+                eprintln!("  Run: {}", "niko settings configure".cyan());
+                return Ok(());
+            }
+        }
+    };
 
     if verbose {
         ui::print_dim(&format!("  provider: {}", provider.name()));
@@ -67,12 +98,32 @@ pub fn run(file_path: Option<&str>, provider_override: Option<&str>, verbose: bo
     let mut spinner = ui::Spinner::new("Analyzing code...");
     spinner.start();
 
-    let result = chunker::explain_code(&code, provider.as_ref(), verbose);
+    let role_prompt = role_cfg
+        .as_ref()
+        .map(|r| r.system_prompt.as_str())
+        .filter(|p| !p.trim().is_empty());
+    // No provider here advertises its context window yet, so chunking falls
+    // back to the fixed line-count budget.
+    let result = chunker::explain_code(
+        &code,
+        lang_hint,
+        provider.as_ref(),
+        None,
+        None,
+        verbose,
+        role_prompt,
+        None::<fn(&str)>,
+        Some(abort),
+    );
     spinner.stop();
 
     match result {
         Ok(explanation) => {
-            ui::display_explanation(&explanation);
+            if json {
+                println!("{}", chunker::to_json(&explanation)?);
+            } else {
+                ui::display_explanation(&explanation);
+            }
         }
         Err(e) => {
             ui::print_error("Code analysis failed");
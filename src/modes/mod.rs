@@ -0,0 +1,6 @@
+pub mod arena;
+pub mod cmd;
+pub mod explain;
+pub mod serve;
+pub mod settings;
+pub mod watch;
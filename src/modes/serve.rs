@@ -0,0 +1,292 @@
+use std::io::Read;
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::config;
+use crate::llm::{self, Message, Provider};
+use crate::ui;
+
+/// Max tokens for a chat completion when the request doesn't specify one
+const DEFAULT_MAX_TOKENS: u32 = 2048;
+
+#[derive(serde::Deserialize)]
+struct ChatRequest {
+    #[serde(default)]
+    messages: Vec<WireMessage>,
+    #[serde(default)]
+    stream: bool,
+    #[serde(default)]
+    max_tokens: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+struct WireMessage {
+    role: String,
+    #[serde(default)]
+    content: String,
+}
+
+impl From<&WireMessage> for Message {
+    fn from(m: &WireMessage) -> Self {
+        match m.role.as_str() {
+            "system" => Message::system(m.content.clone()),
+            "assistant" => Message::assistant(m.content.clone()),
+            _ => Message::user(m.content.clone()),
+        }
+    }
+}
+
+/// Generates a `chatcmpl-`-prefixed id the same way `tui::fetch_cache_path`
+/// derives a stable-looking hex tag: hash the current instant, format as hex.
+fn completion_id() -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    std::time::Instant::now().hash(&mut hasher);
+    format!("chatcmpl-{:016x}", hasher.finish())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Run the `niko serve` mode — expose the active provider as an
+/// OpenAI-compatible HTTP endpoint (`POST /v1/chat/completions`,
+/// `GET /v1/models`) so other OpenAI-client tooling can point at niko
+/// regardless of which backend is actually configured.
+///
+/// There is no authentication on this endpoint, so by default it only binds
+/// `127.0.0.1` — anyone who can reach it can make requests on the
+/// configured provider's credentials. Pass `bind_all` to opt into listening
+/// on `0.0.0.0` for access from other hosts on the network.
+pub fn run(port: u16, provider_override: Option<&str>, bind_all: bool, verbose: bool) -> Result<()> {
+    let provider = llm::get_provider(provider_override)?;
+    if !provider.is_available() {
+        anyhow::bail!(
+            "Provider '{}' not ready. Run 'niko settings configure'.",
+            provider.name()
+        );
+    }
+
+    let host = if bind_all { "0.0.0.0" } else { "127.0.0.1" };
+    let server = tiny_http::Server::http(format!("{}:{}", host, port))
+        .map_err(|e| anyhow::anyhow!("Failed to bind port {}: {}", port, e))?;
+
+    ui::print_success(&format!(
+        "Serving '{}' as an OpenAI-compatible API on http://{}:{}",
+        provider.name(),
+        host,
+        port
+    ));
+    if bind_all {
+        ui::print_dim("  Bound to 0.0.0.0 — reachable from other hosts. This endpoint has no authentication.");
+    }
+    ui::print_dim("  POST /v1/chat/completions");
+    ui::print_dim("  GET  /v1/models");
+    eprintln!();
+
+    for mut request in server.incoming_requests() {
+        let method = request.method().clone();
+        let url = request.url().to_string();
+
+        if verbose {
+            eprintln!("{} {:?} {}", "debug".dimmed(), method, url);
+        }
+
+        match (method, url.as_str()) {
+            (tiny_http::Method::Get, "/v1/models") => {
+                let result = handle_models();
+                match result {
+                    Ok(json) => respond_json(request, 200, &json),
+                    Err(e) => respond_json(request, 500, &error_body(&e.to_string())),
+                }
+            }
+            (tiny_http::Method::Post, "/v1/chat/completions") => {
+                let mut body = String::new();
+                if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                    respond_json(request, 400, &error_body(&format!("Failed to read body: {}", e)));
+                    continue;
+                }
+                handle_chat_completions(provider.as_ref(), &body, request);
+            }
+            _ => respond_json(request, 404, &error_body("Not found. Try /v1/chat/completions or /v1/models.")),
+        }
+    }
+
+    Ok(())
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": { "message": message } }).to_string()
+}
+
+fn respond_json(request: tiny_http::Request, status: u16, body: &str) {
+    let header = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("static header is valid");
+    let response = tiny_http::Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    let _ = request.respond(response);
+}
+
+/// `Read` adapter over a channel of already-formatted SSE chunks (`data:
+/// ...\n\n`), so `tiny_http` can stream a response body as it's produced
+/// instead of needing it all buffered up front. `read` blocks on `rx.recv()`
+/// whenever its pending chunk is exhausted, so each `send` on the other end
+/// becomes a chunk written to the client roughly as soon as it happens; the
+/// sender dropping (generation finished) surfaces here as a `recv()` error,
+/// which we report as EOF.
+struct ChannelReader {
+    rx: mpsc::Receiver<String>,
+    pending: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.pending.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.pending = chunk.into_bytes();
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.pending.len() - self.pos);
+        out[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Responds with `text/event-stream`, reading the body incrementally off
+/// `rx` (see `ChannelReader`) rather than from a pre-built `String`, so
+/// `tiny_http`'s chunked-transfer writer flushes each SSE chunk to the
+/// client as soon as it's sent rather than only once the whole response is
+/// assembled.
+fn respond_sse_stream(request: tiny_http::Request, rx: mpsc::Receiver<String>) {
+    let header = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/event-stream"[..])
+        .expect("static header is valid");
+    let reader = ChannelReader {
+        rx,
+        pending: Vec::new(),
+        pos: 0,
+    };
+    let response = tiny_http::Response::new(tiny_http::StatusCode(200), vec![header], reader, None, None);
+    let _ = request.respond(response);
+}
+
+/// List models across every configured provider (not just the one serving
+/// `/v1/chat/completions`), so OpenAI-compatible clients can see the full
+/// catalog behind this one endpoint. Each id is prefixed with its provider
+/// name (`ollama/llama3`, `claude/claude-sonnet-4-20250514`, ...).
+/// `/v1/chat/completions` does not read the request's `model` field: every
+/// request goes to the provider the server was started with. Providers
+/// that fail to construct or aren't available are skipped rather than
+/// failing the whole listing.
+fn handle_models() -> Result<String> {
+    let cfg = config::load()?;
+    let mut data = Vec::new();
+
+    for (name, pcfg) in &cfg.providers {
+        let provider = match llm::from_config(name, pcfg) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        if !provider.is_available() {
+            continue;
+        }
+        if let Ok(models) = provider.list_models() {
+            for m in models {
+                data.push(serde_json::json!({
+                    "id": format!("{}/{}", name, m.id),
+                    "object": "model",
+                    "owned_by": name,
+                }));
+            }
+        }
+    }
+
+    Ok(serde_json::json!({ "object": "list", "data": data }).to_string())
+}
+
+fn handle_chat_completions(provider: &dyn Provider, body: &str, request: tiny_http::Request) {
+    let parsed: ChatRequest = match serde_json::from_str(body) {
+        Ok(p) => p,
+        Err(e) => {
+            respond_json(request, 400, &error_body(&format!("Invalid request body: {}", e)));
+            return;
+        }
+    };
+
+    let messages: Vec<Message> = parsed.messages.iter().map(Message::from).collect();
+    if messages.is_empty() {
+        respond_json(request, 400, &error_body("'messages' must not be empty"));
+        return;
+    }
+
+    let max_tokens = parsed.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    let id = completion_id();
+    let created = unix_now();
+
+    if parsed.stream {
+        let (tx, rx) = mpsc::channel::<String>();
+        let model_name = provider.name().to_string();
+
+        thread::scope(|scope| {
+            // `move` so `tx` is owned by (and dropped with) this closure once
+            // generation finishes, rather than living on in the outer scope
+            // until after `respond_sse_stream` returns below — otherwise the
+            // `ChannelReader` never sees end-of-stream and blocks forever.
+            scope.spawn(move || {
+                let result = llm::generate_streaming(provider, &messages, max_tokens, &mut |token: &str| {
+                    let event = serde_json::json!({
+                        "id": id,
+                        "object": "chat.completion.chunk",
+                        "created": created,
+                        "model": model_name,
+                        "choices": [{ "index": 0, "delta": { "content": token }, "finish_reason": null }],
+                    });
+                    let _ = tx.send(format!("data: {}\n\n", event));
+                });
+
+                match result {
+                    Ok(_) => {
+                        let _ = tx.send("data: [DONE]\n\n".to_string());
+                    }
+                    Err(e) => {
+                        let event = serde_json::json!({ "error": { "message": e.to_string() } });
+                        let _ = tx.send(format!("data: {}\n\n", event));
+                    }
+                }
+            });
+
+            respond_sse_stream(request, rx);
+        });
+        return;
+    }
+
+    match llm::generate_with_retry(provider, &messages, max_tokens) {
+        Ok(text) => {
+            let completion = serde_json::json!({
+                "id": id,
+                "object": "chat.completion",
+                "created": created,
+                "model": provider.name(),
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": text },
+                    "finish_reason": "stop",
+                }],
+            });
+            respond_json(request, 200, &completion.to_string());
+        }
+        Err(e) => respond_json(request, 500, &error_body(&e.to_string())),
+    }
+}
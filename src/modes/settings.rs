@@ -1,14 +1,19 @@
+use std::collections::HashMap;
 use std::io::{self, Write};
 
 use anyhow::Result;
 use colored::*;
 
-use crate::config::{self, ProviderConfig};
+use crate::config::{self, ProviderConfig, RoleConfig};
 use crate::llm;
 use crate::llm::Provider;
 use crate::llm::ollama;
 use crate::ui;
 
+/// Fallback context window offered when configuring a provider, since
+/// there's usually no API to ask the model what it actually supports.
+const DEFAULT_CONTEXT_WINDOW: u32 = 4096;
+
 /// Settings action types
 pub enum Action {
     Show,
@@ -16,6 +21,17 @@ pub enum Action {
     Set { key: String, value: String },
     Init,
     Path,
+    Preload,
+    Test { provider: Option<String> },
+    RoleList,
+    RoleCreate {
+        name: String,
+        system_prompt: String,
+        provider: Option<String>,
+        model: Option<String>,
+        max_tokens: Option<u32>,
+    },
+    RoleDelete { name: String },
 }
 
 /// Run the /settings mode
@@ -29,6 +45,17 @@ pub fn run(action: Option<Action>) -> Result<()> {
             println!("{}", config::config_path().display());
             Ok(())
         }
+        Some(Action::Preload) => preload_active_provider(),
+        Some(Action::Test { provider }) => test_provider(provider.as_deref()),
+        Some(Action::RoleList) => list_roles(),
+        Some(Action::RoleCreate {
+            name,
+            system_prompt,
+            provider,
+            model,
+            max_tokens,
+        }) => create_role(&name, system_prompt, provider, model, max_tokens),
+        Some(Action::RoleDelete { name }) => delete_role(&name),
     }
 }
 
@@ -99,6 +126,9 @@ fn show_config() -> Result<()> {
             };
             ui::box_kv("    Status", &status);
             ui::box_kv("    URL   ", &pcfg.base_url.dimmed().to_string());
+            if !pcfg.api_key.is_empty() {
+                ui::box_kv("    Key   ", &format_key(&pcfg.api_key));
+            }
         } else {
             ui::box_kv("    Key   ", &format_key(&pcfg.api_key));
             ui::box_kv("    URL   ", &pcfg.base_url.dimmed().to_string());
@@ -109,6 +139,19 @@ fn show_config() -> Result<()> {
         } else {
             ui::box_kv("    Model ", &pcfg.model.cyan().to_string());
         }
+
+        if let Some(ctx) = pcfg.context_window {
+            ui::box_kv("    Context", &format!("{} tokens", ctx).dimmed().to_string());
+        }
+
+        if !pcfg.fallbacks.is_empty() {
+            ui::box_kv("    Fallbacks", &pcfg.fallbacks.join(" → ").dimmed().to_string());
+        }
+    }
+
+    if let Some(msg) = &cfg.default_system_message {
+        ui::box_sep();
+        ui::box_kv("  System message", &truncate_for_display(msg).dimmed().to_string());
     }
 
     ui::box_sep();
@@ -237,8 +280,40 @@ fn configure_ollama(name: &str, default_url: &str) -> Result<()> {
     );
     ui::box_sep();
 
+    // Optional API key, for servers sitting behind an authenticating reverse
+    // proxy or a hosted Ollama endpoint — a plain local install can skip this.
+    let env_key = std::env::var("OLLAMA_API_KEY").ok();
+    let api_key = if let Some(ref key) = env_key {
+        ui::box_empty();
+        ui::box_line(&format!("  Found key in {}", "$OLLAMA_API_KEY".cyan()));
+        ui::box_bottom();
+        eprintln!();
+
+        let use_it = prompt_input("  Use this key? [Y/n]: ")?;
+        if use_it.trim().is_empty() || use_it.trim().to_lowercase().starts_with('y') {
+            key.clone()
+        } else {
+            prompt_input("  API key (leave blank if none): ")?
+                .trim()
+                .to_string()
+        }
+    } else {
+        ui::box_empty();
+        ui::box_line(&"  API key (leave blank for a plain local install):".dimmed().to_string());
+        ui::box_bottom();
+        eprintln!();
+
+        prompt_input("  API key: ")?.trim().to_string()
+    };
+
+    let auth_options = if api_key.is_empty() {
+        HashMap::new()
+    } else {
+        HashMap::from([("auth_token".to_string(), api_key.clone())])
+    };
+
     // List local models
-    let provider = llm::ollama::OllamaProvider::new(default_url, "")?;
+    let provider = llm::ollama::OllamaProvider::new(default_url, "", auth_options)?;
     let local_models = provider.list_models().unwrap_or_default();
 
     if !local_models.is_empty() {
@@ -310,12 +385,17 @@ fn configure_ollama(name: &str, default_url: &str) -> Result<()> {
         }
     }
 
-    config::upsert_provider(name, ProviderConfig {
+    let context_window = prompt_context_window(DEFAULT_CONTEXT_WINDOW)?;
+
+    let pcfg = ProviderConfig {
         kind: "ollama".into(),
         base_url: default_url.into(),
         model: selected_model.clone(),
+        api_key: api_key.clone(),
+        context_window,
         ..Default::default()
-    })?;
+    };
+    config::upsert_provider(name, pcfg.clone())?;
     config::set_active_provider(name)?;
 
     eprintln!();
@@ -326,6 +406,11 @@ fn configure_ollama(name: &str, default_url: &str) -> Result<()> {
     ));
     eprintln!();
 
+    let warm = prompt_input("  Warm up model now? [Y/n]: ")?;
+    if warm.trim().is_empty() || warm.trim().to_lowercase().starts_with('y') {
+        preload_model(name, &pcfg)?;
+    }
+
     Ok(())
 }
 
@@ -500,11 +585,14 @@ fn configure_custom() -> Result<()> {
         .trim()
         .to_string();
 
+    let context_window = prompt_context_window(DEFAULT_CONTEXT_WINDOW)?;
+
     config::upsert_provider(&name, ProviderConfig {
         kind: "openai_compat".into(),
         api_key,
         base_url,
         model: model.clone(),
+        context_window,
         ..Default::default()
     })?;
     config::set_active_provider(&name)?;
@@ -531,6 +619,14 @@ fn set_config(key: &str, value: &str) -> Result<()> {
                 config::set_active_provider(value)?;
                 ui::print_success(&format!("Active provider → {}", value.cyan()));
             }
+            "system_message" => {
+                config::set_default_system_message(value)?;
+                if value.is_empty() {
+                    ui::print_success("Default system message cleared");
+                } else {
+                    ui::print_success(&format!("Default system message → {}", truncate_for_display(value).cyan()));
+                }
+            }
             _ => {
                 anyhow::bail!(
                     "Unknown setting: {}\nUsage: niko settings set <provider>.<field> <value>",
@@ -565,6 +661,190 @@ fn init_config() -> Result<()> {
     Ok(())
 }
 
+// ─── Preload ────────────────────────────────────────────────────────────────
+
+fn preload_active_provider() -> Result<()> {
+    let (name, pcfg) = config::active_provider()?;
+    preload_model(&name, &pcfg)
+}
+
+/// Issues a minimal generate request to force `pcfg`'s model into memory,
+/// so the first real request doesn't pay Ollama's first-load cost. A no-op
+/// for other provider kinds, which don't have this problem.
+fn preload_model(name: &str, pcfg: &ProviderConfig) -> Result<()> {
+    if pcfg.kind != "ollama" {
+        ui::print_dim(&format!("'{}' is not an Ollama provider, nothing to preload", name));
+        return Ok(());
+    }
+
+    let provider = llm::from_config(name, pcfg)?;
+
+    let mut spinner = ui::Spinner::new("Loading model into memory...");
+    spinner.start();
+    let result = provider.generate(&[llm::Message::user("")], 1);
+    spinner.stop();
+    result?;
+
+    ui::print_success(&format!("{} is loaded and ready", pcfg.model));
+    Ok(())
+}
+
+// ─── Test ───────────────────────────────────────────────────────────────────
+
+/// Runs the same `list_models` probe the configure wizards already use as an
+/// implicit health check, as a standalone diagnostic for an already-saved
+/// provider (e.g. after hand-editing the config, or when chat starts failing).
+fn test_provider(provider: Option<&str>) -> Result<()> {
+    let cfg = config::load()?;
+    let name = provider.map(|s| s.to_string()).unwrap_or_else(|| cfg.active_provider.clone());
+    let pcfg = cfg.providers.get(&name).cloned().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Provider '{}' not configured.\nRun 'niko settings configure' to add it.",
+            name
+        )
+    })?;
+
+    eprintln!();
+    ui::box_top(&format!("{}", format!("Testing {}", name).bold()));
+    ui::box_empty();
+
+    if pcfg.kind == "ollama" {
+        let installed = ollama::is_ollama_installed();
+        let running = ollama::is_ollama_running();
+        ui::box_kv(
+            "  Installed",
+            &if installed { "✓ yes".green().to_string() } else { "✗ no".red().to_string() },
+        );
+        ui::box_kv(
+            "  Running  ",
+            &if running { "✓ yes".green().to_string() } else { "✗ no".red().to_string() },
+        );
+
+        if !running {
+            ui::box_empty();
+            ui::box_bottom();
+            eprintln!();
+            ui::print_warning("Ollama is not running, skipping model fetch");
+            return Ok(());
+        }
+    } else {
+        ui::box_kv(
+            "  Key    ",
+            &if pcfg.api_key.is_empty() { "✗ missing".red().to_string() } else { "✓ set".green().to_string() },
+        );
+        ui::box_kv("  URL    ", &pcfg.base_url.dimmed().to_string());
+    }
+
+    let provider_impl = llm::from_config(&name, &pcfg)?;
+
+    let start = std::time::Instant::now();
+    let result = provider_impl.list_models();
+    let elapsed = start.elapsed();
+
+    ui::box_kv("  Latency ", &format!("{}ms", elapsed.as_millis()).dimmed().to_string());
+
+    match result {
+        Ok(models) => {
+            ui::box_kv("  Reachable", &"✓ yes".green().to_string());
+            ui::box_kv("  Models  ", &models.len().to_string());
+            ui::box_empty();
+            ui::box_bottom();
+            eprintln!();
+            ui::print_success(&format!(
+                "{} is reachable ({} models, {}ms)",
+                name,
+                models.len(),
+                elapsed.as_millis()
+            ));
+        }
+        Err(e) => {
+            ui::box_kv("  Reachable", &"✗ no".red().to_string());
+            ui::box_empty();
+            ui::box_bottom();
+            eprintln!();
+            ui::print_warning(&format!("{} is not reachable: {}", name, e));
+        }
+    }
+
+    Ok(())
+}
+
+// ─── Roles ──────────────────────────────────────────────────────────────────
+
+fn list_roles() -> Result<()> {
+    let cfg = config::load()?;
+
+    eprintln!();
+    ui::box_top(&format!("{}", "Saved Roles".bold()));
+
+    if cfg.roles.is_empty() {
+        ui::box_empty();
+        ui::box_line(&"  (no roles saved)".dimmed().to_string());
+        ui::box_empty();
+        ui::box_line(&"  niko settings role create <name> -s \"<system prompt>\"".dimmed().to_string());
+    } else {
+        let mut names: Vec<_> = cfg.roles.keys().cloned().collect();
+        names.sort();
+        for (i, name) in names.iter().enumerate() {
+            let role = &cfg.roles[name];
+            if i > 0 {
+                ui::box_sep();
+            }
+            ui::box_line(&format!("  {} {}", "▸".dimmed(), name.bold()));
+            ui::box_kv("    Prompt", &truncate_for_display(&role.system_prompt));
+            if let Some(provider) = &role.provider {
+                ui::box_kv("    Provider", &provider.cyan().to_string());
+            }
+            if let Some(model) = &role.model {
+                ui::box_kv("    Model", &model.cyan().to_string());
+            }
+            if let Some(max_tokens) = role.max_tokens {
+                ui::box_kv("    Max tokens", &max_tokens.to_string());
+            }
+        }
+    }
+
+    ui::box_bottom();
+    eprintln!();
+
+    Ok(())
+}
+
+fn create_role(
+    name: &str,
+    system_prompt: String,
+    provider: Option<String>,
+    model: Option<String>,
+    max_tokens: Option<u32>,
+) -> Result<()> {
+    config::upsert_role(name, RoleConfig {
+        system_prompt,
+        provider,
+        model,
+        max_tokens,
+    })?;
+
+    ui::print_success(&format!("Role '{}' saved", name.cyan()));
+    ui::print_dim(&format!("  Use with: niko --role {} ...", name));
+
+    Ok(())
+}
+
+fn delete_role(name: &str) -> Result<()> {
+    config::delete_role(name)?;
+    ui::print_success(&format!("Role '{}' deleted", name.cyan()));
+    Ok(())
+}
+
+fn truncate_for_display(s: &str) -> String {
+    let s = s.trim();
+    if s.len() > 60 {
+        format!("{}…", &s[..60])
+    } else {
+        s.to_string()
+    }
+}
+
 // ─── Helpers ────────────────────────────────────────────────────────────────
 
 fn format_key(key: &str) -> String {
@@ -584,3 +864,25 @@ fn prompt_input(prompt: &str) -> Result<String> {
     io::stdin().read_line(&mut input)?;
     Ok(input.trim_end_matches('\n').trim_end_matches('\r').to_string())
 }
+
+/// Prompts for `ProviderConfig.context_window`. There's no API to infer a
+/// sensible default (Ollama doesn't report a model's built-in size, and
+/// many OpenAI-compatible endpoints don't either), so blank accepts
+/// `default_tokens` and `0`/"none" opts out of setting it at all.
+fn prompt_context_window(default_tokens: u32) -> Result<Option<u32>> {
+    let raw = prompt_input(&format!("  Context window in tokens [{}]: ", default_tokens))?;
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(Some(default_tokens));
+    }
+    if raw == "0" || raw.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    match raw.parse::<u32>() {
+        Ok(n) => Ok(Some(n)),
+        Err(_) => {
+            ui::print_warning("Not a number, using default");
+            Ok(Some(default_tokens))
+        }
+    }
+}
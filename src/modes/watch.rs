@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use notify::Watcher;
+
+use crate::{chunker, config, llm, ui};
+
+/// How long to wait after the first fs event before re-explaining, so an
+/// editor's "truncate, then rewrite" save (or several rapid saves) only
+/// triggers one re-analysis instead of one per intermediate write.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Run `niko explain --watch`: explain `file_path` once immediately, then
+/// block watching it for saves, re-explaining after each debounced burst of
+/// writes. Every re-run goes through the same content-hash cache as a plain
+/// `niko explain`, so only the chunks that actually changed since the last
+/// save cost an LLM call. Each re-run first lists the chunks added, changed
+/// and removed since the previous run, then prints the full explanation again.
+pub fn run(
+    file_path: &str,
+    provider_override: Option<&str>,
+    role: Option<&str>,
+    verbose: bool,
+    json: bool,
+    abort: &llm::AbortSignal,
+) -> Result<()> {
+    let path = Path::new(file_path);
+    if !path.is_file() {
+        bail!("'{}' is not a file", file_path);
+    }
+
+    let role_cfg = role.map(config::get_role).transpose()?;
+    let name = llm::provider_name_for_role(provider_override, role_cfg.as_ref())?;
+    let provider = llm::get_provider_for_role(provider_override, role_cfg.as_ref())?;
+
+    let provider = if provider.is_available() {
+        provider
+    } else {
+        match llm::resolve_available_provider(&name) {
+            Ok((_, fallback)) => fallback,
+            Err(_) => {
+                ui::print_warning(&format!("Provider '{}' not ready", provider.name()));
+                eprintln!("  Run: {}", "niko settings configure".cyan());
+                return Ok(());
+            }
+        }
+    };
+
+    let role_prompt = role_cfg
+        .as_ref()
+        .map(|r| r.system_prompt.as_str())
+        .filter(|p| !p.trim().is_empty());
+    let lang_hint = path.extension().and_then(|e| e.to_str());
+
+    eprintln!();
+    eprintln!(
+        "  {} Watching {} for changes ({} to stop)…",
+        "👁".to_string(),
+        file_path.cyan(),
+        "Ctrl+C".dimmed()
+    );
+
+    let mut previous: Option<chunker::ExplainResult> = None;
+    previous = explain_and_report(path, lang_hint, provider.as_ref(), role_prompt, verbose, json, previous.as_ref(), abort)?;
+
+    let (raw_tx, raw_rx) = mpsc::channel::<notify::Event>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = raw_tx.send(event);
+        }
+    })
+    .context("Failed to start file watcher")?;
+
+    // Watch the parent directory rather than the file itself: many editors
+    // replace the file (unlink + rewrite) instead of writing in place, which
+    // some platforms stop notifying on otherwise.
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let watch_dir = canonical.parent().map(Path::to_path_buf).unwrap_or_else(|| Path::new(".").to_path_buf());
+    watcher
+        .watch(&watch_dir, notify::RecursiveMode::NonRecursive)
+        .context("Failed to watch file")?;
+
+    loop {
+        // Poll rather than block indefinitely on `recv()` so Ctrl+C is
+        // noticed while idle between saves, not just mid-generation.
+        let first = loop {
+            if abort.is_aborted() {
+                return Ok(());
+            }
+            match raw_rx.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(event) => break event,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()), // watcher dropped
+            }
+        };
+        if !first.paths.iter().any(|p| p == &canonical) {
+            continue;
+        }
+
+        // Drain the rest of this burst so N rapid saves coalesce into one re-run.
+        while raw_rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+
+        previous = explain_and_report(path, lang_hint, provider.as_ref(), role_prompt, verbose, json, previous.as_ref(), abort)?;
+    }
+}
+
+fn explain_and_report(
+    path: &Path,
+    lang_hint: Option<&str>,
+    provider: &dyn llm::Provider,
+    role_prompt: Option<&str>,
+    verbose: bool,
+    json: bool,
+    previous: Option<&chunker::ExplainResult>,
+    abort: &llm::AbortSignal,
+) -> Result<Option<chunker::ExplainResult>> {
+    let code = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", path.display(), e))?;
+    let code = code.trim();
+
+    if code.is_empty() {
+        ui::print_warning(&format!("'{}' is empty, skipping", path.display()));
+        return Ok(previous.cloned());
+    }
+
+    if !json {
+        eprintln!();
+        ui::print_rule();
+        println!(
+            "  {} {}",
+            "Re-explaining:".dimmed(),
+            path.display().to_string().bold()
+        );
+    }
+
+    let result = chunker::explain_code(code, lang_hint, provider, None, None, verbose, role_prompt, None::<fn(&str)>, Some(abort))?;
+
+    if json {
+        println!("{}", chunker::to_json(&result)?);
+    } else {
+        print_chunk_diff(previous, &result);
+        ui::display_explanation(&result);
+    }
+
+    Ok(Some(result))
+}
+
+/// Print which chunks are new, changed, or gone relative to `previous`,
+/// keyed by line range — a chunk whose range and explanation are both
+/// unchanged is a cache hit and isn't worth mentioning.
+fn print_chunk_diff(previous: Option<&chunker::ExplainResult>, current: &chunker::ExplainResult) {
+    let Some(previous) = previous else {
+        println!(
+            "  {} {} chunk(s) analyzed",
+            "+".green().bold(),
+            current.total_chunks
+        );
+        return;
+    };
+
+    let prev_by_range: HashMap<(usize, usize), &str> = previous
+        .chunk_explanations
+        .iter()
+        .map(|c| ((c.start_line, c.end_line), c.explanation.as_str()))
+        .collect();
+    let cur_by_range: HashMap<(usize, usize), &str> = current
+        .chunk_explanations
+        .iter()
+        .map(|c| ((c.start_line, c.end_line), c.explanation.as_str()))
+        .collect();
+
+    for chunk in &current.chunk_explanations {
+        let key = (chunk.start_line, chunk.end_line);
+        match prev_by_range.get(&key) {
+            None => println!(
+                "  {} lines {}-{} (new)",
+                "+".green().bold(),
+                chunk.start_line,
+                chunk.end_line
+            ),
+            Some(old) if *old != chunk.explanation => println!(
+                "  {} lines {}-{} (changed)",
+                "~".yellow().bold(),
+                chunk.start_line,
+                chunk.end_line
+            ),
+            _ => {}
+        }
+    }
+
+    for chunk in &previous.chunk_explanations {
+        let key = (chunk.start_line, chunk.end_line);
+        if !cur_by_range.contains_key(&key) {
+            println!(
+                "  {} lines {}-{} (removed)",
+                "-".red().bold(),
+                chunk.start_line,
+                chunk.end_line
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::env;
+use std::path::PathBuf;
 use std::process::Command;
 
 /// System context information for prompt generation
@@ -8,19 +9,120 @@ pub struct SystemContext {
     pub shell: String,
     pub working_dir: String,
     pub available_tools: Vec<String>,
+    pub aliases: Vec<(String, String)>,
+    pub functions: Vec<String>,
+    pub project: Option<ProjectContext>,
+}
+
+/// Repository/project state, gathered cheaply from `git` plus lockfile
+/// sniffing — omitted entirely when the cwd isn't inside a git work tree.
+pub struct ProjectContext {
+    pub branch: String,
+    pub ahead: u32,
+    pub behind: u32,
+    pub dirty: bool,
+    pub staged: bool,
+    pub project_types: Vec<String>,
 }
 
 /// Gather system context (OS, shell, cwd, available tools)
 pub fn gather_context() -> SystemContext {
+    let shell = detect_shell();
+    let (aliases, functions) = detect_aliases_and_functions(&shell);
+
     SystemContext {
         os: std::env::consts::OS.to_string(),
         arch: std::env::consts::ARCH.to_string(),
-        shell: detect_shell(),
+        shell,
         working_dir: env::current_dir()
             .map(|p| p.display().to_string())
             .unwrap_or_else(|_| "unknown".into()),
         available_tools: detect_tools(),
+        aliases,
+        functions,
+        project: gather_project_context(),
+    }
+}
+
+/// Mirrors oh-my-zsh's `git_current_branch`/`git_commits_ahead` approach:
+/// a handful of cheap `git` invocations rather than parsing `git status`'s
+/// long-form output. Returns `None` outside a work tree.
+fn gather_project_context() -> Option<ProjectContext> {
+    let is_work_tree = Command::new("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim() == "true")
+        .unwrap_or(false);
+
+    if !is_work_tree {
+        return None;
+    }
+
+    let branch = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|| "HEAD".to_string());
+
+    let (ahead, behind) = Command::new("git")
+        .args(["rev-list", "--left-right", "--count", "@{upstream}...HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| {
+            let text = String::from_utf8_lossy(&o.stdout).trim().to_string();
+            let (b, a) = text.split_once(char::is_whitespace)?;
+            Some((a.trim().parse().unwrap_or(0), b.trim().parse().unwrap_or(0)))
+        })
+        .unwrap_or((0, 0));
+
+    let porcelain = Command::new("git")
+        .args(["status", "--porcelain"])
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default();
+    let dirty = porcelain.lines().any(|l| l.len() >= 2 && l.as_bytes()[1] != b' ');
+    let staged = porcelain.lines().any(|l| l.len() >= 2 && l.as_bytes()[0] != b' ' && l.as_bytes()[0] != b'?');
+
+    Some(ProjectContext {
+        branch,
+        ahead,
+        behind,
+        dirty,
+        staged,
+        project_types: detect_project_types(),
+    })
+}
+
+/// Sniffs the cwd for lockfiles/manifests that identify the project's
+/// package manager / language toolchain.
+fn detect_project_types() -> Vec<String> {
+    let markers: &[(&str, &str)] = &[
+        ("Cargo.toml", "rust/cargo"),
+        ("package-lock.json", "node/npm"),
+        ("yarn.lock", "node/yarn"),
+        ("pnpm-lock.yaml", "node/pnpm"),
+        ("package.json", "node/npm"),
+        ("go.mod", "go"),
+        ("requirements.txt", "python/pip"),
+        ("pyproject.toml", "python/poetry"),
+        ("Gemfile", "ruby/bundler"),
+        ("composer.json", "php/composer"),
+    ];
+
+    let mut seen = std::collections::HashSet::new();
+    let mut out = Vec::new();
+    for (file, kind) in markers {
+        if std::path::Path::new(file).is_file() && seen.insert(*kind) {
+            out.push(kind.to_string());
+        }
     }
+    out
 }
 
 /// Build the system prompt for command generation mode
@@ -79,12 +181,14 @@ RULES:
 6. For truly catastrophic commands (e.g., `rm -rf /`, format disk), output:
    echo "Declined: <specific reason>"
 7. NEVER fabricate flags — only use flags you are certain exist for that tool
+8. If an existing alias or function (see SYSTEM) already matches the user's intent, prefer emitting it over the command it expands to (e.g. emit `k get pods` instead of `kubectl get pods` if `k` is aliased to `kubectl`)
+9. If SYSTEM shows a dirty or unpushed-commits repo, warn (via `echo "Warning: ..."` prefixed to the command, or a declined response for rule 6) before history-rewriting or hard-reset operations; use the package manager matching the detected project type
 {os_specific}
 
 SYSTEM:
 - OS: {os}  |  Arch: {arch}  |  Shell: {shell}
 - CWD: {cwd}
-- Tools: {tools}
+- Tools: {tools}{aliases}{functions}{project}
 
 EXAMPLES — Files & Search:
 "find large files over 100MB" → find . -type f -size +100M -exec ls -lh {{}} +
@@ -155,9 +259,61 @@ Command:"#,
         cwd = ctx.working_dir,
         tools = ctx.available_tools.join(", "),
         os_specific = os_specific,
+        aliases = format_aliases(&ctx.aliases),
+        functions = format_functions(&ctx.functions),
+        project = format_project(ctx.project.as_ref()),
     )
 }
 
+/// Renders the `- Git: ...` SYSTEM line, or nothing outside a work tree.
+fn format_project(project: Option<&ProjectContext>) -> String {
+    let Some(p) = project else { return String::new() };
+
+    let mut status = Vec::new();
+    if p.staged {
+        status.push("staged changes".to_string());
+    }
+    if p.dirty {
+        status.push("unstaged changes".to_string());
+    }
+    if status.is_empty() {
+        status.push("clean".to_string());
+    }
+
+    let mut line = format!(
+        "\n- Git: branch `{}`, {} ahead / {} behind upstream, {}",
+        p.branch,
+        p.ahead,
+        p.behind,
+        status.join(", ")
+    );
+    if !p.project_types.is_empty() {
+        line.push_str(&format!("\n- Project type: {}", p.project_types.join(", ")));
+    }
+    line
+}
+
+/// Renders the `- Aliases: ...` SYSTEM line, or nothing if there are none.
+fn format_aliases(aliases: &[(String, String)]) -> String {
+    if aliases.is_empty() {
+        return String::new();
+    }
+    let rendered = aliases
+        .iter()
+        .map(|(name, value)| format!("{}='{}'", name, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("\n- Aliases: {}", rendered)
+}
+
+/// Renders the `- Functions: ...` SYSTEM line, or nothing if there are none.
+fn format_functions(functions: &[String]) -> String {
+    if functions.is_empty() {
+        return String::new();
+    }
+    format!("\n- Functions: {}", functions.join(", "))
+}
+
 /// Build the system prompt for explaining a command
 #[allow(dead_code)]
 pub fn cmd_explain_prompt() -> String {
@@ -297,19 +453,170 @@ fn detect_tools() -> Vec<String> {
         "convert",
     ];
 
-    tools
-        .iter()
-        .filter(|tool| which(tool))
-        .map(|s| s.to_string())
-        .collect()
+    // Each probe is an independent PATH lookup, so fan them out across
+    // threads rather than scanning PATH for each tool one after another at startup.
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = tools
+            .iter()
+            .map(|tool| scope.spawn(|| (*tool, which_path(tool))))
+            .collect();
+
+        handles
+            .into_iter()
+            .filter_map(|h| h.join().ok())
+            .filter(|(_, path)| path.is_some())
+            .map(|(tool, _)| tool.to_string())
+            .collect()
+    })
 }
 
+/// Windows executable suffixes tried, in order, when `PATHEXT` isn't set.
+const DEFAULT_PATHEXT: &[&str] = &[".exe", ".cmd", ".bat", ".ps1"];
+
+/// Cross-platform `which`: scans `$PATH` for `tool` without depending on the
+/// `which` binary, which doesn't exist on stock Windows. Honors `PATHEXT`
+/// there so `tool` resolves to `tool.exe`/`tool.cmd`/etc.
 pub fn which(tool: &str) -> bool {
-    Command::new("which")
-        .arg(tool)
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
+    which_path(tool).is_some()
+}
+
+/// Like [`which`], but returns the resolved absolute path so callers (e.g.
+/// tool-help discovery) can invoke the exact binary found instead of relying
+/// on a second, possibly-inconsistent PATH lookup.
+pub fn which_path(tool: &str) -> Option<PathBuf> {
+    let path_var = env::var_os("PATH")?;
+
+    for dir in env::split_paths(&path_var) {
+        let candidate = dir.join(tool);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+
+        if cfg!(target_os = "windows") {
+            let pathext = env::var("PATHEXT").unwrap_or_default();
+            let exts: Vec<&str> = if pathext.is_empty() {
+                DEFAULT_PATHEXT.to_vec()
+            } else {
+                pathext.split(';').collect()
+            };
+            for ext in exts {
+                let with_ext = dir.join(format!("{}{}", tool, ext));
+                if with_ext.is_file() {
+                    return Some(with_ext);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Caps how many aliases/functions get sent to the model, so a profile with
+/// hundreds of them doesn't blow up the prompt size.
+const MAX_ALIASES: usize = 60;
+const MAX_FUNCTIONS: usize = 60;
+
+/// Marker printed between the alias and function listings in the sourced
+/// shell's output, so both can be pulled out of a single subprocess call.
+const ALIASES_FUNCTIONS_SEP: &str = "---NIKO-FUNCTIONS---";
+
+/// Non-interactively sources the user's shell (so aliases/functions defined
+/// in their rc files are picked up) and captures a deduplicated, size-capped
+/// list of both, for "prefer the user's own alias" prompting.
+fn detect_aliases_and_functions(shell: &str) -> (Vec<(String, String)>, Vec<String>) {
+    if cfg!(target_os = "windows") && shell == "powershell" {
+        return detect_aliases_and_functions_powershell();
+    }
+
+    match shell {
+        "zsh" => detect_aliases_and_functions_posix("zsh", "print -l ${(ok)functions}"),
+        "bash" => detect_aliases_and_functions_posix("bash", "declare -F"),
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
+/// Runs `alias` and a shell-specific function-listing command in one
+/// interactive, non-login invocation (`-i -c`) so rc-file definitions are
+/// loaded, then parses both out of the combined output.
+fn detect_aliases_and_functions_posix(shell_bin: &str, functions_cmd: &str) -> (Vec<(String, String)>, Vec<String>) {
+    let script = format!("alias; echo '{}'; {}", ALIASES_FUNCTIONS_SEP, functions_cmd);
+    let output = Command::new(shell_bin)
+        .args(["-i", "-c", &script])
+        .env("TERM", "dumb")
+        .output();
+
+    let Ok(output) = output else { return (Vec::new(), Vec::new()) };
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    match text.split_once(ALIASES_FUNCTIONS_SEP) {
+        Some((alias_part, functions_part)) => (parse_aliases(alias_part), parse_function_names(functions_part)),
+        None => (parse_aliases(&text), Vec::new()),
+    }
+}
+
+fn detect_aliases_and_functions_powershell() -> (Vec<(String, String)>, Vec<String>) {
+    let script = format!(
+        "Get-Alias | ForEach-Object {{ \"$($_.Name)=$($_.Definition)\" }}; \
+         Write-Output '{sep}'; \
+         Get-Command -CommandType Function | ForEach-Object {{ $_.Name }}",
+        sep = ALIASES_FUNCTIONS_SEP
+    );
+    let output = Command::new("pwsh").args(["-Command", &script]).output();
+
+    let Ok(output) = output else { return (Vec::new(), Vec::new()) };
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    match text.split_once(ALIASES_FUNCTIONS_SEP) {
+        Some((alias_part, functions_part)) => (parse_aliases(alias_part), parse_function_names(functions_part)),
+        None => (parse_aliases(&text), Vec::new()),
+    }
+}
+
+/// Parses `alias name=value` / `name=value` lines, deduplicated and capped
+/// at `MAX_ALIASES`.
+fn parse_aliases(text: &str) -> Vec<(String, String)> {
+    let mut seen = std::collections::HashSet::new();
+    let mut out = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim().strip_prefix("alias ").unwrap_or(line.trim());
+        let Some((name, value)) = line.split_once('=') else { continue };
+        let name = name.trim();
+        if name.is_empty() || !seen.insert(name.to_string()) {
+            continue;
+        }
+        let value = value.trim().trim_matches('\'').trim_matches('"');
+        out.push((name.to_string(), value.to_string()));
+        if out.len() >= MAX_ALIASES {
+            break;
+        }
+    }
+
+    out
+}
+
+/// Parses bash's `declare -f name` lines or zsh's bare-name `print -l`
+/// output into just the function names, deduplicated and capped at
+/// `MAX_FUNCTIONS`.
+fn parse_function_names(text: &str) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut out = Vec::new();
+
+    for line in text.lines() {
+        let name = line.trim().strip_prefix("declare -f ").unwrap_or(line.trim());
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            continue;
+        }
+        if !seen.insert(name.to_string()) {
+            continue;
+        }
+        out.push(name.to_string());
+        if out.len() >= MAX_FUNCTIONS {
+            break;
+        }
+    }
+
+    out
 }
 
 // ---------------------------------------------------------------------------
@@ -368,12 +675,16 @@ const SUBCOMMAND_TOOLS: &[&str] = &[
 /// Scans the query for words matching executables on PATH, runs their --help,
 /// and returns formatted help text for the LLM. Also handles two-word
 /// subcommands like "docker compose", "kubectl get".
-pub fn discover_tool_help(query: &str, verbose: bool) -> String {
+pub fn discover_tool_help(query: &str, verbose: bool, refresh: bool) -> String {
     let words: Vec<&str> = query.split_whitespace().collect();
     if words.is_empty() {
         return String::new();
     }
 
+    if refresh {
+        crate::cache::prune_tool_help();
+    }
+
     let mut help_sections = Vec::new();
     let mut seen_tools = std::collections::HashSet::new();
 
@@ -387,20 +698,22 @@ pub fn discover_tool_help(query: &str, verbose: bool) -> String {
 
         if SUBCOMMAND_TOOLS.contains(&base.as_str()) {
             let key = format!("{} {}", base, sub);
-            if !seen_tools.contains(&key) && which(&base) {
-                if let Some(help_text) = get_subcommand_help(&base, &sub) {
-                    if verbose {
-                        eprintln!(
-                            "  [help] captured `{} {} --help` ({} chars)",
-                            base,
-                            sub,
-                            help_text.len()
-                        );
+            if !seen_tools.contains(&key) {
+                if let Some(path) = which_path(&base) {
+                    if let Some(help_text) = get_subcommand_help(&base, &path, &sub, refresh) {
+                        if verbose {
+                            eprintln!(
+                                "  [help] captured `{} {} --help` ({} chars)",
+                                base,
+                                sub,
+                                help_text.len()
+                            );
+                        }
+                        help_sections
+                            .push(format!("TOOL REFERENCE: `{} {}`\n{}", base, sub, help_text));
+                        seen_tools.insert(key);
+                        seen_tools.insert(base.clone());
                     }
-                    help_sections
-                        .push(format!("TOOL REFERENCE: `{} {}`\n{}", base, sub, help_text));
-                    seen_tools.insert(key);
-                    seen_tools.insert(base.clone());
                 }
             }
         }
@@ -419,8 +732,8 @@ pub fn discover_tool_help(query: &str, verbose: bool) -> String {
             continue;
         }
 
-        if which(&tool) {
-            if let Some(help_text) = get_tool_help(&tool) {
+        if let Some(path) = which_path(&tool) {
+            if let Some(help_text) = get_tool_help(&tool, &path, refresh) {
                 if verbose {
                     eprintln!(
                         "  [help] captured `{} --help` ({} chars)",
@@ -452,33 +765,66 @@ fn normalize_tool_word(word: &str) -> String {
         .to_string()
 }
 
-/// Try --help, -h, then `tool help` to get help text
-fn get_tool_help(tool: &str) -> Option<String> {
-    if let Some(text) = run_help_command(tool, &["--help"]) {
-        return Some(truncate_help(&text));
-    }
-    if let Some(text) = run_help_command(tool, &["-h"]) {
-        return Some(truncate_help(&text));
-    }
-    if let Some(text) = run_help_command(tool, &["help"]) {
-        return Some(truncate_help(&text));
+/// Try the on-disk cache (keyed by tool version), then a parsed
+/// shell-completion file, then --help, -h, then `tool help`. `tool_path` is
+/// the exact binary `which_path` resolved, so this invokes it directly
+/// instead of re-resolving `tool` through PATH a second time.
+fn get_tool_help(tool: &str, tool_path: &std::path::Path, refresh: bool) -> Option<String> {
+    let version = tool_version(tool_path);
+
+    if !refresh {
+        if let Some(cached) = crate::cache::lookup_tool_help(tool, None, &version) {
+            return Some(cached);
+        }
     }
-    None
+
+    let text = crate::completions::lookup(tool, None)
+        .or_else(|| run_help_command(tool_path, &["--help"]).map(|t| truncate_help(&t)))
+        .or_else(|| run_help_command(tool_path, &["-h"]).map(|t| truncate_help(&t)))
+        .or_else(|| run_help_command(tool_path, &["help"]).map(|t| truncate_help(&t)))?;
+
+    crate::cache::store_tool_help(tool, None, &version, &text);
+    Some(text)
 }
 
-/// Try `tool subcommand --help` or `tool help subcommand`
-fn get_subcommand_help(tool: &str, subcommand: &str) -> Option<String> {
-    if let Some(text) = run_help_command(tool, &[subcommand, "--help"]) {
-        return Some(truncate_help(&text));
-    }
-    if let Some(text) = run_help_command(tool, &["help", subcommand]) {
-        return Some(truncate_help(&text));
+/// Try the on-disk cache (keyed by tool version), then a parsed
+/// shell-completion file, then `tool subcommand --help` or `tool help subcommand`.
+fn get_subcommand_help(tool: &str, tool_path: &std::path::Path, subcommand: &str, refresh: bool) -> Option<String> {
+    let version = tool_version(tool_path);
+
+    if !refresh {
+        if let Some(cached) = crate::cache::lookup_tool_help(tool, Some(subcommand), &version) {
+            return Some(cached);
+        }
     }
-    None
+
+    let text = crate::completions::lookup(tool, Some(subcommand))
+        .or_else(|| run_help_command(tool_path, &[subcommand, "--help"]).map(|t| truncate_help(&t)))
+        .or_else(|| run_help_command(tool_path, &["help", subcommand]).map(|t| truncate_help(&t)))?;
+
+    crate::cache::store_tool_help(tool, Some(subcommand), &version, &text);
+    Some(text)
+}
+
+/// Gets the tool's version string (first line of `tool --version`) to key
+/// the tool-help cache, so an upgrade naturally invalidates cached flags
+/// instead of serving stale ones. Returns an empty string if it can't be
+/// determined, which still caches correctly as long as that stays
+/// consistent.
+fn tool_version(tool_path: &std::path::Path) -> String {
+    let Ok(output) = Command::new(tool_path).arg("--version").env("TERM", "dumb").output() else {
+        return String::new();
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let text = if stdout.trim().is_empty() { stderr } else { stdout };
+
+    text.lines().next().unwrap_or("").trim().to_string()
 }
 
 /// Run a command with args and capture output (picks stdout or stderr, whichever is longer)
-fn run_help_command(cmd: &str, args: &[&str]) -> Option<String> {
+fn run_help_command(cmd: &std::path::Path, args: &[&str]) -> Option<String> {
     let output = Command::new(cmd)
         .args(args)
         // Kill after 3 seconds — some tools hang without a TTY
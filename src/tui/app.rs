@@ -1,22 +1,46 @@
 use std::time::Instant;
 
 use ratatui::style::Style;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
 use tui_textarea::TextArea;
 
-use crate::tui::workspace::WorkspaceIndex;
+use crate::tui::git::GitStatus;
+use crate::tui::jobs::{JobManager, JobState};
+use crate::tui::plan::Plan;
+use crate::tui::theme::Theme;
+use crate::tui::workspace::{RagMode, WorkspaceIndex};
 
 #[derive(Debug, Clone)]
 pub enum TuiMessage {
-    Token(String),
+    /// `generation` is `App::generation` at the moment this generation was
+    /// spawned — compared back against the current value so a stale
+    /// cancelled (or superseded) generation's tokens/completion can't land
+    /// in a newer generation's buffer or reappear in history.
+    Token {
+        generation: u64,
+        text: String,
+    },
     StreamFinished {
+        generation: u64,
         latency_ms: u128,
         output_chars: usize,
     },
-    Error(String),
+    Error {
+        generation: u64,
+        message: String,
+    },
     WarmupStatus(String),
     WorkspaceIndexReady {
         index: WorkspaceIndex,
         source: String,
+        /// Human-readable trigger for this rebuild (which paths changed,
+        /// "periodic fallback", ...); shown by `/stats` as the last reindex
+        /// reason. `None` for a synchronous `/index`-triggered rebuild.
+        reason: Option<String>,
+    },
+    GitStatusReady {
+        status: Option<GitStatus>,
     },
     CommandStarted {
         pid: u32,
@@ -27,6 +51,34 @@ pub enum TuiMessage {
         cmd: String,
         output: String,
     },
+    /// A job's lifecycle state changed; reported by its background thread
+    /// rather than mutated directly so the registry stays main-thread-only.
+    JobUpdate {
+        id: u64,
+        state: JobState,
+        pid: Option<u32>,
+        error: Option<String>,
+    },
+    /// `/next`'s validation step finished running in the background; carries
+    /// the same `CheckResult`s `validation::run_checks` always produced,
+    /// just delivered off the main thread instead of blocking it.
+    ValidationReady {
+        results: Vec<crate::tui::validation::CheckResult>,
+    },
+    /// A `/<name>` dispatch to a `niko-<name>` extension finished running in
+    /// the background; carries the same `(exit code, combined output)` pair
+    /// `extensions::run` always produced.
+    ExtensionReady {
+        name: String,
+        result: Result<(Option<i32>, String), String>,
+    },
+    /// A `/fetch <url>` finished downloading/stripping in the background;
+    /// carries the same `Result<String, String>` `fetch_url_as_text` always
+    /// produced.
+    FetchReady {
+        url: String,
+        result: Result<String, String>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,6 +91,9 @@ pub enum Route {
 #[derive(Debug, Clone)]
 pub struct HistoryEntry {
     pub is_user: bool,
+    /// True for raw `/run` command stdout/stderr, which is rendered through
+    /// the ANSI-aware path instead of the markdown-lite renderer.
+    pub is_command_output: bool,
     pub text: String,
 }
 
@@ -48,6 +103,14 @@ pub enum Focus {
     Output,
 }
 
+/// An AI-proposed change to a workspace file, staged for human review via
+/// the diff overlay before `/approve` writes it to disk.
+#[derive(Debug, Clone)]
+pub struct FileEdit {
+    pub path: String,
+    pub new_contents: String,
+}
+
 pub struct App<'a> {
     pub route: Route,
     pub input_buffer: TextArea<'a>,
@@ -65,15 +128,96 @@ pub struct App<'a> {
     pub show_help: bool,
     pub status_line: String,
     pub rag_enabled: bool,
+    /// Keyword vs. semantic vs. hybrid retrieval, toggled with `/rag keyword|semantic|hybrid`
+    pub rag_mode: RagMode,
     pub workspace_index: Option<WorkspaceIndex>,
+    /// Trigger for the most recent background reindex, shown by `/stats`
+    pub last_reindex_reason: Option<String>,
+    /// True while `/watch on` has a filesystem watcher debouncing edits into
+    /// incremental re-indexes; owning `fs_watcher` is what actually keeps it
+    /// alive, this just mirrors that for `/watch`'s own status reporting
+    pub watch_enabled: bool,
+    /// The OS-level watch handle for `/watch on`. Dropping it (via `/watch
+    /// off`, or app exit) unregisters the watch and lets the debounce thread
+    /// exit on its next `recv()` error.
+    pub fs_watcher: Option<notify::RecommendedWatcher>,
+    /// Background watch handle for the config file, kept alive for the whole
+    /// session so `Event::ConfigReload` keeps firing when it changes on disk.
+    pub cfg_watcher: Option<notify::RecommendedWatcher>,
+    /// Current branch/ahead-behind/dirty-file counts, refreshed on a timer;
+    /// `None` outside a git worktree or before the first poll completes
+    pub git_status: Option<GitStatus>,
     pub pending_command: Option<String>,
-    pub command_running: bool,
-    pub command_pid: Option<u32>,
+    /// Set while a `/diagnostics` build is running so the `CommandOutput`
+    /// handler parses its buffer into structured diagnostics instead of
+    /// displaying it raw; holds the detected toolchain ("cargo", "tsc", "npm")
+    pub pending_diagnostics_kind: Option<String>,
+    /// `--force` flag for the `/next` validation step currently running in
+    /// the background; consumed when `TuiMessage::ValidationReady` arrives
+    /// to decide whether a failed check still blocks the plan.
+    pub pending_validation_force: Option<bool>,
+    /// Registry of every tracked background task (approved shell commands,
+    /// the workspace indexer, ...). Replaces the old single
+    /// `command_pid`/`command_running`/`command_resize_tx` fields so the
+    /// TUI can run and report on more than one job at a time.
+    pub job_manager: JobManager,
     pub planner_steps: Vec<String>,
     pub planner_cursor: usize,
+    /// Backing store for `planner_steps`/`planner_cursor`, carrying
+    /// per-step status and a hash of the originating task so `/plan
+    /// --resume` can tell a genuine continuation from stale state. `None`
+    /// until the first `/plan` or a successful resume.
+    pub current_plan: Option<Plan>,
     pub total_responses: u64,
     pub total_output_chars: u64,
     pub last_latency_ms: Option<u128>,
+    /// Loaded once and reused for syntax highlighting in the conversation pane
+    pub syntax_set: SyntaxSet,
+    pub theme_set: ThemeSet,
+    /// User-configurable UI color palette, loaded from `~/.niko/theme.toml`
+    pub theme: Theme,
+    /// True while the fuzzy file-picker overlay (@-attachments, /open) is open
+    pub file_picker_open: bool,
+    pub file_picker_query: String,
+    pub file_picker_cursor: usize,
+    /// File edits the assistant has proposed, awaiting `/approve` or `/deny`
+    pub pending_edits: Vec<FileEdit>,
+    /// True while the unified-diff review overlay for `pending_edits` is open
+    pub diff_overlay_open: bool,
+    pub diff_overlay_scroll: u16,
+    /// True while incremental in-conversation search (Ctrl+F) is active
+    pub search_active: bool,
+    pub search_query: String,
+    /// (line index into the rendered history, match start, match end), in
+    /// the order they appear on screen; recomputed every frame while active
+    pub search_matches: Vec<(usize, usize, usize)>,
+    pub search_current: usize,
+    /// `result_scroll` to restore when search is cancelled with Esc
+    pub search_prev_scroll: u16,
+    /// Set by the event loop on Enter/Shift+Enter/typing; consumed by
+    /// `draw_output_history` to advance `search_current` (1 = next, -1 =
+    /// previous, 0 = jump to the first match after the query changed)
+    pub search_pending_jump: Option<i8>,
+    /// Cached (cols, rows), refreshed on `Event::Resize` so a resize can be
+    /// told apart from a no-op tick
+    pub term_size: (u16, u16),
+    /// Set whenever visible state changes (key input, streaming tokens,
+    /// spinner ticks, resize); the event loop only redraws while this is
+    /// true, and clears it right after
+    pub dirty: bool,
+    /// Cancellation flag for the in-flight `generate_stream_cancellable`
+    /// call, if any. `None` when no generation is running; set alongside
+    /// `is_loading` when a request is spawned, and aborted (not cleared) by
+    /// the Esc handler in `Route::Processing` so the background thread can
+    /// stop reading mid-stream instead of running to completion unseen.
+    pub active_generation: Option<crate::llm::AbortSignal>,
+    /// Bumped every time the current generation is invalidated — a new one
+    /// is spawned, or the Esc handler cancels one early. `Token`/
+    /// `StreamFinished`/`Error` messages carry the generation they were
+    /// produced for; the event loop drops any whose `generation` doesn't
+    /// match this, so a cancelled or superseded generation's output can't
+    /// land in a newer generation's buffer or reappear in history.
+    pub generation: u64,
 }
 
 impl<'a> Default for App<'a> {
@@ -99,19 +243,90 @@ impl<'a> Default for App<'a> {
             show_help: false,
             status_line: "Ready".to_string(),
             rag_enabled: true,
+            rag_mode: RagMode::Keyword,
             workspace_index: None,
+            last_reindex_reason: None,
+            watch_enabled: false,
+            cfg_watcher: None,
+            fs_watcher: None,
+            git_status: None,
             pending_command: None,
-            command_running: false,
-            command_pid: None,
+            pending_diagnostics_kind: None,
+            pending_validation_force: None,
+            job_manager: JobManager::default(),
             planner_steps: Vec::new(),
             planner_cursor: 0,
+            current_plan: None,
             total_responses: 0,
             total_output_chars: 0,
             last_latency_ms: None,
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            theme_set: ThemeSet::load_defaults(),
+            theme: Theme::load(),
+            file_picker_open: false,
+            file_picker_query: String::new(),
+            file_picker_cursor: 0,
+            pending_edits: Vec::new(),
+            diff_overlay_open: false,
+            diff_overlay_scroll: 0,
+            search_active: false,
+            search_query: String::new(),
+            search_matches: Vec::new(),
+            search_current: 0,
+            search_prev_scroll: 0,
+            search_pending_jump: None,
+            term_size: crossterm::terminal::size().unwrap_or((80, 24)),
+            dirty: true,
+            active_generation: None,
+            generation: 0,
         }
     }
 }
 
+/// Model name → (context window tokens, tiktoken encoding name).
+/// Matched by substring against the active model, first match wins.
+/// New providers/models should add an entry here.
+const MODEL_TOKEN_TABLE: &[(&str, usize, &str)] = &[
+    ("gpt-4o", 128_000, "o200k_base"),
+    ("gpt-4-turbo", 128_000, "cl100k_base"),
+    ("gpt-4", 8_192, "cl100k_base"),
+    ("gpt-3.5", 16_385, "cl100k_base"),
+    ("o1", 200_000, "o200k_base"),
+    ("claude-3", 200_000, "cl100k_base"),
+    ("claude-sonnet-4", 200_000, "cl100k_base"),
+    ("deepseek", 64_000, "cl100k_base"),
+    ("mistral", 32_000, "cl100k_base"),
+    ("llama3", 8_192, "cl100k_base"),
+    ("llama", 4_096, "cl100k_base"),
+    ("qwen", 32_000, "cl100k_base"),
+    ("mixtral", 32_000, "cl100k_base"),
+];
+
+/// Fallback context window when the model isn't recognized
+const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+/// Fallback tiktoken encoding when the model isn't recognized
+const DEFAULT_ENCODING: &str = "cl100k_base";
+
+/// Look up the context window (in tokens) for a model name
+pub fn model_context_window(model: &str) -> usize {
+    let model_lower = model.to_lowercase();
+    MODEL_TOKEN_TABLE
+        .iter()
+        .find(|(needle, _, _)| model_lower.contains(needle))
+        .map(|(_, ctx, _)| *ctx)
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+/// Look up the tiktoken encoding name for a model
+pub fn model_encoding(model: &str) -> &'static str {
+    let model_lower = model.to_lowercase();
+    MODEL_TOKEN_TABLE
+        .iter()
+        .find(|(needle, _, _)| model_lower.contains(needle))
+        .map(|(_, _, enc)| *enc)
+        .unwrap_or(DEFAULT_ENCODING)
+}
+
 impl<'a> App<'a> {
     pub fn new() -> Self {
         Self::default()
@@ -127,6 +342,15 @@ impl<'a> App<'a> {
         self.streaming_scroll = 0;
         self.focus = Focus::Input;
         self.pasted_code = None;
+        self.file_picker_open = false;
+        self.file_picker_query.clear();
+        self.file_picker_cursor = 0;
+        self.diff_overlay_open = false;
+        self.diff_overlay_scroll = 0;
+        self.search_active = false;
+        self.search_query.clear();
+        self.search_matches.clear();
+        self.search_pending_jump = None;
 
         match self.route {
             Route::Chat => self
@@ -136,9 +360,31 @@ impl<'a> App<'a> {
         }
     }
 
-    pub fn on_tick(&mut self) {
+    /// Advance tick-driven animation state. Returns `true` if anything
+    /// visible actually changed, so the event loop can skip a redraw on
+    /// idle ticks instead of repainting every 100ms for nothing.
+    pub fn on_tick(&mut self) -> bool {
         if self.is_loading {
             self.spinner_state = self.spinner_state.wrapping_add(1);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Update the cached terminal size; returns `true` if it actually
+    /// changed. A shrinking width can wrap far more lines than a scroll
+    /// offset tuned for the old width expects, so clamp both history scroll
+    /// offsets back within the buffers' line counts.
+    pub fn set_term_size(&mut self, cols: u16, rows: u16) -> bool {
+        if self.term_size == (cols, rows) {
+            return false;
         }
+        self.term_size = (cols, rows);
+        let result_lines = self.result_buffer.lines().count() as u16;
+        let streaming_lines = self.streaming_buffer.lines().count() as u16;
+        self.result_scroll = self.result_scroll.min(result_lines);
+        self.streaming_scroll = self.streaming_scroll.min(streaming_lines);
+        true
     }
 }
@@ -7,6 +7,7 @@ use std::{
 use anyhow::Result;
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent};
 
+use crate::config::Config;
 use crate::tui::app::TuiMessage;
 
 #[derive(Debug)]
@@ -14,8 +15,13 @@ pub enum Event {
     Tick,
     Key(KeyEvent),
     Paste(String),
-    Resize,
+    Resize(u16, u16),
     AppMessage(TuiMessage),
+    /// Emitted by the config-file watcher spawned in `tui::mod` whenever
+    /// `~/.niko/config.yaml` changes on disk. `Ok` carries the freshly
+    /// parsed config; `Err` carries a message when the new contents don't
+    /// parse, so the previous in-memory settings are kept untouched.
+    ConfigReload(Result<Config, String>),
 }
 
 pub struct EventHandler {
@@ -51,9 +57,9 @@ impl EventHandler {
                                 .send(Event::Paste(s))
                                 .expect("failed to send paste event");
                         }
-                        CrosstermEvent::Resize(_, _) => {
+                        CrosstermEvent::Resize(cols, rows) => {
                             handler_sender
-                                .send(Event::Resize)
+                                .send(Event::Resize(cols, rows))
                                 .expect("failed to send resize event");
                         }
                         _ => {}
@@ -0,0 +1,114 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A `niko-<name>` executable found on `PATH`, dispatchable as `/<name>`
+/// for anything the core command set doesn't already handle — the same
+/// model git/cargo use for `git-<name>`/`cargo-<name>` subcommands.
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
+/// Scans every directory in `PATH` for `niko-<name>` executables. Later
+/// `PATH` entries don't override earlier ones, matching how a real shell
+/// resolves the first match it finds.
+pub fn discover() -> Vec<Extension> {
+    let Some(path_var) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    let mut found = Vec::new();
+    for dir in env::split_paths(&path_var) {
+        let Ok(read_dir) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            let Some(name) = file_name.strip_prefix("niko-") else {
+                continue;
+            };
+            if name.is_empty() || !seen.insert(name.to_string()) {
+                continue;
+            }
+            let path = entry.path();
+            if is_executable(&path) {
+                found.push(Extension {
+                    name: name.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// Finds the extension registered for `/<name>`, re-scanning `PATH` each
+/// time so a newly-installed extension is picked up without restarting.
+pub fn find(name: &str) -> Option<Extension> {
+    discover().into_iter().find(|e| e.name == name)
+}
+
+/// Extracts `@path` tokens from a command line the same way chat-input
+/// attachments are detected, so extensions get the same attached-file list
+/// the model would have.
+fn extract_attachments(args: &str) -> Vec<String> {
+    args.split_whitespace()
+        .filter(|t| t.starts_with('@'))
+        .map(|t| {
+            t.trim_start_matches('@')
+                .trim_matches(|c: char| c == '"' || c == '\'' || c == ',' || c == ';' || c == ')')
+                .to_string()
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Runs `ext` with `args` (the raw text after the command name), forwarding
+/// context through environment variables so third parties can extend niko
+/// without touching the core: `NIKO_CWD`, `NIKO_ATTACHMENTS` (colon-
+/// separated `@path` mentions found in `args`), and `NIKO_TASK` (the
+/// caller's notion of the active task, if any). Returns the combined
+/// stdout+stderr and the exit code; a `None` exit code means the process
+/// was killed by a signal rather than exiting.
+pub fn run(ext: &Extension, args: &str, cwd: &std::path::Path, task: Option<&str>) -> Result<(Option<i32>, String), String> {
+    let attachments = extract_attachments(args).join(":");
+
+    let output = Command::new(&ext.path)
+        .args(args.split_whitespace())
+        .current_dir(cwd)
+        .env("NIKO_CWD", cwd.display().to_string())
+        .env("NIKO_ATTACHMENTS", attachments)
+        .env("NIKO_TASK", task.unwrap_or_default())
+        .output()
+        .map_err(|e| format!("failed to run niko-{}: {}", ext.name, e))?;
+
+    let mut combined = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.is_empty() {
+        if !combined.is_empty() {
+            combined.push('\n');
+        }
+        combined.push_str(&stderr);
+    }
+
+    Ok((output.status.code(), combined))
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Snapshot of the workspace's git state, refreshed on a timer by
+/// `spawn_background_git_poller` so the UI never blocks on a git invocation.
+#[derive(Debug, Clone)]
+pub struct GitStatus {
+    pub branch: String,
+    pub ahead: usize,
+    pub behind: usize,
+    pub staged: usize,
+    pub modified: usize,
+    pub untracked: usize,
+}
+
+impl GitStatus {
+    /// Shell out to `git` to collect branch, ahead/behind, and dirty-file
+    /// counts for `root`. Returns `None` outside a git worktree or if `git`
+    /// isn't on `PATH`.
+    pub fn collect(root: &Path) -> Option<Self> {
+        let branch = run_git(root, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let branch = branch.trim().to_string();
+        if branch.is_empty() {
+            return None;
+        }
+
+        let (mut ahead, mut behind) = (0, 0);
+        if let Some(counts) = run_git(
+            root,
+            &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"],
+        ) {
+            let mut parts = counts.split_whitespace();
+            ahead = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+            behind = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+        }
+
+        let (mut staged, mut modified, mut untracked) = (0, 0, 0);
+        if let Some(status) = run_git(root, &["status", "--porcelain"]) {
+            for line in status.lines() {
+                let mut chars = line.chars();
+                let index_status = chars.next().unwrap_or(' ');
+                let worktree_status = chars.next().unwrap_or(' ');
+                if index_status == '?' && worktree_status == '?' {
+                    untracked += 1;
+                } else {
+                    if index_status != ' ' {
+                        staged += 1;
+                    }
+                    if worktree_status != ' ' {
+                        modified += 1;
+                    }
+                }
+            }
+        }
+
+        Some(Self {
+            branch,
+            ahead,
+            behind,
+            staged,
+            modified,
+            untracked,
+        })
+    }
+
+    /// Compact header segment, e.g. `⎇ main ↑2 ↓1 ●3 +1`
+    pub fn header_segment(&self) -> String {
+        let mut segment = format!("⎇ {}", self.branch);
+        if self.ahead > 0 {
+            segment.push_str(&format!(" ↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            segment.push_str(&format!(" ↓{}", self.behind));
+        }
+        let dirty = self.staged + self.modified;
+        if dirty > 0 {
+            segment.push_str(&format!(" ●{}", dirty));
+        }
+        if self.untracked > 0 {
+            segment.push_str(&format!(" +{}", self.untracked));
+        }
+        segment
+    }
+}
+
+fn run_git(root: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
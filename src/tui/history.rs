@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// One completed `/run`/`/approve` invocation, persisted to `history.jsonl`
+/// under `config_dir()` so commands survive restarts and can be replayed or
+/// audited later with `/history` and `/rerun` instead of scrolling back
+/// through the chat transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub timestamp_unix: u64,
+    pub command: String,
+    pub cwd: String,
+    /// `None` if the process was killed by a signal rather than exiting.
+    pub exit_code: Option<i32>,
+    pub duration_ms: u128,
+}
+
+/// Entries beyond this are dropped from the front on the next append, so a
+/// long-lived project's history file can't grow unbounded.
+const MAX_ENTRIES: usize = 500;
+
+pub fn history_path() -> PathBuf {
+    config::config_dir().join("history.jsonl")
+}
+
+fn next_id(entries: &[HistoryEntry]) -> u64 {
+    entries.iter().map(|e| e.id).max().map_or(0, |m| m + 1)
+}
+
+/// Loads every entry currently on disk, oldest first. A missing file reads
+/// as empty rather than an error, same as `config::load`'s first-run path.
+pub fn load() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = fs::read_to_string(&path).map_err(|e| format!("failed to read history: {}", e))?;
+    Ok(raw
+        .lines()
+        .filter_map(|line| serde_json::from_str::<HistoryEntry>(line).ok())
+        .collect())
+}
+
+/// Appends a completed command to the history file. Deduplicates an exact
+/// repeat of the immediately preceding entry (re-running the same command
+/// back to back shouldn't spam `/history`) and rotates the oldest entries
+/// out once the file passes `MAX_ENTRIES`.
+pub fn append(
+    command: String,
+    cwd: String,
+    exit_code: Option<i32>,
+    duration_ms: u128,
+    timestamp_unix: u64,
+) -> Result<(), String> {
+    let mut entries = load()?;
+
+    if let Some(last) = entries.last() {
+        if last.command == command && last.cwd == cwd {
+            return Ok(());
+        }
+    }
+
+    entries.push(HistoryEntry {
+        id: next_id(&entries),
+        timestamp_unix,
+        command,
+        cwd,
+        exit_code,
+        duration_ms,
+    });
+
+    if entries.len() > MAX_ENTRIES {
+        let drop = entries.len() - MAX_ENTRIES;
+        entries.drain(0..drop);
+    }
+
+    let dir = config::config_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("failed to create config directory: {}", e))?;
+
+    let mut raw = String::new();
+    for entry in &entries {
+        raw.push_str(
+            &serde_json::to_string(entry)
+                .map_err(|e| format!("failed to encode history entry: {}", e))?,
+        );
+        raw.push('\n');
+    }
+    fs::write(history_path(), raw).map_err(|e| format!("failed to write history: {}", e))
+}
+
+/// The most recent `n` entries, newest first — what `/history [n]` displays.
+pub fn recent(n: usize) -> Result<Vec<HistoryEntry>, String> {
+    let mut entries = load()?;
+    entries.reverse();
+    entries.truncate(n);
+    Ok(entries)
+}
+
+/// Looks up a single entry by id, for `/rerun <id>`.
+pub fn find(id: u64) -> Result<Option<HistoryEntry>, String> {
+    Ok(load()?.into_iter().find(|e| e.id == id))
+}
+
+/// Reverse (most-recent-first) substring search over past commands, for
+/// `/history search <query>`.
+pub fn search(query: &str) -> Result<Vec<HistoryEntry>, String> {
+    let mut entries = load()?;
+    entries.reverse();
+    Ok(entries
+        .into_iter()
+        .filter(|e| e.command.contains(query))
+        .collect())
+}
@@ -0,0 +1,199 @@
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lifecycle of a tracked background task. `Idle` doubles as the paused
+/// state for jobs that support pause/resume — there's no separate
+/// `Paused` variant; a `Pause` control moves a `Running` job to `Idle`,
+/// and `Resume` moves it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Idle,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Idle => "idle",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+/// What a job represents, so `/stop` and the sidebar can pick out "the"
+/// foreground command without string-matching on labels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Command,
+    Indexer,
+    Validation,
+}
+
+impl JobKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobKind::Command => "command",
+            JobKind::Indexer => "indexer",
+            JobKind::Validation => "validation",
+        }
+    }
+}
+
+/// Sent down a job's control channel to ask its background thread to
+/// change behavior. The thread decides how (or whether) to honor it — a
+/// Windows-hosted command has no pause primitive and just ignores `Pause`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobControl {
+    Pause,
+    Resume,
+    Cancel,
+}
+
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    pub label: String,
+    pub state: JobState,
+    pub started_unix: u64,
+    pub last_error: Option<String>,
+    pub pid: Option<u32>,
+    pub control_tx: mpsc::Sender<JobControl>,
+    /// Only set for PTY-backed commands, which need terminal resizes
+    /// forwarded separately from job control.
+    pub resize_tx: Option<mpsc::Sender<(u16, u16)>>,
+    /// Only set for PTY-backed commands: forwards a line of keyboard input
+    /// to the command's stdin, for interactive tools (confirmation
+    /// prompts, REPLs) that need more than a fire-and-forget shell line.
+    pub input_tx: Option<mpsc::Sender<String>>,
+}
+
+pub fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Registry of every long-running task the TUI has spawned — approved
+/// shell commands, the workspace indexer, and anything future work (e.g.
+/// background embedding jobs) registers itself under. Registration always
+/// happens on the main thread before the background thread is spawned;
+/// the thread only ever reports state transitions back via
+/// `TuiMessage::JobUpdate` — it never touches this struct directly, same
+/// as every other piece of TUI background state.
+#[derive(Default)]
+pub struct JobManager {
+    pub jobs: Vec<Job>,
+    next_id: u64,
+}
+
+impl JobManager {
+    pub fn register(
+        &mut self,
+        kind: JobKind,
+        label: String,
+        control_tx: mpsc::Sender<JobControl>,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push(Job {
+            id,
+            kind,
+            label,
+            state: JobState::Queued,
+            started_unix: now_unix(),
+            last_error: None,
+            pid: None,
+            control_tx,
+            resize_tx: None,
+            input_tx: None,
+        });
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Job> {
+        self.jobs.iter().find(|j| j.id == id)
+    }
+
+    fn get_mut(&mut self, id: u64) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|j| j.id == id)
+    }
+
+    pub fn set_resize_tx(&mut self, id: u64, tx: mpsc::Sender<(u16, u16)>) {
+        if let Some(job) = self.get_mut(id) {
+            job.resize_tx = Some(tx);
+        }
+    }
+
+    pub fn set_input_tx(&mut self, id: u64, tx: mpsc::Sender<String>) {
+        if let Some(job) = self.get_mut(id) {
+            job.input_tx = Some(tx);
+        }
+    }
+
+    pub fn apply_update(
+        &mut self,
+        id: u64,
+        state: JobState,
+        pid: Option<u32>,
+        error: Option<String>,
+    ) {
+        if let Some(job) = self.get_mut(id) {
+            job.state = state;
+            if pid.is_some() {
+                job.pid = pid;
+            }
+            if error.is_some() {
+                job.last_error = error;
+            }
+        }
+    }
+
+    /// The most recently registered command job — what `/stop` targets and
+    /// the sidebar shows when no explicit id is given.
+    pub fn last_command(&self) -> Option<&Job> {
+        self.jobs.iter().rev().find(|j| j.kind == JobKind::Command)
+    }
+
+    pub fn list(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    fn send_control(&self, id: u64, control: JobControl) -> Result<(), String> {
+        let job = self.get(id).ok_or_else(|| format!("No job with id {}", id))?;
+        if job.state.is_terminal() {
+            return Err(format!(
+                "Job {} has already finished ({})",
+                id,
+                job.state.label()
+            ));
+        }
+        job.control_tx
+            .send(control)
+            .map_err(|_| format!("Job {} is no longer listening for control messages", id))
+    }
+
+    pub fn cancel(&self, id: u64) -> Result<(), String> {
+        self.send_control(id, JobControl::Cancel)
+    }
+
+    pub fn pause(&self, id: u64) -> Result<(), String> {
+        self.send_control(id, JobControl::Pause)
+    }
+
+    pub fn resume(&self, id: u64) -> Result<(), String> {
+        self.send_control(id, JobControl::Resume)
+    }
+}
@@ -1,18 +1,29 @@
 pub mod app;
 pub mod events;
+pub mod extensions;
+pub mod git;
+pub mod history;
+pub mod jobs;
+pub mod plan;
+pub mod sandbox;
+pub mod theme;
 pub mod ui;
+pub mod validation;
 pub mod workspace;
 
 use std::error::Error;
 use std::fs;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::process::Stdio;
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+use notify::Watcher;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use syntect::parsing::SyntaxSet;
+
 use crossterm::{
     event::{
         DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
@@ -24,8 +35,9 @@ use crossterm::{
 use ratatui::{backend::CrosstermBackend, style::Style, Terminal};
 
 use crate::llm;
-use app::{App, Focus, HistoryEntry, Route, TuiMessage};
+use app::{App, FileEdit, Focus, HistoryEntry, Route, TuiMessage};
 use events::{Event, EventHandler};
+use jobs::{JobControl, JobKind, JobState};
 
 pub fn run() -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
@@ -44,13 +56,43 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     let sender = events.sender.clone();
     let base_system_prompt = crate::prompt::chat_system_prompt(&crate::prompt::gather_context());
 
+    let (indexer_control_tx, indexer_control_rx) = mpsc::channel();
+    let indexer_job_id = app.job_manager.register(
+        JobKind::Indexer,
+        "workspace indexer".to_string(),
+        indexer_control_tx,
+    );
+
     spawn_warmup(sender.clone());
-    spawn_background_indexer(sender.clone());
+    spawn_background_indexer(sender.clone(), indexer_job_id, indexer_control_rx);
+    spawn_background_git_poller(sender.clone());
+    app.cfg_watcher = start_config_watcher(sender.clone());
+
+    if let Ok(Some(saved)) = plan::load() {
+        if saved.first_pending() < saved.steps.len() {
+            app.history.push(HistoryEntry {
+                is_user: false,
+                is_command_output: false,
+                text: format!(
+                    "Found an interrupted plan for: {}\nUse `/plan --resume {}` to continue where it left off, or `/plan <task>` to start fresh.",
+                    saved.task, saved.task
+                ),
+            });
+        }
+    }
 
     loop {
-        terminal.draw(|f| ui::draw(f, &mut app))?;
+        if app.dirty {
+            terminal.draw(|f| ui::draw(f, &mut app))?;
+            app.dirty = false;
+        }
 
-        match events.next()? {
+        let event = events.next()?;
+        if !matches!(event, Event::Tick) {
+            app.dirty = true;
+        }
+
+        match event {
             Event::Key(key) => {
                 if key.modifiers.contains(KeyModifiers::CONTROL)
                     && (key.code == KeyCode::Char('c') || key.code == KeyCode::Char('d'))
@@ -78,6 +120,106 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                     continue;
                 }
 
+                if app.file_picker_open {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.file_picker_open = false;
+                            continue;
+                        }
+                        KeyCode::Up => {
+                            app.file_picker_cursor = app.file_picker_cursor.saturating_sub(1);
+                            continue;
+                        }
+                        KeyCode::Down => {
+                            app.file_picker_cursor = app.file_picker_cursor.saturating_add(1);
+                            continue;
+                        }
+                        KeyCode::Enter => {
+                            let selected = {
+                                let results = ui::fuzzy_rank_files(&app, &app.file_picker_query);
+                                let idx = app
+                                    .file_picker_cursor
+                                    .min(results.len().saturating_sub(1));
+                                results.get(idx).map(|m| m.path.to_string())
+                            };
+                            if let Some(path) = selected {
+                                insert_file_picker_selection(&mut app, &path);
+                            }
+                            app.file_picker_open = false;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+
+                if app.diff_overlay_open {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.diff_overlay_open = false;
+                            continue;
+                        }
+                        KeyCode::Up => {
+                            app.diff_overlay_scroll = app.diff_overlay_scroll.saturating_sub(1);
+                            continue;
+                        }
+                        KeyCode::Down => {
+                            app.diff_overlay_scroll = app.diff_overlay_scroll.saturating_add(1);
+                            continue;
+                        }
+                        KeyCode::PageUp => {
+                            app.diff_overlay_scroll = app.diff_overlay_scroll.saturating_sub(10);
+                            continue;
+                        }
+                        KeyCode::PageDown => {
+                            app.diff_overlay_scroll = app.diff_overlay_scroll.saturating_add(10);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('f')
+                {
+                    if app.search_active {
+                        app.search_active = false;
+                        app.result_scroll = app.search_prev_scroll;
+                    } else {
+                        app.search_active = true;
+                        app.search_query.clear();
+                        app.search_matches.clear();
+                        app.search_current = 0;
+                        app.search_prev_scroll = app.result_scroll;
+                        app.search_pending_jump = None;
+                    }
+                    continue;
+                }
+
+                if app.search_active {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.search_active = false;
+                            app.result_scroll = app.search_prev_scroll;
+                        }
+                        KeyCode::Enter => {
+                            app.search_pending_jump = Some(if key.modifiers.contains(KeyModifiers::SHIFT) {
+                                -1
+                            } else {
+                                1
+                            });
+                        }
+                        KeyCode::Char(c) => {
+                            app.search_query.push(c);
+                            app.search_pending_jump = Some(0);
+                        }
+                        KeyCode::Backspace => {
+                            app.search_query.pop();
+                            app.search_pending_jump = Some(0);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 let now = Instant::now();
                 let is_paste = now.duration_since(app.last_key_time) < Duration::from_millis(5);
                 app.last_key_time = now;
@@ -114,7 +256,19 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                                     continue;
                                 }
 
-                                let mut final_input = enrich_with_attached_files(&input);
+                                if let Some(job) = app.job_manager.last_command() {
+                                    if matches!(job.state, JobState::Running | JobState::Idle) {
+                                        if let Some(tx) = &job.input_tx {
+                                            let _ = tx.send(input.clone());
+                                        }
+                                        app.input_buffer = tui_textarea::TextArea::default();
+                                        app.input_buffer.set_cursor_line_style(Style::default());
+                                        continue;
+                                    }
+                                }
+
+                                let mut final_input =
+                                    enrich_with_attached_files(&input, &app.syntax_set);
                                 if app.rag_enabled {
                                     final_input =
                                         enrich_with_workspace_context(&mut app, &final_input);
@@ -122,23 +276,18 @@ pub fn run() -> Result<(), Box<dyn Error>> {
 
                                 app.history.push(HistoryEntry {
                                     is_user: true,
+                                    is_command_output: false,
                                     text: final_input.replace('\r', ""),
                                 });
 
                                 let mut messages = Vec::new();
-                                messages.push(crate::llm::Message {
-                                    role: crate::llm::Role::System,
-                                    content: base_system_prompt.clone(),
-                                });
+                                messages.push(crate::llm::Message::system(base_system_prompt.clone()));
 
                                 for entry in &app.history {
-                                    messages.push(crate::llm::Message {
-                                        role: if entry.is_user {
-                                            crate::llm::Role::User
-                                        } else {
-                                            crate::llm::Role::Assistant
-                                        },
-                                        content: entry.text.clone(),
+                                    messages.push(if entry.is_user {
+                                        crate::llm::Message::user(entry.text.clone())
+                                    } else {
+                                        crate::llm::Message::assistant(entry.text.clone())
                                     });
                                 }
 
@@ -151,19 +300,27 @@ pub fn run() -> Result<(), Box<dyn Error>> {
 
                                 let sender_token = sender.clone();
                                 let sender_final = sender.clone();
+                                let abort = llm::AbortSignal::new();
+                                app.active_generation = Some(abort.clone());
+                                app.generation += 1;
+                                let my_generation = app.generation;
 
                                 thread::spawn(move || {
                                     let started = Instant::now();
                                     let provider_res = llm::get_provider(None);
                                     match provider_res {
                                         Ok(provider) => {
-                                            let res = llm::generate_streaming(
+                                            let res = llm::generate_streaming_cancellable(
                                                 provider.as_ref(),
                                                 &messages,
                                                 2048,
+                                                &abort,
                                                 &mut |token: &str| {
                                                     let _ = sender_token.send(Event::AppMessage(
-                                                        TuiMessage::Token(token.to_string()),
+                                                        TuiMessage::Token {
+                                                            generation: my_generation,
+                                                            text: token.to_string(),
+                                                        },
                                                     ));
                                                 },
                                             );
@@ -172,6 +329,7 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                                                 Ok(final_text) => {
                                                     let _ = sender_final.send(Event::AppMessage(
                                                         TuiMessage::StreamFinished {
+                                                            generation: my_generation,
                                                             latency_ms: started
                                                                 .elapsed()
                                                                 .as_millis(),
@@ -181,14 +339,20 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                                                 }
                                                 Err(e) => {
                                                     let _ = sender_final.send(Event::AppMessage(
-                                                        TuiMessage::Error(e.to_string()),
+                                                        TuiMessage::Error {
+                                                            generation: my_generation,
+                                                            message: e.to_string(),
+                                                        },
                                                     ));
                                                 }
                                             }
                                         }
                                         Err(e) => {
                                             let _ = sender_final.send(Event::AppMessage(
-                                                TuiMessage::Error(e.to_string()),
+                                                TuiMessage::Error {
+                                                    generation: my_generation,
+                                                    message: e.to_string(),
+                                                },
                                             ));
                                         }
                                     }
@@ -215,13 +379,47 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                                 KeyCode::End => app.result_scroll = u16::MAX,
                                 _ => {}
                             }
+                        } else if key.code == KeyCode::Char('@') {
+                            app.input_buffer.input(key);
+                            app.file_picker_open = true;
+                            app.file_picker_query.clear();
+                            app.file_picker_cursor = 0;
+                        } else if app.file_picker_open {
+                            match key.code {
+                                KeyCode::Char(c) => {
+                                    app.input_buffer.input(key);
+                                    app.file_picker_query.push(c);
+                                    app.file_picker_cursor = 0;
+                                }
+                                KeyCode::Backspace => {
+                                    app.input_buffer.input(key);
+                                    if app.file_picker_query.pop().is_none() {
+                                        app.file_picker_open = false;
+                                    }
+                                    app.file_picker_cursor = 0;
+                                }
+                                _ => {
+                                    app.input_buffer.input(key);
+                                }
+                            }
                         } else {
                             app.input_buffer.input(key);
                         }
                     }
                     Route::Processing => {
                         if key.code == KeyCode::Esc {
+                            if let Some(abort) = app.active_generation.take() {
+                                abort.abort();
+                            }
+                            // Bump the generation so the cancelled thread's
+                            // Token/StreamFinished/Error messages (which may
+                            // already be queued, or delayed behind a
+                            // blocking read) are recognized as stale and
+                            // ignored instead of reappearing in history or
+                            // overwriting a subsequent generation's buffer.
+                            app.generation += 1;
                             app.is_loading = false;
+                            app.streaming_buffer.clear();
                             app.status_line = "Cancelled current request".to_string();
                             app.set_route(Route::Chat);
                         }
@@ -248,27 +446,72 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                     }
                 }
             }
-            Event::Tick => app.on_tick(),
-            Event::Resize => {}
+            Event::ConfigReload(result) => match result {
+                Ok(cfg) => {
+                    app.status_line =
+                        format!("Config reloaded — active provider: `{}`", cfg.active_provider);
+                }
+                Err(e) => {
+                    app.status_line = format!("Config invalid, keeping previous settings: {}", e);
+                }
+            },
+            Event::Tick => {
+                if app.on_tick() {
+                    app.dirty = true;
+                }
+            }
+            Event::Resize(cols, rows) => {
+                app.set_term_size(cols, rows);
+                if let Some(job) = app.job_manager.last_command() {
+                    if let Some(tx) = &job.resize_tx {
+                        let _ = tx.send((cols, rows));
+                    }
+                }
+            }
             Event::AppMessage(msg) => match msg {
-                TuiMessage::Token(s) => {
-                    let clean = s.replace('\r', "").replace('\t', "    ");
+                TuiMessage::Token { generation, text } => {
+                    if generation != app.generation {
+                        continue;
+                    }
+                    let clean = text.replace('\r', "").replace('\t', "    ");
                     app.streaming_buffer.push_str(&clean);
                 }
                 TuiMessage::StreamFinished {
+                    generation,
                     latency_ms,
                     output_chars,
                 } => {
-                    let final_response = app.streaming_buffer.clone();
+                    if generation != app.generation {
+                        continue;
+                    }
+                    let raw_response = app.streaming_buffer.clone();
+                    let (final_response, proposed_edits) = extract_proposed_edits(&raw_response);
                     app.is_loading = false;
+                    app.active_generation = None;
                     app.set_route(Route::Chat);
 
                     if !final_response.trim().is_empty() {
                         app.history.push(HistoryEntry {
                             is_user: false,
+                            is_command_output: false,
                             text: final_response,
                         });
                     }
+                    if !proposed_edits.is_empty() {
+                        let paths: Vec<&str> =
+                            proposed_edits.iter().map(|e| e.path.as_str()).collect();
+                        app.history.push(HistoryEntry {
+                            is_user: false,
+                            is_command_output: false,
+                            text: format!(
+                                "Proposed edits to: {}\nReview with `/diff`, apply with `/approve`, or discard with `/deny`.",
+                                paths.join(", ")
+                            ),
+                        });
+                        app.pending_edits.extend(proposed_edits);
+                        app.diff_overlay_open = true;
+                        app.diff_overlay_scroll = 0;
+                    }
                     app.total_responses += 1;
                     app.total_output_chars += output_chars as u64;
                     app.last_latency_ms = Some(latency_ms);
@@ -279,18 +522,23 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                     app.streaming_buffer.clear();
                     app.focus = Focus::Input;
                 }
-                TuiMessage::Error(e) => {
+                TuiMessage::Error { generation, message } => {
+                    if generation != app.generation {
+                        continue;
+                    }
                     let partial = app.streaming_buffer.trim().to_string();
                     app.is_loading = false;
+                    app.active_generation = None;
                     app.set_route(Route::Chat);
                     app.streaming_buffer.clear();
                     let text = if partial.is_empty() {
-                        format!("**Error:** {}", e)
+                        format!("**Error:** {}", message)
                     } else {
-                        format!("{}\n\n**Error:** {}", partial, e)
+                        format!("{}\n\n**Error:** {}", partial, message)
                     };
                     app.history.push(HistoryEntry {
                         is_user: false,
+                        is_command_output: false,
                         text,
                     });
                     app.status_line = "Request failed".to_string();
@@ -298,13 +546,24 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                 TuiMessage::WarmupStatus(status) => {
                     app.status_line = status;
                 }
-                TuiMessage::WorkspaceIndexReady { index, source } => {
+                TuiMessage::WorkspaceIndexReady {
+                    index,
+                    source,
+                    reason,
+                } => {
+                    app.status_line = format!(
+                        "Index synced ({}): {} reused, {} reindexed",
+                        source, index.reused_files, index.reindexed_files
+                    );
                     app.workspace_index = Some(index);
-                    app.status_line = format!("Index synced ({})", source);
+                    if reason.is_some() {
+                        app.last_reindex_reason = reason;
+                    }
+                }
+                TuiMessage::GitStatusReady { status } => {
+                    app.git_status = status;
                 }
                 TuiMessage::CommandStarted { pid, cmd } => {
-                    app.command_pid = Some(pid);
-                    app.command_running = true;
                     app.status_line = format!("Running pid {}: {}", pid, cmd);
                 }
                 TuiMessage::CommandStream(chunk) => {
@@ -313,14 +572,129 @@ pub fn run() -> Result<(), Box<dyn Error>> {
                 }
                 TuiMessage::CommandOutput { cmd, output } => {
                     app.is_loading = false;
-                    app.command_running = false;
-                    app.command_pid = None;
                     app.streaming_buffer.clear();
-                    app.history.push(HistoryEntry {
-                        is_user: false,
-                        text: format!("```bash\n$ {}\n{}\n```", cmd, output),
-                    });
-                    app.status_line = format!("Command completed: {}", cmd);
+                    if let Some(kind) = app.pending_diagnostics_kind.take() {
+                        app.history.push(HistoryEntry {
+                            is_user: false,
+                            is_command_output: false,
+                            text: summarize_diagnostics(&kind, &cmd, &output),
+                        });
+                        app.status_line = "Diagnostics ready".to_string();
+                    } else {
+                        app.history.push(HistoryEntry {
+                            is_user: false,
+                            is_command_output: true,
+                            text: format!("$ {}\n{}", cmd, output),
+                        });
+                        app.status_line = format!("Command completed: {}", cmd);
+                    }
+                }
+                TuiMessage::JobUpdate {
+                    id,
+                    state,
+                    pid,
+                    error,
+                } => {
+                    app.job_manager.apply_update(id, state, pid, error);
+                }
+                TuiMessage::ValidationReady { results } => {
+                    app.is_loading = false;
+                    let all_passed = results.iter().all(|r| r.passed);
+                    let force = app.pending_validation_force.take().unwrap_or(false);
+
+                    let mut lines = vec!["Validation results:".to_string()];
+                    for r in &results {
+                        lines.push(format!(
+                            "- {} (`{}`): {}, exit={}\n  {}",
+                            r.name,
+                            r.command,
+                            if r.passed { "PASS" } else { "FAIL" },
+                            r.exit_code
+                                .map(|c| c.to_string())
+                                .unwrap_or_else(|| "signal".to_string()),
+                            r.output_tail,
+                        ));
+                    }
+
+                    if !all_passed && !force {
+                        lines.push("Validation failed — plan is blocked at this step. Fix the checks above or re-run `/next --force` to proceed anyway.".to_string());
+                        app.history.push(HistoryEntry {
+                            is_user: false,
+                            is_command_output: false,
+                            text: lines.join("\n"),
+                        });
+                        app.status_line = "Validation failed".to_string();
+                    } else {
+                        lines.push(if all_passed {
+                            "All checks passed.".to_string()
+                        } else {
+                            "Proceeding to the next step despite failed checks (--force).".to_string()
+                        });
+                        app.history.push(HistoryEntry {
+                            is_user: false,
+                            is_command_output: false,
+                            text: lines.join("\n"),
+                        });
+                        advance_plan_step(&mut app, VALIDATION_STEP_LABEL);
+                        app.status_line = "Validation complete".to_string();
+                    }
+                }
+                TuiMessage::ExtensionReady { name, result } => {
+                    app.is_loading = false;
+                    match result {
+                        Ok((Some(0), output)) => {
+                            app.history.push(HistoryEntry {
+                                is_user: false,
+                                is_command_output: true,
+                                text: if output.is_empty() {
+                                    format!("(niko-{} produced no output)", name)
+                                } else {
+                                    output
+                                },
+                            });
+                            app.status_line = format!("niko-{} finished", name);
+                        }
+                        Ok((code, output)) => {
+                            push_error(
+                                &mut app,
+                                &format!(
+                                    "niko-{} exited with {}: {}",
+                                    name,
+                                    code.map(|c| c.to_string()).unwrap_or_else(|| "signal".to_string()),
+                                    output.trim()
+                                ),
+                            );
+                            app.status_line = format!("niko-{} failed", name);
+                        }
+                        Err(e) => {
+                            push_error(&mut app, &e);
+                            app.status_line = format!("niko-{} failed", name);
+                        }
+                    }
+                }
+                TuiMessage::FetchReady { url, result } => {
+                    match result {
+                        Ok(text) => {
+                            let lang = fence_lang_for(
+                                &app.syntax_set,
+                                url.split(['?', '#']).next().unwrap_or(&url),
+                            );
+                            app.history.push(HistoryEntry {
+                                is_user: false,
+                                is_command_output: false,
+                                text: format!("\n\n[Attached file: {}]\n```{}\n{}\n```", url, lang, text),
+                            });
+                            app.status_line = format!("Fetched {}", url);
+                        }
+                        Err(e) => {
+                            app.history.push(HistoryEntry {
+                                is_user: false,
+                                is_command_output: false,
+                                text: format!("Could not fetch `{}`: {}", url, e),
+                            });
+                            app.status_line = "Fetch failed".to_string();
+                        }
+                    }
                 }
             },
         }
@@ -365,14 +739,53 @@ fn spawn_warmup(sender: mpsc::Sender<Event>) {
     });
 }
 
-fn spawn_background_indexer(sender: mpsc::Sender<Event>) {
+/// How long to coalesce a burst of filesystem events before rebuilding, so
+/// N rapid saves (or a `git checkout`) trigger one rebuild instead of N.
+const INDEXER_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Safety-net rebuild interval in case the watcher misses something (a
+/// platform quirk, a mount `notify` can't see); far longer than the old
+/// fixed 45s poll since the watcher is expected to catch real edits
+/// immediately instead.
+const INDEXER_FALLBACK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Caps how many changed paths are named in a rebuild's `reason` string.
+const INDEXER_REASON_PATH_LIMIT: usize = 5;
+
+/// Keeps the workspace index fresh by watching `cwd` for changes instead of
+/// polling on a fixed timer: an edit debounces into one `build_incremental`
+/// call almost immediately, ignored paths (via `path_is_indexable`) never
+/// trigger a rebuild, and a long fallback timer still fires if the watcher
+/// ever misses an event. Reports state via `TuiMessage::JobUpdate` and
+/// honors `JobControl::{Pause,Resume,Cancel}` the same way every other
+/// tracked job does.
+fn spawn_background_indexer(
+    sender: mpsc::Sender<Event>,
+    job_id: u64,
+    control_rx: mpsc::Receiver<JobControl>,
+) {
     thread::spawn(move || {
         let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         let cache_path = crate::config::config_dir().join("workspace_index.json");
+        let mut paused = false;
+
+        let send_state = |state: JobState| {
+            let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                id: job_id,
+                state,
+                pid: None,
+                error: None,
+            }));
+        };
 
-        loop {
-            let index =
-                workspace::WorkspaceIndex::build_incremental(&cwd, &cache_path, 1600, 256 * 1024);
+        let rebuild = |reason: Option<String>| {
+            let index = workspace::WorkspaceIndex::build_incremental(
+                &cwd,
+                &cache_path,
+                1600,
+                256 * 1024,
+                workspace::DEFAULT_RETENTION_SECS,
+            );
             let source = if cache_path.exists() {
                 "incremental"
             } else {
@@ -382,8 +795,114 @@ fn spawn_background_indexer(sender: mpsc::Sender<Event>) {
             let _ = sender.send(Event::AppMessage(TuiMessage::WorkspaceIndexReady {
                 index,
                 source,
+                reason,
             }));
-            thread::sleep(Duration::from_secs(45));
+        };
+
+        let (raw_tx, raw_rx) = mpsc::channel::<notify::Event>();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                if let Ok(event) = res {
+                    let _ = raw_tx.send(event);
+                }
+            })
+            .ok();
+        if let Some(w) = watcher.as_mut() {
+            let _ = w.watch(&cwd, notify::RecursiveMode::Recursive);
+        }
+
+        send_state(JobState::Running);
+        rebuild(Some("startup".to_string()));
+        send_state(JobState::Idle);
+
+        loop {
+            let deadline = Instant::now() + INDEXER_FALLBACK_INTERVAL;
+            let mut changed_paths: Vec<String> = Vec::new();
+
+            'wait: loop {
+                match control_rx.try_recv() {
+                    Ok(JobControl::Cancel) => {
+                        send_state(JobState::Cancelled);
+                        return;
+                    }
+                    Ok(JobControl::Pause) => {
+                        paused = true;
+                        send_state(JobState::Idle);
+                    }
+                    Ok(JobControl::Resume) => paused = false,
+                    Err(mpsc::TryRecvError::Empty) | Err(mpsc::TryRecvError::Disconnected) => {}
+                }
+
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break 'wait; // fallback timer fired
+                }
+
+                match raw_rx.recv_timeout(remaining.min(Duration::from_millis(500))) {
+                    Ok(event) => {
+                        if !event.paths.iter().any(|p| path_is_indexable(&cwd, p)) {
+                            continue;
+                        }
+                        for p in &event.paths {
+                            if let Ok(rel) = p.strip_prefix(&cwd) {
+                                changed_paths.push(rel.display().to_string());
+                            }
+                        }
+                        // Drain the rest of this burst so rapid saves coalesce.
+                        loop {
+                            match raw_rx.recv_timeout(INDEXER_WATCH_DEBOUNCE) {
+                                Ok(more) => {
+                                    for p in &more.paths {
+                                        if let Ok(rel) = p.strip_prefix(&cwd) {
+                                            changed_paths.push(rel.display().to_string());
+                                        }
+                                    }
+                                }
+                                Err(_) => break,
+                            }
+                        }
+                        break 'wait;
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        // No watcher (failed to start) or it died; just fall
+                        // back to the periodic timer.
+                        thread::sleep(Duration::from_millis(500));
+                    }
+                }
+            }
+
+            if paused {
+                continue;
+            }
+
+            let reason = if changed_paths.is_empty() {
+                "periodic fallback".to_string()
+            } else {
+                changed_paths.sort();
+                changed_paths.dedup();
+                let shown = changed_paths.len().min(INDEXER_REASON_PATH_LIMIT);
+                let mut reason = format!("changed: {}", changed_paths[..shown].join(", "));
+                if changed_paths.len() > shown {
+                    reason.push_str(&format!(" (+{} more)", changed_paths.len() - shown));
+                }
+                reason
+            };
+
+            send_state(JobState::Running);
+            rebuild(Some(reason));
+            send_state(JobState::Idle);
+        }
+    });
+}
+
+fn spawn_background_git_poller(sender: mpsc::Sender<Event>) {
+    thread::spawn(move || {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        loop {
+            let status = git::GitStatus::collect(&cwd);
+            let _ = sender.send(Event::AppMessage(TuiMessage::GitStatusReady { status }));
+            thread::sleep(Duration::from_secs(10));
         }
     });
 }
@@ -430,6 +949,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                     }
                     app.history.push(HistoryEntry {
                         is_user: false,
+                        is_command_output: false,
                         text: lines.join("\n"),
                     });
                 }
@@ -442,6 +962,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
             if name.is_empty() {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: "Usage: `/provider <name>`".to_string(),
                 });
                 return true;
@@ -450,6 +971,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                 Ok(()) => {
                     app.history.push(HistoryEntry {
                         is_user: false,
+                        is_command_output: false,
                         text: format!("Active provider set to `{}`", name),
                     });
                     app.status_line = format!("Switched provider to {}", name);
@@ -483,6 +1005,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                     }
                     app.history.push(HistoryEntry {
                         is_user: false,
+                        is_command_output: false,
                         text: lines.join("\n"),
                     });
                 }
@@ -495,6 +1018,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
             if model.is_empty() {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: "Usage: `/model <id>`".to_string(),
                 });
                 return true;
@@ -504,6 +1028,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                     Ok(()) => {
                         app.history.push(HistoryEntry {
                             is_user: false,
+                            is_command_output: false,
                             text: format!("Model for `{}` set to `{}`", name, model),
                         });
                         app.status_line = format!("Model updated: {}", model);
@@ -519,14 +1044,16 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                 Ok(cfg) => {
                     app.history.push(HistoryEntry {
                         is_user: false,
+                        is_command_output: false,
                         text: format!(
-                            "Session stats:\n- Messages: {}\n- Responses: {}\n- Total output chars: {}\n- Last latency: {} ms\n- Active provider: `{}`\n- RAG: {}",
+                            "Session stats:\n- Messages: {}\n- Responses: {}\n- Total output chars: {}\n- Last latency: {} ms\n- Active provider: `{}`\n- RAG: {}\n- Last reindex reason: {}",
                             app.history.len(),
                             app.total_responses,
                             app.total_output_chars,
                             app.last_latency_ms.unwrap_or(0),
                             cfg.active_provider,
-                            if app.rag_enabled { "on" } else { "off" }
+                            if app.rag_enabled { "on" } else { "off" },
+                            app.last_reindex_reason.as_deref().unwrap_or("none yet")
                         ),
                     });
                 }
@@ -543,6 +1070,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
             if query.is_empty() {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: "Usage: `/search <query>`".to_string(),
                 });
                 return true;
@@ -563,6 +1091,7 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                 };
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text,
                 });
             }
@@ -571,38 +1100,116 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
         "/open" => {
             let path = input.strip_prefix("/open").unwrap_or_default().trim();
             if path.is_empty() {
-                app.history.push(HistoryEntry {
-                    is_user: false,
-                    text: "Usage: `/open <path>`".to_string(),
-                });
+                app.file_picker_open = true;
+                app.file_picker_query.clear();
+                app.file_picker_cursor = 0;
                 return true;
             }
-            let payload = enrich_with_attached_files(&format!("@{}", path));
+            let payload = enrich_with_attached_files(&format!("@{}", path), &app.syntax_set);
             if payload.contains("[Attached file:") {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: payload,
                 });
             } else {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: format!("Could not open `{}` as UTF-8 text file.", path),
                 });
             }
             true
         }
+        "/fetch" => {
+            let url = input.strip_prefix("/fetch").unwrap_or_default().trim();
+            if url.is_empty() {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: "Usage: `/fetch <url>`".to_string(),
+                });
+                return true;
+            }
+            app.status_line = format!("Fetching {}...", url);
+            spawn_fetch(url.to_string(), sender);
+            true
+        }
         "/plan" => {
-            let task = input.strip_prefix("/plan").unwrap_or_default().trim();
+            let mut rest = input.strip_prefix("/plan").unwrap_or_default().trim();
+            let mut resume = false;
+            let mut force = false;
+            loop {
+                if let Some(stripped) = rest.strip_prefix("--resume") {
+                    resume = true;
+                    rest = stripped.trim_start();
+                } else if let Some(stripped) = rest.strip_prefix("--force") {
+                    force = true;
+                    rest = stripped.trim_start();
+                } else {
+                    break;
+                }
+            }
+            let task = rest;
+
+            if resume {
+                match plan::load() {
+                    Ok(Some(saved)) => {
+                        let matches = task.is_empty() || plan::hash_task(task) == saved.task_hash;
+                        if matches || force {
+                            app.planner_cursor = saved.first_pending();
+                            let done = app.planner_cursor;
+                            let total = saved.steps.len();
+                            app.planner_steps =
+                                saved.steps.iter().map(|s| s.description.clone()).collect();
+                            let task_label = saved.task.clone();
+                            app.current_plan = Some(saved);
+                            app.history.push(HistoryEntry {
+                                is_user: false,
+                                is_command_output: false,
+                                text: format!(
+                                    "Resumed plan for: {}\n{}/{} steps already done. Use `/next` to continue.",
+                                    task_label, done, total
+                                ),
+                            });
+                        } else {
+                            app.history.push(HistoryEntry {
+                                is_user: false,
+                                is_command_output: false,
+                                text: format!(
+                                    "Saved plan doesn't match this task (saved: \"{}\"). Use `/plan <task>` to start fresh, or `/plan --resume --force <task>` to resume anyway.",
+                                    saved.task
+                                ),
+                            });
+                        }
+                    }
+                    Ok(None) => {
+                        app.history.push(HistoryEntry {
+                            is_user: false,
+                            is_command_output: false,
+                            text: "No saved plan to resume. Use `/plan <task>`.".to_string(),
+                        });
+                    }
+                    Err(e) => push_error(app, &e),
+                }
+                return true;
+            }
+
             if task.is_empty() {
                 app.history.push(HistoryEntry {
                     is_user: false,
-                    text: "Usage: `/plan <task>`".to_string(),
+                    is_command_output: false,
+                    text: "Usage: `/plan <task>` or `/plan --resume [task]`".to_string(),
                 });
                 return true;
             }
-            let steps = build_local_plan(task);
-            app.planner_steps = steps;
+            let new_plan = plan::Plan::new(task, build_local_plan(task));
+            app.planner_steps = new_plan.steps.iter().map(|s| s.description.clone()).collect();
             app.planner_cursor = 0;
+            if let Err(e) = plan::save(&new_plan) {
+                push_error(app, &format!("Plan created but not saved: {}", e));
+            }
+            app.current_plan = Some(new_plan);
             let mut out = vec!["Plan created:".to_string()];
             for (i, step) in app.planner_steps.iter().enumerate() {
                 out.push(format!("{}. {}", i + 1, step));
@@ -610,14 +1217,18 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
             out.push("Use `/next` to view next step.".to_string());
             app.history.push(HistoryEntry {
                 is_user: false,
+                is_command_output: false,
                 text: out.join("\n"),
             });
             true
         }
         "/next" => {
+            let force = input.strip_prefix("/next").unwrap_or_default().trim() == "--force";
+
             if app.planner_steps.is_empty() {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: "No active plan. Use `/plan <task>`.".to_string(),
                 });
                 return true;
@@ -625,16 +1236,21 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
             if app.planner_cursor >= app.planner_steps.len() {
                 app.history.push(HistoryEntry {
                     is_user: false,
+                    is_command_output: false,
                     text: "Plan complete.".to_string(),
                 });
                 return true;
             }
             let step = app.planner_steps[app.planner_cursor].clone();
-            app.planner_cursor += 1;
-            app.history.push(HistoryEntry {
-                is_user: false,
-                text: format!("Step {}: {}", app.planner_cursor, step),
-            });
+
+            if step == VALIDATION_STEP_LABEL {
+                app.pending_validation_force = Some(force);
+                app.status_line = "Running validation checks...".to_string();
+                spawn_validation(app, sender);
+                return true;
+            }
+
+            advance_plan_step(app, &step);
             true
         }
         "/rag" => {
@@ -648,164 +1264,709 @@ fn handle_slash_command(app: &mut App, input: &str, sender: &mpsc::Sender<Event>
                     app.rag_enabled = false;
                     app.status_line = "RAG disabled".to_string();
                 }
+                "keyword" => {
+                    app.rag_mode = workspace::RagMode::Keyword;
+                    app.status_line = "RAG mode: keyword".to_string();
+                }
+                "semantic" => {
+                    app.rag_mode = workspace::RagMode::Semantic;
+                    app.status_line = "RAG mode: semantic".to_string();
+                }
+                "hybrid" => {
+                    app.rag_mode = workspace::RagMode::Hybrid;
+                    app.status_line = "RAG mode: hybrid".to_string();
+                }
+                _ => {
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: "Usage: `/rag on|off|keyword|semantic|hybrid`".to_string(),
+                    });
+                }
+            }
+            true
+        }
+        "/watch" => {
+            let arg = parts.next().unwrap_or_default().to_lowercase();
+            match arg.as_str() {
+                "on" => start_workspace_watcher(app, sender.clone()),
+                "off" => stop_workspace_watcher(app),
                 _ => {
                     app.history.push(HistoryEntry {
                         is_user: false,
-                        text: "Usage: `/rag on|off`".to_string(),
+                        is_command_output: false,
+                        text: "Usage: `/watch on|off`".to_string(),
                     });
                 }
             }
             true
         }
-        "/run" => {
-            let command = input
-                .strip_prefix("/run")
-                .unwrap_or_default()
-                .trim()
-                .to_string();
+        "/run" | "/pty" => {
+            let command = input.strip_prefix(cmd).unwrap_or_default().trim().to_string();
             if command.is_empty() {
                 app.history.push(HistoryEntry {
                     is_user: false,
-                    text: "Usage: `/run <shell command>`".to_string(),
+                    is_command_output: false,
+                    text: format!("Usage: `{} <shell command>`", cmd),
                 });
                 return true;
             }
 
             app.pending_command = Some(command.clone());
+            let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+            let safety = crate::config::load().map(|c| c.safety).unwrap_or_default();
+            let profile = sandbox::build_profile(&safety.sandbox, &cwd);
             app.history.push(HistoryEntry {
                 is_user: false,
+                is_command_output: false,
                 text: format!(
-                    "Pending command:\n```bash\n{}\n```\nApprove with `/approve` or cancel with `/deny`.",
-                    command
+                    "Pending command (PTY-backed, type to send stdin once running):\n```bash\n{}\n```\nExecution profile: {}\nApprove with `/approve` or cancel with `/deny`.",
+                    command, profile.description
                 ),
             });
             true
         }
-        "/approve" => {
-            let Some(command) = app.pending_command.take() else {
+        "/diagnostics" => {
+            let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+            let Some((kind, command)) = detect_diagnostics_command(&cwd) else {
                 app.history.push(HistoryEntry {
                     is_user: false,
-                    text: "No pending command. Use `/run <cmd>` first.".to_string(),
+                    is_command_output: false,
+                    text: "No recognized build toolchain found (looked for Cargo.toml, tsconfig.json, package.json).".to_string(),
                 });
                 return true;
             };
 
-            if is_blocked_command(&command) {
-                app.history.push(HistoryEntry {
-                    is_user: false,
-                    text: "Command blocked by safety rules.".to_string(),
-                });
-                return true;
-            }
-
-            app.command_running = true;
             app.is_loading = true;
-            run_command_async(command, sender.clone());
-            app.status_line = "Running approved command...".to_string();
+            app.pending_diagnostics_kind = Some(kind.to_string());
+            spawn_tracked_command(app, command, sender);
+            app.status_line = format!("Running diagnostics ({})...", kind);
             true
         }
-        "/stop" => {
-            if let Some(pid) = app.command_pid {
-                match stop_running_command(pid) {
-                    Ok(()) => {
-                        app.status_line = format!("Sent stop signal to pid {}", pid);
-                        app.history.push(HistoryEntry {
-                            is_user: false,
-                            text: format!("Stop signal sent to process `{}`.", pid),
-                        });
+        "/approve" => {
+            if let Some(command) = app.pending_command.take() {
+                if is_blocked_command(&command) {
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: "Command blocked by safety rules.".to_string(),
+                    });
+                    return true;
+                }
+
+                app.is_loading = true;
+                spawn_tracked_command(app, command, sender);
+                app.status_line = "Running approved command...".to_string();
+                return true;
+            }
+
+            if !app.pending_edits.is_empty() {
+                let mut written = Vec::new();
+                let mut failed = Vec::new();
+                for edit in app.pending_edits.drain(..) {
+                    match std::fs::write(&edit.path, &edit.new_contents) {
+                        Ok(()) => written.push(edit.path),
+                        Err(e) => failed.push(format!("{}: {}", edit.path, e)),
                     }
-                    Err(e) => {
-                        app.history.push(HistoryEntry {
-                            is_user: false,
-                            text: format!("**Error stopping process {}:** {}", pid, e),
-                        });
+                }
+                app.diff_overlay_open = false;
+
+                let mut text = String::new();
+                if !written.is_empty() {
+                    text.push_str(&format!("Wrote edits to: {}", written.join(", ")));
+                }
+                if !failed.is_empty() {
+                    if !text.is_empty() {
+                        text.push('\n');
                     }
+                    text.push_str(&format!("Failed: {}", failed.join("; ")));
                 }
-            } else {
                 app.history.push(HistoryEntry {
                     is_user: false,
-                    text: "No running command to stop.".to_string(),
+                    is_command_output: false,
+                    text,
                 });
+                return true;
             }
-            true
-        }
-        "/deny" => {
-            app.pending_command = None;
-            app.status_line = "Pending command discarded".to_string();
-            true
-        }
-        _ => {
+
             app.history.push(HistoryEntry {
                 is_user: false,
-                text: format!("Unknown command: `{}`. Use `/help`.", cmd),
+                is_command_output: false,
+                text: "No pending command or edits. Use `/run <cmd>` or wait for a proposed edit.".to_string(),
             });
             true
         }
-    }
-}
-
-fn run_command_async(cmd: String, sender: mpsc::Sender<Event>) {
-    thread::spawn(move || {
-        let mut child = if cfg!(target_os = "windows") {
-            match Command::new("cmd")
-                .args(["/C", &cmd])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-            {
-                Ok(c) => c,
-                Err(e) => {
-                    let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
-                        cmd,
-                        output: format!("Failed to run command: {}", e),
-                    }));
-                    return;
+        "/stop" => {
+            let Some(job) = app.job_manager.last_command() else {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: "No running command to stop.".to_string(),
+                });
+                return true;
+            };
+            let id = job.id;
+            match app.job_manager.cancel(id) {
+                Ok(()) => {
+                    app.status_line = format!("Sent stop signal to job #{}", id);
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: format!("Stop signal sent to job `#{}`.", id),
+                    });
                 }
-            }
-        } else {
-            match Command::new("sh")
-                .args(["-lc", &cmd])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-            {
-                Ok(c) => c,
                 Err(e) => {
-                    let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
-                        cmd,
-                        output: format!("Failed to run command: {}", e),
-                    }));
-                    return;
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: format!("**Error stopping job {}:** {}", id, e),
+                    });
                 }
             }
+            true
+        }
+        "/jobs" => {
+            if app.job_manager.list().is_empty() {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: "No tracked jobs.".to_string(),
+                });
+                return true;
+            }
+            let now = jobs::now_unix();
+            let mut lines = vec!["Tracked jobs:".to_string()];
+            for job in app.job_manager.list() {
+                let runtime = now.saturating_sub(job.started_unix);
+                lines.push(format!(
+                    "- #{} [{}] {} — {} ({}s){}",
+                    job.id,
+                    job.kind.label(),
+                    job.label,
+                    job.state.label(),
+                    runtime,
+                    job.last_error
+                        .as_ref()
+                        .map(|e| format!(" — last error: {}", e))
+                        .unwrap_or_default(),
+                ));
+            }
+            app.history.push(HistoryEntry {
+                is_user: false,
+                is_command_output: false,
+                text: lines.join("\n"),
+            });
+            true
+        }
+        "/cancel" | "/pause" | "/resume" => {
+            let Some(id) = parts.next().and_then(|s| s.parse::<u64>().ok()) else {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: format!("Usage: `{} <job id>` (see `/jobs`)", cmd),
+                });
+                return true;
+            };
+            let result = match cmd {
+                "/cancel" => app.job_manager.cancel(id),
+                "/pause" => app.job_manager.pause(id),
+                _ => app.job_manager.resume(id),
+            };
+            match result {
+                Ok(()) => {
+                    app.status_line = format!("{} sent to job #{}", &cmd[1..], id);
+                }
+                Err(e) => push_error(app, &e),
+            }
+            true
+        }
+        "/deny" => {
+            let had_command = app.pending_command.take().is_some();
+            let had_edits = !app.pending_edits.is_empty();
+            app.pending_edits.clear();
+            app.diff_overlay_open = false;
+            app.status_line = if had_command || had_edits {
+                "Pending command/edits discarded".to_string()
+            } else {
+                "Nothing pending".to_string()
+            };
+            true
+        }
+        "/diff" => {
+            if app.pending_edits.is_empty() {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: "No proposed edits to review.".to_string(),
+                });
+            } else {
+                app.diff_overlay_open = true;
+                app.diff_overlay_scroll = 0;
+            }
+            true
+        }
+        "/history" => {
+            const DEFAULT_COUNT: usize = 20;
+            let arg = parts.next().unwrap_or_default();
+            let result = if arg == "search" {
+                let query = parts.collect::<Vec<_>>().join(" ");
+                if query.is_empty() {
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: "Usage: `/history search <query>`".to_string(),
+                    });
+                    return true;
+                }
+                history::search(&query)
+            } else {
+                let n = arg.parse::<usize>().unwrap_or(DEFAULT_COUNT);
+                history::recent(n)
+            };
+            match result {
+                Ok(entries) if entries.is_empty() => {
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: "No matching command history.".to_string(),
+                    });
+                }
+                Ok(entries) => {
+                    let mut lines = vec!["Command history (most recent first):".to_string()];
+                    for entry in &entries {
+                        lines.push(format!(
+                            "- #{} `{}` — exit={} {}ms cwd={}",
+                            entry.id,
+                            entry.command,
+                            entry
+                                .exit_code
+                                .map(|c| c.to_string())
+                                .unwrap_or_else(|| "signal".to_string()),
+                            entry.duration_ms,
+                            entry.cwd,
+                        ));
+                    }
+                    lines.push(String::new());
+                    lines.push("Rerun one with `/rerun <id>`.".to_string());
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: lines.join("\n"),
+                    });
+                }
+                Err(e) => push_error(app, &e),
+            }
+            true
+        }
+        "/rerun" => {
+            let Some(id) = parts.next().and_then(|s| s.parse::<u64>().ok()) else {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: "Usage: `/rerun <id>` (see `/history`)".to_string(),
+                });
+                return true;
+            };
+            match history::find(id) {
+                Ok(Some(entry)) => {
+                    app.pending_command = Some(entry.command.clone());
+                    app.history.push(HistoryEntry {
+                        is_user: false,
+                        is_command_output: false,
+                        text: format!(
+                            "Pending command (from history #{}, PTY-backed):\n```bash\n{}\n```\nApprove with `/approve` or cancel with `/deny`.",
+                            entry.id, entry.command
+                        ),
+                    });
+                }
+                Ok(None) => push_error(app, &format!("No history entry with id {}", id)),
+                Err(e) => push_error(app, &e),
+            }
+            true
+        }
+        _ => {
+            let name = cmd.trim_start_matches('/');
+            let Some(ext) = extensions::find(name) else {
+                app.history.push(HistoryEntry {
+                    is_user: false,
+                    is_command_output: false,
+                    text: format!("Unknown command: `{}`. Use `/help`.", cmd),
+                });
+                return true;
+            };
+
+            let args = input.strip_prefix(cmd).unwrap_or_default().trim();
+            let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+            let task = app
+                .planner_steps
+                .get(app.planner_cursor)
+                .cloned()
+                .or_else(|| app.pending_command.clone());
+
+            app.status_line = format!("Running niko-{}...", ext.name);
+            spawn_extension(app, ext, args.to_string(), cwd, task, sender);
+            true
+        }
+    }
+}
+
+/// Picks a machine-readable build invocation from the project files present
+/// in `cwd`, returning `(toolchain label, shell command)`. Cargo is checked
+/// first since a workspace may also carry a `package.json` for tooling.
+fn detect_diagnostics_command(cwd: &Path) -> Option<(&'static str, String)> {
+    if cwd.join("Cargo.toml").exists() {
+        Some(("cargo", "cargo build --message-format=json".to_string()))
+    } else if cwd.join("tsconfig.json").exists() {
+        Some((
+            "tsc",
+            "npx --no-install tsc --noEmit --pretty false".to_string(),
+        ))
+    } else if cwd.join("package.json").exists() {
+        Some(("npm", "npm run build --if-present".to_string()))
+    } else {
+        None
+    }
+}
+
+/// One build error/warning, normalized across toolchains so the model can be
+/// pointed at an exact span instead of a raw log line.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Diagnostic {
+    file: String,
+    line: u32,
+    column: u32,
+    severity: String,
+    message: String,
+}
+
+/// Cap on diagnostics included in the summary, so a build with hundreds of
+/// errors doesn't blow the prompt budget — the count of anything dropped is
+/// still reported.
+const MAX_DIAGNOSTICS: usize = 40;
+
+/// Parses `cargo build --message-format=json`'s newline-delimited JSON
+/// stream into `Diagnostic`s, keeping only actual compiler errors/warnings
+/// (not build-script or metadata lines).
+fn parse_cargo_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            continue;
+        }
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if value.get("reason").and_then(|r| r.as_str()) != Some("compiler-message") {
+            continue;
+        }
+        let Some(message) = value.get("message") else {
+            continue;
+        };
+        let severity = message
+            .get("level")
+            .and_then(|l| l.as_str())
+            .unwrap_or("note")
+            .to_string();
+        if severity != "error" && severity != "warning" {
+            continue;
+        }
+        let text = message
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let spans = message.get("spans").and_then(|s| s.as_array());
+        let primary = spans.and_then(|spans| {
+            spans
+                .iter()
+                .find(|s| s.get("is_primary").and_then(|p| p.as_bool()) == Some(true))
+                .or_else(|| spans.first())
+        });
+        let (file, line_no, column) = match primary {
+            Some(s) => (
+                s.get("file_name")
+                    .and_then(|f| f.as_str())
+                    .unwrap_or("?")
+                    .to_string(),
+                s.get("line_start").and_then(|l| l.as_u64()).unwrap_or(0) as u32,
+                s.get("column_start").and_then(|c| c.as_u64()).unwrap_or(0) as u32,
+            ),
+            None => ("?".to_string(), 0, 0),
+        };
+
+        out.push(Diagnostic {
+            file,
+            line: line_no,
+            column,
+            severity,
+            message: text,
+        });
+    }
+    out
+}
+
+/// Parses `tsc`'s `file(line,col): error TSxxxx: message` output lines.
+fn parse_tsc_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let re = regex::Regex::new(r"^(.+?)\((\d+),(\d+)\): (error|warning) (TS\d+): (.+)$").unwrap();
+    let mut out = Vec::new();
+    for line in output.lines() {
+        let Some(caps) = re.captures(line.trim()) else {
+            continue;
+        };
+        out.push(Diagnostic {
+            file: caps[1].to_string(),
+            line: caps[2].parse().unwrap_or(0),
+            column: caps[3].parse().unwrap_or(0),
+            severity: caps[4].to_string(),
+            message: format!("{}: {}", &caps[5], &caps[6]),
+        });
+    }
+    out
+}
+
+/// Turns a `/diagnostics` build's raw output into a deduplicated, capped
+/// summary ready to be enriched into a prompt like an attachment.
+fn summarize_diagnostics(kind: &str, cmd: &str, output: &str) -> String {
+    let mut diagnostics = match kind {
+        "cargo" => parse_cargo_diagnostics(output),
+        "tsc" => parse_tsc_diagnostics(output),
+        _ => Vec::new(),
+    };
+
+    if diagnostics.is_empty() {
+        if kind == "npm" {
+            // No structured format for an arbitrary `npm run build`; fall
+            // back to the same capped raw dump `/run` would show.
+            let mut capped = output.to_string();
+            if capped.len() > 4000 {
+                capped.truncate(4000);
+                capped.push_str("\n[...truncated]");
+            }
+            return format!("[diagnostics: {}]\n```text\n{}\n```", cmd, capped);
+        }
+        return format!("`{}` reported no errors or warnings.", cmd);
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    diagnostics.retain(|d| seen.insert(d.clone()));
+    let total = diagnostics.len();
+    diagnostics.truncate(MAX_DIAGNOSTICS);
+
+    let errors = diagnostics.iter().filter(|d| d.severity == "error").count();
+    let warnings = diagnostics
+        .iter()
+        .filter(|d| d.severity == "warning")
+        .count();
+
+    let mut text = format!(
+        "[diagnostics: {}] {} error{}, {} warning{} from `{}`\n",
+        kind,
+        errors,
+        if errors == 1 { "" } else { "s" },
+        warnings,
+        if warnings == 1 { "" } else { "s" },
+        cmd
+    );
+    for d in &diagnostics {
+        text.push_str(&format!(
+            "- {}:{}:{} [{}] {}\n",
+            d.file, d.line, d.column, d.severity, d.message
+        ));
+    }
+    if total > diagnostics.len() {
+        text.push_str(&format!(
+            "\n[...{} more diagnostics omitted]",
+            total - diagnostics.len()
+        ));
+    }
+    text
+}
+
+/// Registers `cmd` as a new `JobKind::Command` job, launches it via
+/// `run_command_async`, and wires the resulting resize channel back into
+/// the job record — the one place `/run`'s `/approve` and `/diagnostics`
+/// share so both stay tracked the same way.
+fn spawn_tracked_command(app: &mut App, cmd: String, sender: &mpsc::Sender<Event>) {
+    let (control_tx, control_rx) = mpsc::channel();
+    let job_id = app
+        .job_manager
+        .register(JobKind::Command, cmd.clone(), control_tx);
+    let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+    let (resize_tx, input_tx) =
+        run_command_async(cmd, sender.clone(), (cols, rows), job_id, control_rx);
+    app.job_manager.set_resize_tx(job_id, resize_tx);
+    app.job_manager.set_input_tx(job_id, input_tx);
+}
+
+/// Grace period after a cancelled job's SIGTERM before escalating to
+/// SIGKILL if its process group is still alive.
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Sends SIGTERM (Unix) or `taskkill` (Windows) to the process group led by
+/// `pid` — the slave side of the PTY makes the child its own session leader
+/// (`setsid`), so `pid` doubles as the process group id and signalling
+/// `-pid` reaches every descendant a shell pipeline left behind, not just
+/// the shell itself. Spawns a grace-period watcher that escalates to
+/// SIGKILL if the group is still alive after `STOP_GRACE_PERIOD`.
+fn terminate_process_group(pid: u32, sender: mpsc::Sender<Event>) {
+    if cfg!(target_os = "windows") {
+        let _ = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .status();
+        return;
+    }
+
+    let pgid = format!("-{}", pid);
+    let _ = Command::new("kill").args(["-TERM", &pgid]).status();
+
+    thread::spawn(move || {
+        thread::sleep(STOP_GRACE_PERIOD);
+        let still_alive = Command::new("kill")
+            .args(["-0", &pgid])
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if still_alive {
+            let _ = Command::new("kill").args(["-KILL", &pgid]).status();
+            let _ = sender.send(Event::AppMessage(TuiMessage::CommandStream(format!(
+                "\n[process group {} ignored SIGTERM, sent SIGKILL after {:.0}s]\n",
+                pid,
+                STOP_GRACE_PERIOD.as_secs_f64()
+            ))));
+        }
+    });
+}
+
+/// Runs `cmd` attached to a pseudo-terminal sized to `initial_size` (cols,
+/// rows) so TTY-aware tools (progress bars, colored output, interactive
+/// prompts) behave as they would in a real terminal. Reports its lifecycle
+/// to `job_id` via `TuiMessage::JobUpdate` and honors `Pause`/`Resume`/
+/// `Cancel` sent down `control_rx` (Pause/Resume map to `kill -STOP`/
+/// `-CONT` on Unix; Windows has no such primitive and only Cancel works
+/// there). Returns `(resize_tx, input_tx)`: the caller forwards later
+/// `Event::Resize` events through the first, and lines of keyboard input
+/// meant for the command's stdin (confirmation prompts, REPLs) through the
+/// second.
+fn run_command_async(
+    cmd: String,
+    sender: mpsc::Sender<Event>,
+    initial_size: (u16, u16),
+    job_id: u64,
+    control_rx: mpsc::Receiver<JobControl>,
+) -> (mpsc::Sender<(u16, u16)>, mpsc::Sender<String>) {
+    let (resize_tx, resize_rx) = mpsc::channel::<(u16, u16)>();
+    let (input_tx, input_rx) = mpsc::channel::<String>();
+
+    thread::spawn(move || {
+        let started_at = Instant::now();
+        let cwd = std::env::current_dir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+
+        let pty_system = native_pty_system();
+        let pair = match pty_system.openpty(PtySize {
+            cols: initial_size.0,
+            rows: initial_size.1,
+            pixel_width: 0,
+            pixel_height: 0,
+        }) {
+            Ok(pair) => pair,
+            Err(e) => {
+                let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                    id: job_id,
+                    state: JobState::Failed,
+                    pid: None,
+                    error: Some(e.to_string()),
+                }));
+                let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
+                    cmd,
+                    output: format!("Failed to allocate a pseudo-terminal: {}", e),
+                }));
+                return;
+            }
         };
 
+        let safety = crate::config::load().map(|c| c.safety).unwrap_or_default();
+        let profile = sandbox::build_profile(&safety.sandbox, Path::new(&cwd));
+        let (program, args) = sandbox::wrap_command(&profile, &safety.sandbox, Path::new(&cwd), &cmd);
+        let mut builder = CommandBuilder::new(&program);
+        builder.args(&args);
+        builder.env("TERM", "xterm-256color");
+
+        let mut child = match pair.slave.spawn_command(builder) {
+            Ok(c) => c,
+            Err(e) => {
+                let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                    id: job_id,
+                    state: JobState::Failed,
+                    pid: None,
+                    error: Some(e.to_string()),
+                }));
+                let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
+                    cmd,
+                    output: format!("Failed to run command: {}", e),
+                }));
+                return;
+            }
+        };
+        // Drop our end of the slave so the master's reader gets EOF once the
+        // child (and anything it exec'd) has exited.
+        drop(pair.slave);
+
+        let pid = child.process_id().unwrap_or(0);
         let _ = sender.send(Event::AppMessage(TuiMessage::CommandStarted {
-            pid: child.id(),
+            pid,
             cmd: cmd.clone(),
         }));
+        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+            id: job_id,
+            state: JobState::Running,
+            pid: Some(pid),
+            error: None,
+        }));
 
-        let Some(mut out) = child.stdout.take() else {
-            let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
-                cmd,
-                output: "Failed to capture stdout".to_string(),
-            }));
-            return;
-        };
-        let Some(mut err) = child.stderr.take() else {
-            let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
-                cmd,
-                output: "Failed to capture stderr".to_string(),
-            }));
-            return;
+        let mut reader = match pair.master.try_clone_reader() {
+            Ok(r) => r,
+            Err(e) => {
+                let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                    id: job_id,
+                    state: JobState::Failed,
+                    pid: Some(pid),
+                    error: Some(e.to_string()),
+                }));
+                let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
+                    cmd,
+                    output: format!("Failed to read from pseudo-terminal: {}", e),
+                }));
+                return;
+            }
         };
 
+        // Forwards lines typed in the TUI into the command's stdin via the
+        // PTY's master fd, for interactive tools that prompt mid-run. Exits
+        // on its own once `input_tx` is dropped (job finished) or the
+        // writer errors (PTY closed).
+        if let Ok(mut writer) = pair.master.take_writer() {
+            thread::spawn(move || {
+                while let Ok(line) = input_rx.recv() {
+                    if writer.write_all(line.as_bytes()).is_err() {
+                        break;
+                    }
+                    if writer.write_all(b"\n").is_err() || writer.flush().is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
         let sender_out = sender.clone();
-        let out_thread = thread::spawn(move || -> String {
-            let mut buf = [0_u8; 2048];
+        let read_thread = thread::spawn(move || -> String {
+            let mut buf = [0_u8; 4096];
             let mut acc = String::new();
             loop {
-                match out.read(&mut buf) {
+                match reader.read(&mut buf) {
                     Ok(0) => break,
                     Ok(n) => {
                         let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
@@ -819,29 +1980,98 @@ fn run_command_async(cmd: String, sender: mpsc::Sender<Event>) {
             acc
         });
 
-        let sender_err = sender.clone();
-        let err_thread = thread::spawn(move || -> String {
-            let mut buf = [0_u8; 1024];
-            let mut acc = String::new();
-            loop {
-                match err.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
-                        acc.push_str(&chunk);
-                        let _ =
-                            sender_err.send(Event::AppMessage(TuiMessage::CommandStream(chunk)));
+        // Apply resize/control requests and poll for exit until the child is
+        // done; the PTY combines stdout/stderr so a single reader thread
+        // suffices.
+        let master = pair.master;
+        let mut cancelled = false;
+        let mut timed_out = false;
+        loop {
+            if safety.sandbox.max_wall_clock_secs > 0
+                && !cancelled
+                && started_at.elapsed() >= Duration::from_secs(safety.sandbox.max_wall_clock_secs)
+            {
+                cancelled = true;
+                timed_out = true;
+                let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                    id: job_id,
+                    state: JobState::Cancelled,
+                    pid: Some(pid),
+                    error: Some(format!(
+                        "wall-clock limit of {}s exceeded",
+                        safety.sandbox.max_wall_clock_secs
+                    )),
+                }));
+                terminate_process_group(pid, sender.clone());
+            }
+
+            match control_rx.try_recv() {
+                Ok(JobControl::Cancel) => {
+                    cancelled = true;
+                    let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                        id: job_id,
+                        state: JobState::Cancelled,
+                        pid: Some(pid),
+                        error: None,
+                    }));
+                    terminate_process_group(pid, sender.clone());
+                }
+                Ok(JobControl::Pause) => {
+                    if cfg!(target_os = "windows") {
+                        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                            id: job_id,
+                            state: JobState::Running,
+                            pid: Some(pid),
+                            error: Some("Pause is not supported on Windows".to_string()),
+                        }));
+                    } else {
+                        let _ = Command::new("kill")
+                            .args(["-STOP", &format!("-{}", pid)])
+                            .status();
+                        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                            id: job_id,
+                            state: JobState::Idle,
+                            pid: Some(pid),
+                            error: None,
+                        }));
                     }
-                    Err(_) => break,
                 }
+                Ok(JobControl::Resume) => {
+                    if !cfg!(target_os = "windows") {
+                        let _ = Command::new("kill")
+                            .args(["-CONT", &format!("-{}", pid)])
+                            .status();
+                    }
+                    let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                        id: job_id,
+                        state: JobState::Running,
+                        pid: Some(pid),
+                        error: None,
+                    }));
+                }
+                Err(mpsc::TryRecvError::Empty) | Err(mpsc::TryRecvError::Disconnected) => {}
             }
-            acc
-        });
+
+            match resize_rx.recv_timeout(Duration::from_millis(200)) {
+                Ok((cols, rows)) => {
+                    let _ = master.resize(PtySize {
+                        cols,
+                        rows,
+                        pixel_width: 0,
+                        pixel_height: 0,
+                    });
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if matches!(child.try_wait(), Ok(Some(_))) {
+                        break;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
 
         let status = child.wait().ok();
-        let stdout_all = out_thread.join().unwrap_or_default();
-        let stderr_all = err_thread.join().unwrap_or_default();
-        let mut rendered = format!("{}{}", stdout_all, stderr_all);
+        let mut rendered = read_thread.join().unwrap_or_default();
         if rendered.trim().is_empty() {
             rendered = format!("(no output, exit={:?})", status);
         }
@@ -853,36 +2083,52 @@ fn run_command_async(cmd: String, sender: mpsc::Sender<Event>) {
             rendered.truncate(end);
             rendered.push_str("\n[...truncated]");
         }
+        if timed_out {
+            rendered.push_str(&format!(
+                "\n[killed: exceeded {}s wall-clock limit]",
+                safety.sandbox.max_wall_clock_secs
+            ));
+        }
+
+        if !cancelled {
+            let succeeded = status.map(|s| s.success()).unwrap_or(false);
+            let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+                id: job_id,
+                state: if succeeded {
+                    JobState::Done
+                } else {
+                    JobState::Failed
+                },
+                pid: Some(pid),
+                error: if succeeded {
+                    None
+                } else {
+                    Some(format!("exited with status: {:?}", status))
+                },
+            }));
+        }
+
+        let exit_code = status.as_ref().and_then(|s| s.code());
+        if let Err(e) = history::append(
+            cmd.clone(),
+            cwd,
+            exit_code,
+            started_at.elapsed().as_millis(),
+            jobs::now_unix(),
+        ) {
+            let _ = sender.send(Event::AppMessage(TuiMessage::CommandStream(format!(
+                "\n[failed to record command history: {}]\n",
+                e
+            ))));
+        }
 
         let _ = sender.send(Event::AppMessage(TuiMessage::CommandOutput {
             cmd,
             output: rendered,
         }));
     });
-}
 
-fn stop_running_command(pid: u32) -> Result<(), String> {
-    if cfg!(target_os = "windows") {
-        let status = Command::new("taskkill")
-            .args(["/PID", &pid.to_string(), "/T", "/F"])
-            .status()
-            .map_err(|e| format!("taskkill failed: {}", e))?;
-        if status.success() {
-            Ok(())
-        } else {
-            Err(format!("taskkill exited with status: {}", status))
-        }
-    } else {
-        let status = Command::new("kill")
-            .args(["-TERM", &pid.to_string()])
-            .status()
-            .map_err(|e| format!("kill failed: {}", e))?;
-        if status.success() {
-            Ok(())
-        } else {
-            Err(format!("kill exited with status: {}", status))
-        }
-    }
+    (resize_tx, input_tx)
 }
 
 fn is_blocked_command(cmd: &str) -> bool {
@@ -902,6 +2148,156 @@ fn is_blocked_command(cmd: &str) -> bool {
         .any(|token| lowered.contains(&token.to_lowercase()))
 }
 
+/// How long to wait for more filesystem events before re-indexing, so a
+/// save-all or a `git checkout` touching dozens of files triggers one
+/// rebuild instead of one per file.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(400);
+
+/// True if `path` is somewhere the indexer would actually look at, so the
+/// watcher doesn't wake up and re-index for `.git/`/`target/` churn.
+fn path_is_indexable(root: &Path, path: &Path) -> bool {
+    let Ok(rel) = path.strip_prefix(root) else {
+        return false;
+    };
+    !rel.components().any(|c| {
+        c.as_os_str()
+            .to_str()
+            .map(|s| workspace::is_ignored_dir(&s.to_lowercase()))
+            .unwrap_or(false)
+    })
+}
+
+/// Starts (if not already running) a background filesystem watcher over the
+/// current directory that debounces bursts of edits and triggers an
+/// incremental re-index, feeding the result back through the normal
+/// `TuiMessage::WorkspaceIndexReady` path so it never blocks input.
+fn start_workspace_watcher(app: &mut App, sender: mpsc::Sender<Event>) {
+    if app.fs_watcher.is_some() {
+        app.status_line = "Already watching workspace".to_string();
+        return;
+    }
+
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let (raw_tx, raw_rx) = mpsc::channel::<notify::Event>();
+
+    let mut watcher = match notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = raw_tx.send(event);
+        }
+    }) {
+        Ok(w) => w,
+        Err(e) => {
+            app.status_line = format!("Watch failed: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = watcher.watch(&cwd, notify::RecursiveMode::Recursive) {
+        app.status_line = format!("Watch failed: {}", e);
+        return;
+    }
+
+    let watch_root = cwd.clone();
+    thread::spawn(move || loop {
+        let first = match raw_rx.recv() {
+            Ok(event) => event,
+            Err(_) => break, // watcher dropped via `/watch off`
+        };
+        if !first.paths.iter().any(|p| path_is_indexable(&watch_root, p)) {
+            continue;
+        }
+
+        // Drain the rest of this burst so N rapid saves coalesce into one rebuild.
+        loop {
+            match raw_rx.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+
+        let cache_path = crate::config::config_dir().join("workspace_index.json");
+        let index =
+            workspace::WorkspaceIndex::build_incremental(&watch_root, &cache_path, 1600, 256 * 1024, workspace::DEFAULT_RETENTION_SECS);
+        if sender
+            .send(Event::AppMessage(TuiMessage::WorkspaceIndexReady {
+                index,
+                source: "watch".to_string(),
+                reason: Some("/watch on".to_string()),
+            }))
+            .is_err()
+        {
+            break; // app has exited
+        }
+    });
+
+    app.fs_watcher = Some(watcher);
+    app.watch_enabled = true;
+    app.status_line = "Watching workspace for changes".to_string();
+}
+
+/// How long to wait after a config write before re-parsing it, so editors
+/// that truncate-then-rewrite (or `niko settings set` writing a couple of
+/// fields) don't trigger a reload per intermediate write.
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Starts a background watcher over `config::config_path()` that debounces
+/// rapid successive writes and re-parses the file on change, forwarding the
+/// result through `Event::ConfigReload` so a long-running session can pick
+/// up `niko settings set ...` (or a hand edit) without restarting.
+fn start_config_watcher(sender: mpsc::Sender<Event>) -> Option<notify::RecommendedWatcher> {
+    let path = crate::config::config_path();
+    let (raw_tx, raw_rx) = mpsc::channel::<notify::Event>();
+
+    let mut watcher = match notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = raw_tx.send(event);
+        }
+    }) {
+        Ok(w) => w,
+        Err(_) => return None,
+    };
+
+    // Watch the parent directory rather than the file itself: many editors
+    // replace the file (unlink + rewrite) instead of writing in place,
+    // which some platforms stop notifying on otherwise.
+    let watch_target = path.parent().unwrap_or(&path).to_path_buf();
+    if watcher.watch(&watch_target, notify::RecursiveMode::NonRecursive).is_err() {
+        return None;
+    }
+
+    thread::spawn(move || loop {
+        let first = match raw_rx.recv() {
+            Ok(event) => event,
+            Err(_) => break, // watcher dropped on app exit
+        };
+        if !first.paths.iter().any(|p| p == &path) {
+            continue;
+        }
+
+        // Drain the rest of this burst so a save-then-rewrite coalesces into
+        // a single reload instead of one per intermediate write.
+        loop {
+            match raw_rx.recv_timeout(CONFIG_RELOAD_DEBOUNCE) {
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+
+        let result = crate::config::load().map_err(|e| e.to_string());
+        if sender.send(Event::ConfigReload(result)).is_err() {
+            break; // app has exited
+        }
+    });
+
+    Some(watcher)
+}
+
+fn stop_workspace_watcher(app: &mut App) {
+    app.fs_watcher = None;
+    app.watch_enabled = false;
+    app.status_line = "Stopped watching workspace".to_string();
+}
+
 fn build_workspace_index(app: &mut App, force_rebuild: bool) {
     if app.workspace_index.is_some() && !force_rebuild {
         return;
@@ -914,11 +2310,11 @@ fn build_workspace_index(app: &mut App, force_rebuild: bool) {
         let _ = fresh.save_cache(&cache_path);
         fresh
     } else {
-        workspace::WorkspaceIndex::build_incremental(&cwd, &cache_path, 1600, 256 * 1024)
+        workspace::WorkspaceIndex::build_incremental(&cwd, &cache_path, 1600, 256 * 1024, workspace::DEFAULT_RETENTION_SECS)
     };
     app.status_line = format!(
-        "Indexed {} files (skipped {})",
-        index.indexed_files, index.skipped_files
+        "Indexed {} files (skipped {}, reused {}, reindexed {})",
+        index.indexed_files, index.skipped_files, index.reused_files, index.reindexed_files
     );
 
     let now = std::time::SystemTime::now()
@@ -928,6 +2324,7 @@ fn build_workspace_index(app: &mut App, force_rebuild: bool) {
     let age_secs = now.saturating_sub(index.built_unix);
     app.history.push(HistoryEntry {
         is_user: false,
+        is_command_output: false,
         text: format!(
             "Workspace index built for `{}`\n- files indexed: {}\n- skipped: {}\n- age: {}s",
             index.root.display(),
@@ -949,11 +2346,21 @@ fn enrich_with_workspace_context(app: &mut App, input: &str) -> String {
         return input.to_string();
     };
 
-    let matches = index.retrieve(input, 3, 5000);
+    let matches = match app.rag_mode {
+        workspace::RagMode::Keyword => index.retrieve(input, 3, 5000),
+        workspace::RagMode::Semantic => index.retrieve_semantic(input, 3, 5000),
+        workspace::RagMode::Hybrid => index.retrieve_hybrid(input, 3, 5000),
+    };
     if matches.is_empty() {
         return input.to_string();
     }
 
+    if let Some(index) = app.workspace_index.as_mut() {
+        for (path, _) in &matches {
+            index.record_access(path);
+        }
+    }
+
     let mut enriched = input.to_string();
     enriched.push_str("\n\n[Retrieved workspace context]\n");
     for (path, snippet) in matches {
@@ -965,10 +2372,150 @@ fn enrich_with_workspace_context(app: &mut App, input: &str) -> String {
 fn push_error(app: &mut App, err: &str) {
     app.history.push(HistoryEntry {
         is_user: false,
+        is_command_output: false,
         text: format!("**Error:** {}", err),
     });
 }
 
+/// Marks the plan's current step done, saves it (clearing the saved plan
+/// file once every step is done), advances `planner_cursor`, and echoes
+/// "Step N: ..." to history — the bookkeeping `/next` needs after a step
+/// completes, whether that happened synchronously (most steps) or once a
+/// backgrounded validation run reports in via `TuiMessage::ValidationReady`.
+fn advance_plan_step(app: &mut App, step: &str) {
+    if let Some(saved) = app.current_plan.as_mut() {
+        if let Some(s) = saved.steps.get_mut(app.planner_cursor) {
+            s.status = plan::StepStatus::Done;
+        }
+        if let Err(e) = plan::save(saved) {
+            push_error(app, &format!("Step completed but not saved: {}", e));
+        } else if saved.first_pending() >= saved.steps.len() {
+            let _ = plan::clear();
+        }
+    }
+    app.planner_cursor += 1;
+    app.history.push(HistoryEntry {
+        is_user: false,
+        is_command_output: false,
+        text: format!("Step {}: {}", app.planner_cursor, step),
+    });
+}
+
+/// Exact text of `build_local_plan`'s validation step — matched against the
+/// current step in `/next` to decide whether to run `validation::run_checks`
+/// instead of just echoing the step text.
+const VALIDATION_STEP_LABEL: &str = "Run validation (build/tests/manual checks)";
+
+/// Runs `/next`'s validation battery (`validation::run_checks`) on a
+/// background thread instead of the main event-loop thread — format/build/
+/// test can easily take minutes, and running it inline froze the whole UI
+/// (no redraw, no input, no spinner) for that entire time, the same failure
+/// mode `/run`/`/diagnostics` avoid via `run_command_async`. Registered as a
+/// `JobKind::Validation` job so it shows up like any other tracked task;
+/// unlike PTY-backed commands there's no child process to cancel mid-run, so
+/// `JobControl` is simply drained and ignored. Reports back via
+/// `TuiMessage::ValidationReady`.
+fn spawn_validation(app: &mut App, sender: &mpsc::Sender<Event>) {
+    let (control_tx, control_rx) = mpsc::channel();
+    let job_id = app
+        .job_manager
+        .register(JobKind::Validation, "validation".to_string(), control_tx);
+    let sender = sender.clone();
+    app.is_loading = true;
+
+    thread::spawn(move || {
+        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+            id: job_id,
+            state: JobState::Running,
+            pid: None,
+            error: None,
+        }));
+
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let results = validation::run_checks(&cwd);
+
+        // No process to cancel, but drain any control message so the
+        // channel doesn't back up if the UI sent one before we finished.
+        let _ = control_rx.try_recv();
+
+        let all_passed = results.iter().all(|r| r.passed);
+        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+            id: job_id,
+            state: if all_passed { JobState::Done } else { JobState::Failed },
+            pid: None,
+            error: None,
+        }));
+        let _ = sender.send(Event::AppMessage(TuiMessage::ValidationReady { results }));
+    });
+}
+
+/// Runs a `/<name>` dispatch to `ext` on a background thread instead of the
+/// main event-loop thread — `extensions::run` shells out via
+/// `Command::output()` with no timeout, so running it inline froze the
+/// whole UI (no redraw, no input, no spinner) for as long as the extension
+/// took, the same failure mode the `chunk12-3` fix moved `/next` validation
+/// off of. Registered as a `JobKind::Command` job like `/run`/`/diagnostics`
+/// so it shows up the same way; unlike PTY-backed commands there's no child
+/// handle here to cancel mid-run, so `JobControl` is simply drained and
+/// ignored. Reports back via `TuiMessage::ExtensionReady`.
+fn spawn_extension(
+    app: &mut App,
+    ext: extensions::Extension,
+    args: String,
+    cwd: PathBuf,
+    task: Option<String>,
+    sender: &mpsc::Sender<Event>,
+) {
+    let (control_tx, control_rx) = mpsc::channel();
+    let job_id = app.job_manager.register(
+        JobKind::Command,
+        format!("niko-{}", ext.name),
+        control_tx,
+    );
+    let sender = sender.clone();
+    app.is_loading = true;
+
+    thread::spawn(move || {
+        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+            id: job_id,
+            state: JobState::Running,
+            pid: None,
+            error: None,
+        }));
+
+        let result = extensions::run(&ext, &args, &cwd, task.as_deref());
+
+        let _ = control_rx.try_recv();
+
+        let failed = !matches!(result, Ok((Some(0), _)));
+        let _ = sender.send(Event::AppMessage(TuiMessage::JobUpdate {
+            id: job_id,
+            state: if failed { JobState::Failed } else { JobState::Done },
+            pid: None,
+            error: None,
+        }));
+        let _ = sender.send(Event::AppMessage(TuiMessage::ExtensionReady {
+            name: ext.name,
+            result,
+        }));
+    });
+}
+
+/// Runs `/fetch <url>`'s download-and-strip (`fetch_url_as_text`) on a
+/// background thread instead of the main event-loop thread — up to
+/// `FETCH_TIMEOUT` (15s) of blocking `reqwest` call froze the whole UI (no
+/// redraw, no input, Esc did nothing since the event loop wasn't running to
+/// see it). Not tracked as a `JobManager` job since there's no process to
+/// list/cancel, matching how `spawn_warmup` reports a one-shot background
+/// result without registering one. Reports back via `TuiMessage::FetchReady`.
+fn spawn_fetch(url: String, sender: &mpsc::Sender<Event>) {
+    let sender = sender.clone();
+    thread::spawn(move || {
+        let result = fetch_url_as_text(&url);
+        let _ = sender.send(Event::AppMessage(TuiMessage::FetchReady { url, result }));
+    });
+}
+
 fn build_local_plan(task: &str) -> Vec<String> {
     let mut steps = vec![
         format!("Clarify scope and expected outcome for: {}", task),
@@ -996,35 +2543,318 @@ fn build_local_plan(task: &str) -> Vec<String> {
     steps
 }
 
-fn enrich_with_attached_files(input: &str) -> String {
+/// Replace the trailing `@<query>` trigger in the input buffer with the
+/// path chosen from the fuzzy file-picker overlay
+fn insert_file_picker_selection(app: &mut App, path: &str) {
+    let current = app.input_buffer.lines().join("\n");
+    let trigger = format!("@{}", app.file_picker_query);
+    let replaced = match current.rfind(&trigger) {
+        Some(pos) => {
+            let mut s = current.clone();
+            s.replace_range(pos..pos + trigger.len(), &format!("@{} ", path));
+            s
+        }
+        None => format!("{}@{} ", current, path),
+    };
+
+    app.input_buffer = tui_textarea::TextArea::default();
+    app.input_buffer.set_cursor_line_style(Style::default());
+    app.input_buffer.insert_str(&replaced);
+    app.file_picker_query.clear();
+}
+
+/// Pull any ` ```edit:<path> ... ``` ` fenced blocks out of an assistant
+/// response, staging them as `FileEdit`s for human review before they're
+/// written to disk. Returns the response text with those blocks removed
+/// and the list of edits found, in order.
+fn extract_proposed_edits(response: &str) -> (String, Vec<FileEdit>) {
+    let mut remaining = String::new();
+    let mut edits = Vec::new();
+    let mut lines = response.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if let Some(path) = trimmed
+            .strip_prefix("```edit:")
+            .or_else(|| trimmed.strip_prefix("```edit "))
+        {
+            let path = path.trim().to_string();
+            let mut contents = Vec::new();
+            for body_line in lines.by_ref() {
+                if body_line.trim_end() == "```" {
+                    break;
+                }
+                contents.push(body_line);
+            }
+            if !path.is_empty() {
+                edits.push(FileEdit {
+                    path,
+                    new_contents: contents.join("\n"),
+                });
+            }
+            continue;
+        }
+        remaining.push_str(line);
+        remaining.push('\n');
+    }
+
+    (remaining.trim_end().to_string(), edits)
+}
+
+/// Byte budget for a fetched page, applied before HTML stripping
+const FETCH_MAX_BYTES: u64 = 2 * 1024 * 1024;
+/// Byte cap on the stripped plain text (measured with `len()`)
+const FETCH_MAX_CHARS: usize = 20_000;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Downloads `url`, strips it to readable plain text, and caches the result
+/// under `config_dir()/fetch_cache` keyed by a hash of the URL so repeated
+/// `/fetch`es of the same page are instant and work offline.
+fn fetch_url_as_text(url: &str) -> Result<String, String> {
+    let cache_path = fetch_cache_path(url);
+    if let Ok(cached) = fs::read_to_string(&cache_path) {
+        return Ok(cached);
+    }
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+    let resp = client.get(url).send().map_err(|e| format!("request failed: {}", e))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("server returned {}", status));
+    }
+
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_lowercase();
+    if !content_type.is_empty() && !content_type.contains("text") && !content_type.contains("html") {
+        return Err(format!("unsupported content type: {}", content_type));
+    }
+
+    let body = resp
+        .bytes()
+        .map_err(|e| format!("failed to read response body: {}", e))?;
+    if body.len() as u64 > FETCH_MAX_BYTES {
+        return Err(format!(
+            "response larger than {} MB",
+            FETCH_MAX_BYTES / (1024 * 1024)
+        ));
+    }
+
+    let raw = String::from_utf8_lossy(&body).to_string();
+    let mut text = if content_type.contains("html") || raw.trim_start().starts_with('<') {
+        strip_html_to_text(&raw)
+    } else {
+        raw
+    };
+
+    if text.len() > FETCH_MAX_CHARS {
+        let mut end = FETCH_MAX_CHARS;
+        while end > 0 && !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[...truncated]");
+    }
+
+    if let Some(parent) = cache_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(&cache_path, &text);
+
+    Ok(text)
+}
+
+fn fetch_cache_path(url: &str) -> PathBuf {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    url.hash(&mut hasher);
+    crate::config::config_dir()
+        .join("fetch_cache")
+        .join(format!("{:016x}.txt", hasher.finish()))
+}
+
+/// Drops `<script>`/`<style>` blocks and any remaining tags, then collapses
+/// runs of whitespace so a downloaded page reads like plain text instead of
+/// markup soup. Not a full HTML parser — good enough for docs/RFC pages,
+/// which is all `/fetch` needs to handle.
+fn strip_html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len() / 2);
+    let mut chars = html.chars().peekable();
+    let mut in_tag = false;
+    let mut skip_depth: u32 = 0; // >0 while inside a <script>/<style> block
+
+    while let Some(c) = chars.next() {
+        if in_tag {
+            if c == '>' {
+                in_tag = false;
+            }
+            continue;
+        }
+        if c == '<' {
+            // Peek ahead far enough to recognize script/style open+close tags.
+            let mut probe = chars.clone();
+            let closing = probe.peek() == Some(&'/');
+            if closing {
+                probe.next();
+            }
+            let mut tag_name = String::new();
+            while let Some(&pc) = probe.peek() {
+                if pc.is_ascii_alphabetic() {
+                    tag_name.push(pc.to_ascii_lowercase());
+                    probe.next();
+                } else {
+                    break;
+                }
+            }
+
+            if tag_name == "script" || tag_name == "style" {
+                if closing {
+                    skip_depth = skip_depth.saturating_sub(1);
+                } else {
+                    skip_depth += 1;
+                }
+            }
+            in_tag = true;
+            continue;
+        }
+        if skip_depth > 0 {
+            continue;
+        }
+        out.push(c);
+    }
+
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Picks a fenced-code-block language tag for `name` (a file path or URL) by
+/// extension, falling back to `"text"` when there's no extension or
+/// `syntax_set` has no grammar for it.
+fn fence_lang_for(syntax_set: &SyntaxSet, name: &str) -> &str {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    if !ext.is_empty() && syntax_set.find_syntax_by_extension(ext).is_some() {
+        ext
+    } else {
+        "text"
+    }
+}
+
+/// Combined character budget shared across every `@`-attached file in one
+/// input line, so each additional attachment shrinks everyone's slice instead of
+/// each file independently claiming a full-size allowance.
+const TOTAL_ATTACHMENT_BUDGET_CHARS: usize = 24_000;
+/// Floor on a single file's slice of the shared budget, so a pile of tiny
+/// attachments doesn't starve the one that actually matters.
+const MIN_ATTACHMENT_BUDGET_CHARS: usize = 500;
+/// Fraction of a file's budget spent on the head window; the rest goes to
+/// the tail, which tends to hold the most recently touched code.
+const HEAD_BUDGET_RATIO: f64 = 0.6;
+
+/// Heuristic binary-file detector: a NUL byte within the first few KB is
+/// not something valid UTF-8 source ever contains, so treat it as a signal
+/// to skip rather than risk embedding garbage in the prompt.
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8_000).any(|&b| b == 0)
+}
+
+/// Keeps a head and tail window of `contents` within `budget` characters,
+/// joined by an explicit elision marker, instead of hard-truncating and
+/// losing everything past a cutoff — the top of a large file (imports,
+/// signatures) and its tail (often the most recently edited code) usually
+/// matter more than whatever's in the middle.
+fn window_to_budget(contents: &str, budget: usize) -> String {
+    if contents.len() <= budget {
+        return contents.to_string();
+    }
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let head_budget = (budget as f64 * HEAD_BUDGET_RATIO) as usize;
+    let tail_budget = budget.saturating_sub(head_budget);
+
+    let mut head_lines = Vec::new();
+    let mut head_len = 0;
+    for line in &lines {
+        if head_len + line.len() + 1 > head_budget {
+            break;
+        }
+        head_len += line.len() + 1;
+        head_lines.push(*line);
+    }
+
+    let mut tail_lines = Vec::new();
+    let mut tail_len = 0;
+    for line in lines.iter().rev() {
+        if head_lines.len() + tail_lines.len() >= lines.len() {
+            break;
+        }
+        if tail_len + line.len() + 1 > tail_budget {
+            break;
+        }
+        tail_len += line.len() + 1;
+        tail_lines.push(*line);
+    }
+    tail_lines.reverse();
+
+    let elided = lines.len() - head_lines.len() - tail_lines.len();
+    if elided == 0 {
+        return contents.to_string();
+    }
+
+    format!(
+        "{}\n[... {} lines elided ...]\n{}",
+        head_lines.join("\n"),
+        elided,
+        tail_lines.join("\n")
+    )
+}
+
+fn enrich_with_attached_files(input: &str, syntax_set: &SyntaxSet) -> String {
     const MAX_ATTACHMENTS: usize = 3;
     const MAX_FILE_BYTES: usize = 128 * 1024;
-    const MAX_FILE_CHARS: usize = 20_000;
 
     let mut enriched = input.to_string();
-    let mut attached = 0usize;
     let mut seen = std::collections::HashSet::new();
 
+    // Resolve the distinct, existing files referenced first, so the shared
+    // budget below is divided by how many are actually attached rather than
+    // a fixed worst-case count.
+    let mut candidates: Vec<(String, PathBuf)> = Vec::new();
     for token in input.split_whitespace() {
+        if candidates.len() >= MAX_ATTACHMENTS {
+            break;
+        }
         if !token.starts_with('@') {
             continue;
         }
-        if attached >= MAX_ATTACHMENTS {
-            break;
-        }
-
         let raw = token
             .trim_start_matches('@')
             .trim_matches(|c: char| c == '"' || c == '\'' || c == ',' || c == ';' || c == ')');
         if raw.is_empty() || !seen.insert(raw.to_string()) {
             continue;
         }
-
         let path = PathBuf::from(raw);
-        if !path.exists() || !path.is_file() {
-            continue;
+        if path.exists() && path.is_file() {
+            candidates.push((raw.to_string(), path));
         }
+    }
+
+    if candidates.is_empty() {
+        return enriched;
+    }
+
+    let per_file_budget = (TOTAL_ATTACHMENT_BUDGET_CHARS / candidates.len())
+        .max(MIN_ATTACHMENT_BUDGET_CHARS);
 
+    for (raw, path) in candidates {
         let Ok(meta) = fs::metadata(&path) else {
             continue;
         };
@@ -1037,7 +2867,18 @@ fn enrich_with_attached_files(input: &str) -> String {
             continue;
         }
 
-        let Ok(contents) = fs::read_to_string(&path) else {
+        let Ok(raw_bytes) = fs::read(&path) else {
+            enriched.push_str(&format!("\n\n[Attachment skipped: {} could not be read]", raw));
+            continue;
+        };
+        if looks_binary(&raw_bytes) {
+            enriched.push_str(&format!(
+                "\n\n[Attachment skipped: {} looks like a binary file]",
+                raw
+            ));
+            continue;
+        }
+        let Ok(contents) = String::from_utf8(raw_bytes) else {
             enriched.push_str(&format!(
                 "\n\n[Attachment skipped: {} is not UTF-8 text]",
                 raw
@@ -1045,21 +2886,12 @@ fn enrich_with_attached_files(input: &str) -> String {
             continue;
         };
 
-        let mut snippet = contents;
-        if snippet.len() > MAX_FILE_CHARS {
-            let mut end = MAX_FILE_CHARS;
-            while end > 0 && !snippet.is_char_boundary(end) {
-                end -= 1;
-            }
-            snippet.truncate(end);
-            snippet.push_str("\n[...truncated]");
-        }
-
+        let snippet = window_to_budget(&contents, per_file_budget);
+        let lang = fence_lang_for(syntax_set, &raw);
         enriched.push_str(&format!(
-            "\n\n[Attached file: {}]\n```text\n{}\n```",
-            raw, snippet
+            "\n\n[Attached file: {}]\n```{}\n{}\n```",
+            raw, lang, snippet
         ));
-        attached += 1;
     }
 
     enriched
@@ -0,0 +1,100 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub description: String,
+    pub status: StepStatus,
+}
+
+/// A `/plan <task>`'s steps, persisted to `plan.json` under `config_dir()`
+/// so an interrupted session can `/plan --resume` instead of replanning
+/// from scratch. `task_hash` guards resume against stale state: if the
+/// task text no longer hashes to what was saved, the user's intent (or the
+/// tree) has likely moved on, and the saved steps may no longer apply —
+/// analogous to refusing to act on an over-large cached estimate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    pub task: String,
+    pub task_hash: u64,
+    pub steps: Vec<PlanStep>,
+}
+
+pub fn hash_task(task: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    task.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn plan_path() -> PathBuf {
+    config::config_dir().join("plan.json")
+}
+
+impl Plan {
+    pub fn new(task: &str, descriptions: Vec<String>) -> Self {
+        Self {
+            task: task.to_string(),
+            task_hash: hash_task(task),
+            steps: descriptions
+                .into_iter()
+                .map(|description| PlanStep {
+                    description,
+                    status: StepStatus::Pending,
+                })
+                .collect(),
+        }
+    }
+
+    /// Index of the first step that hasn't completed, or `steps.len()` if
+    /// every step is already `Done` — where `/plan --resume` picks back up.
+    pub fn first_pending(&self) -> usize {
+        self.steps
+            .iter()
+            .position(|s| s.status != StepStatus::Done)
+            .unwrap_or(self.steps.len())
+    }
+}
+
+/// Loads the single saved plan, if any. A missing file reads as `None`
+/// rather than an error, same as `config::load`'s first-run path.
+pub fn load() -> Result<Option<Plan>, String> {
+    let path = plan_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw =
+        fs::read_to_string(&path).map_err(|e| format!("failed to read saved plan: {}", e))?;
+    serde_json::from_str(&raw)
+        .map(Some)
+        .map_err(|e| format!("failed to parse saved plan: {}", e))
+}
+
+pub fn save(plan: &Plan) -> Result<(), String> {
+    let dir = config::config_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("failed to create config directory: {}", e))?;
+    let raw = serde_json::to_string(plan).map_err(|e| format!("failed to encode plan: {}", e))?;
+    fs::write(plan_path(), raw).map_err(|e| format!("failed to write saved plan: {}", e))
+}
+
+/// Removes the saved plan, e.g. once `/next` has driven it to completion.
+pub fn clear() -> Result<(), String> {
+    let path = plan_path();
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("failed to remove saved plan: {}", e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::SandboxConfig;
+
+/// How `run_command_async` confined (or didn't confine) a command, computed
+/// once before spawn so it can both build the argv and be shown to the user
+/// in the `/run` confirmation text before they `/approve` it.
+#[derive(Debug, Clone)]
+pub struct SandboxProfile {
+    pub confined: bool,
+    pub description: String,
+}
+
+/// `bwrap` (bubblewrap) is the lightweight, widely-packaged sandboxing tool
+/// this targets — unprivileged user namespaces, unlike hand-rolling
+/// `unshare`/seccomp directly.
+fn bwrap_available() -> bool {
+    Command::new("bwrap")
+        .arg("--version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Decides whether `cmd` can actually be sandboxed given `cfg` and the
+/// current platform, and builds the human-readable description shown
+/// alongside the pending command. Confinement is opt-in via
+/// `cfg.enabled` and only available on Linux with `bwrap` on `PATH`;
+/// everywhere else this degrades to the plain denylist plus the cwd/
+/// wall-clock constraints `run_command_async` always applies.
+pub fn build_profile(cfg: &SandboxConfig, cwd: &Path) -> SandboxProfile {
+    if !cfg.enabled {
+        return SandboxProfile {
+            confined: false,
+            description: format!(
+                "unsandboxed (denylist + cwd={} + {}s wall-clock limit only)",
+                cwd.display(),
+                cfg.max_wall_clock_secs
+            ),
+        };
+    }
+
+    if !cfg!(target_os = "linux") || !bwrap_available() {
+        return SandboxProfile {
+            confined: false,
+            description: "unsandboxed (sandbox.enabled is set, but bwrap/Linux namespaces aren't available here — falling back to denylist + cwd + wall-clock limit only)".to_string(),
+        };
+    }
+
+    let network = if cfg.allow_network {
+        "network allowed"
+    } else {
+        "no network"
+    };
+    let extra = if cfg.extra_read_write.is_empty() {
+        String::new()
+    } else {
+        format!(", read-write also: {}", cfg.extra_read_write.join(", "))
+    };
+    SandboxProfile {
+        confined: true,
+        description: format!(
+            "sandboxed (bwrap): read-only /, read-write {}{}, {}",
+            cwd.display(),
+            extra,
+            network
+        ),
+    }
+}
+
+/// Builds the `(program, args)` niko should actually spawn for `cmd` under
+/// `profile`. Unconfined profiles (including every Windows run — bubblewrap
+/// is Linux-only) fall back to the plain shell invocation used before
+/// sandboxing existed.
+pub fn wrap_command(
+    profile: &SandboxProfile,
+    cfg: &SandboxConfig,
+    cwd: &Path,
+    cmd: &str,
+) -> (String, Vec<String>) {
+    if cfg!(target_os = "windows") || !profile.confined {
+        let shell = if cfg!(target_os = "windows") {
+            "cmd"
+        } else {
+            "sh"
+        };
+        let flag = if cfg!(target_os = "windows") {
+            "/C"
+        } else {
+            "-lc"
+        };
+        return (shell.to_string(), vec![flag.to_string(), cmd.to_string()]);
+    }
+
+    let cwd_str = cwd.display().to_string();
+    let mut args = vec![
+        "--ro-bind".to_string(),
+        "/".to_string(),
+        "/".to_string(),
+        "--dev".to_string(),
+        "/dev".to_string(),
+        "--proc".to_string(),
+        "/proc".to_string(),
+        "--tmpfs".to_string(),
+        "/tmp".to_string(),
+        "--bind".to_string(),
+        cwd_str.clone(),
+        cwd_str.clone(),
+        "--chdir".to_string(),
+        cwd_str,
+        "--die-with-parent".to_string(),
+    ];
+    if !cfg.allow_network {
+        args.push("--unshare-net".to_string());
+    }
+    for path in &cfg.extra_read_write {
+        args.push("--bind".to_string());
+        args.push(path.clone());
+        args.push(path.clone());
+    }
+    args.push("--".to_string());
+    args.push("sh".to_string());
+    args.push("-lc".to_string());
+    args.push(cmd.to_string());
+
+    ("bwrap".to_string(), args)
+}
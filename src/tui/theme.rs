@@ -0,0 +1,277 @@
+//! User-configurable color theme for the TUI, loaded from `~/.niko/theme.toml`
+//! with a built-in "dark" default and a couple of bundled presets.
+
+use std::fs;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+fn default_accent() -> String {
+    "cyan".into()
+}
+fn default_border() -> String {
+    "darkgray".into()
+}
+fn default_overlay_border() -> String {
+    "lightblue".into()
+}
+fn default_user_badge_bg() -> String {
+    "cyan".into()
+}
+fn default_assistant_badge_bg() -> String {
+    "green".into()
+}
+fn default_code_color() -> String {
+    "cyan".into()
+}
+fn default_heading_color() -> String {
+    "magenta".into()
+}
+fn default_bullet_color() -> String {
+    "yellow".into()
+}
+fn default_dim() -> String {
+    "darkgray".into()
+}
+fn default_match_current_bg() -> String {
+    "yellow".into()
+}
+fn default_budget_ok() -> String {
+    "green".into()
+}
+fn default_budget_warn() -> String {
+    "yellow".into()
+}
+fn default_budget_crit() -> String {
+    "red".into()
+}
+fn default_diff_removed_bg() -> String {
+    "#400000".into()
+}
+fn default_diff_added_bg() -> String {
+    "#003000".into()
+}
+fn default_diff_fg() -> String {
+    "white".into()
+}
+fn default_syntax_theme() -> String {
+    "base16-ocean.dark".into()
+}
+
+/// Color palette for the TUI, with every field independently defaultable so
+/// a `theme.toml` only needs to override the fields it cares about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Section titles, hunk headers, and other "this is important" accents
+    #[serde(default = "default_accent")]
+    pub accent: String,
+    /// Ordinary panel borders (conversation pane, sidebar)
+    #[serde(default = "default_border")]
+    pub border: String,
+    /// Popup/overlay borders (help, file picker, diff review)
+    #[serde(default = "default_overlay_border")]
+    pub overlay_border: String,
+    #[serde(default = "default_user_badge_bg")]
+    pub user_badge_bg: String,
+    #[serde(default = "default_assistant_badge_bg")]
+    pub assistant_badge_bg: String,
+    /// Fallback color for fenced code when syntax highlighting isn't active
+    #[serde(default = "default_code_color")]
+    pub code_color: String,
+    #[serde(default = "default_heading_color")]
+    pub heading_color: String,
+    #[serde(default = "default_bullet_color")]
+    pub bullet_color: String,
+    /// Secondary/help text
+    #[serde(default = "default_dim")]
+    pub dim: String,
+    #[serde(default = "default_match_current_bg")]
+    pub match_current_bg: String,
+    #[serde(default = "default_budget_ok")]
+    pub budget_ok: String,
+    #[serde(default = "default_budget_warn")]
+    pub budget_warn: String,
+    #[serde(default = "default_budget_crit")]
+    pub budget_crit: String,
+    #[serde(default = "default_diff_removed_bg")]
+    pub diff_removed_bg: String,
+    #[serde(default = "default_diff_added_bg")]
+    pub diff_added_bg: String,
+    #[serde(default = "default_diff_fg")]
+    pub diff_fg: String,
+    /// Name of a bundled `syntect` theme (see `syntect::highlighting::ThemeSet`)
+    /// used to highlight fenced code blocks
+    #[serde(default = "default_syntax_theme")]
+    pub syntax_theme: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            accent: default_accent(),
+            border: default_border(),
+            overlay_border: default_overlay_border(),
+            user_badge_bg: default_user_badge_bg(),
+            assistant_badge_bg: default_assistant_badge_bg(),
+            code_color: default_code_color(),
+            heading_color: default_heading_color(),
+            bullet_color: default_bullet_color(),
+            dim: default_dim(),
+            match_current_bg: default_match_current_bg(),
+            budget_ok: default_budget_ok(),
+            budget_warn: default_budget_warn(),
+            budget_crit: default_budget_crit(),
+            diff_removed_bg: default_diff_removed_bg(),
+            diff_added_bg: default_diff_added_bg(),
+            diff_fg: default_diff_fg(),
+            syntax_theme: default_syntax_theme(),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            accent: "blue".into(),
+            border: "gray".into(),
+            overlay_border: "blue".into(),
+            user_badge_bg: "blue".into(),
+            assistant_badge_bg: "green".into(),
+            code_color: "blue".into(),
+            heading_color: "magenta".into(),
+            bullet_color: "gray".into(),
+            dim: "gray".into(),
+            match_current_bg: "yellow".into(),
+            budget_ok: "green".into(),
+            budget_warn: "yellow".into(),
+            budget_crit: "red".into(),
+            diff_removed_bg: "#ffd6d6".into(),
+            diff_added_bg: "#d6ffd6".into(),
+            diff_fg: "black".into(),
+            syntax_theme: "base16-ocean.light".into(),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            accent: "yellow".into(),
+            border: "white".into(),
+            overlay_border: "white".into(),
+            user_badge_bg: "blue".into(),
+            assistant_badge_bg: "magenta".into(),
+            code_color: "yellow".into(),
+            heading_color: "yellow".into(),
+            bullet_color: "white".into(),
+            dim: "white".into(),
+            match_current_bg: "white".into(),
+            budget_ok: "green".into(),
+            budget_warn: "yellow".into(),
+            budget_crit: "red".into(),
+            diff_removed_bg: "#800000".into(),
+            diff_added_bg: "#004000".into(),
+            diff_fg: "white".into(),
+            syntax_theme: "Solarized (dark)".into(),
+        }
+    }
+
+    /// Look up one of the bundled presets by name
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" | "high_contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Load the theme from `~/.niko/theme.toml`. The file may set a bundled
+    /// `preset = "light"` and/or override individual fields; any field it
+    /// doesn't set keeps the named preset's value, or the "dark" default's
+    /// when no preset is named. Missing or malformed
+    /// files fall back to the default theme entirely.
+    pub fn load() -> Self {
+        let path = config::config_dir().join("theme.toml");
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        #[derive(Deserialize, Default)]
+        struct RawTheme {
+            preset: Option<String>,
+            #[serde(flatten)]
+            overrides: toml::Value,
+        }
+
+        let Ok(raw) = toml::from_str::<RawTheme>(&content) else {
+            return Self::default();
+        };
+
+        let base = raw
+            .preset
+            .as_deref()
+            .and_then(Theme::by_name)
+            .unwrap_or_default();
+
+        // Re-serialize the base preset, merge the file's overrides on top,
+        // then deserialize back into a `Theme` so unset fields keep the
+        // preset's values and set fields take the override.
+        let Ok(mut merged) = toml::Value::try_from(&base) else {
+            return base;
+        };
+        if let (toml::Value::Table(merged_table), toml::Value::Table(override_table)) =
+            (&mut merged, &raw.overrides)
+        {
+            for (k, v) in override_table {
+                if k != "preset" {
+                    merged_table.insert(k.clone(), v.clone());
+                }
+            }
+        }
+
+        merged.try_into().unwrap_or(base)
+    }
+}
+
+/// Parse a theme color string: a named ratatui color (case-insensitive) or
+/// a `#RRGGBB` hex triplet. Unrecognized values fall back to the terminal's
+/// default foreground/background.
+pub fn parse_color(s: &str) -> Color {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                let r = ((rgb >> 16) & 0xff) as u8;
+                let g = ((rgb >> 8) & 0xff) as u8;
+                let b = (rgb & 0xff) as u8;
+                return Color::Rgb(r, g, b);
+            }
+        }
+        return Color::Reset;
+    }
+
+    match s.to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "dark_gray" | "dark-gray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => Color::Reset,
+    }
+}
@@ -1,4 +1,9 @@
-use super::app::{App, Focus, Route};
+use std::collections::HashMap;
+
+use super::app::{model_context_window, model_encoding, App, Focus, Route};
+use super::jobs::JobState;
+use super::theme::parse_color;
+use super::workspace;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -6,6 +11,44 @@ use ratatui::{
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
+use syntect::highlighting::{HighlightState, Highlighter, Theme};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Fallback theme name when `app.theme.syntax_theme` isn't one of the bundled themes
+const FALLBACK_SYNTAX_THEME: &str = "base16-ocean.dark";
+
+fn resolve_theme(app: &App) -> &Theme {
+    app.theme_set
+        .themes
+        .get(app.theme.syntax_theme.as_str())
+        .or_else(|| app.theme_set.themes.get(FALLBACK_SYNTAX_THEME))
+        .unwrap_or_else(|| {
+            app.theme_set
+                .themes
+                .values()
+                .next()
+                .expect("ThemeSet::load_defaults() always bundles at least one theme")
+        })
+}
+
+/// Parse/highlight state for a fenced code block, carried line-to-line
+/// instead of a bare `in_code_block: bool`.
+struct CodeBlockState {
+    active: bool,
+    parse_state: Option<ParseState>,
+    highlight_state: Option<HighlightState>,
+}
+
+impl CodeBlockState {
+    fn new() -> Self {
+        Self {
+            active: false,
+            parse_state: None,
+            highlight_state: None,
+        }
+    }
+}
 
 pub fn draw(f: &mut Frame, app: &mut App) {
     let input_lines = app.input_buffer.lines().len() as u16;
@@ -46,10 +89,19 @@ pub fn draw(f: &mut Frame, app: &mut App) {
     if app.show_help {
         draw_help_overlay(f, app);
     }
+
+    if app.file_picker_open {
+        draw_file_picker_overlay(f, app);
+    }
+
+    if app.diff_overlay_open {
+        draw_diff_overlay(f, app);
+    }
 }
 
 fn draw_header(f: &mut Frame, app: &App, area: Rect) {
-    let pulse = [Color::Cyan, Color::LightBlue, Color::Blue, Color::Magenta];
+    let accent = parse_color(&app.theme.accent);
+    let pulse = [accent, Color::LightBlue, Color::Blue, Color::Magenta];
     let c = pulse[(app.spinner_state as usize / 2) % pulse.len()];
 
     let status = match app.route {
@@ -68,33 +120,102 @@ fn draw_header(f: &mut Frame, app: &App, area: Rect) {
         ),
         Span::styled(
             format!(" v{}  ", env!("CARGO_PKG_VERSION")),
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(parse_color(&app.theme.dim)),
         ),
         Span::styled(
             format!("{}  ", status),
             Style::default()
-                .fg(Color::Yellow)
+                .fg(accent)
                 .add_modifier(Modifier::BOLD),
         ),
     ]);
 
+    let git_segment = match &app.git_status {
+        Some(status) => status.header_segment(),
+        None => "no repo".to_string(),
+    };
+
     let line2 = Line::from(vec![
         Span::styled(" ", Style::default()),
         Span::styled(&app.status_line, Style::default().fg(Color::Gray)),
+        Span::styled("  ", Style::default()),
+        Span::styled(git_segment, Style::default().fg(parse_color(&app.theme.dim))),
     ]);
 
     f.render_widget(Paragraph::new(Text::from(vec![line1, line2])), area);
 }
 
+/// Count tokens across history, the live input buffer, and any `@file`
+/// attachments it references, using the BPE matching the active model.
+fn count_tokens(app: &App) -> (usize, usize) {
+    let model = crate::config::active_provider()
+        .map(|(_, pcfg)| pcfg.model)
+        .unwrap_or_default();
+    let context_window = model_context_window(&model);
+    let encoding = model_encoding(&model);
+
+    let bpe = if encoding == "o200k_base" {
+        tiktoken_rs::o200k_base()
+    } else {
+        tiktoken_rs::cl100k_base()
+    };
+
+    let mut text = String::new();
+    for entry in &app.history {
+        text.push_str(&entry.text);
+        text.push('\n');
+    }
+
+    let input_text = app.input_buffer.lines().join("\n");
+    text.push_str(&super::enrich_with_attached_files(&input_text, &app.syntax_set));
+
+    let tokens_used = match bpe {
+        Ok(enc) => enc.encode_with_special_tokens(&text).len(),
+        Err(_) => text.split_whitespace().count(),
+    };
+
+    (tokens_used, context_window)
+}
+
+/// A small inline bar gauge that shifts color past 75% and 90% of budget
+fn render_token_gauge(used: usize, total: usize, theme: &super::theme::Theme) -> Line<'static> {
+    const WIDTH: usize = 20;
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64
+    };
+    let filled = ((ratio.min(1.0)) * WIDTH as f64).round() as usize;
+    let color = if ratio >= 0.9 {
+        parse_color(&theme.budget_crit)
+    } else if ratio >= 0.75 {
+        parse_color(&theme.budget_warn)
+    } else {
+        parse_color(&theme.budget_ok)
+    };
+    let bar = "█".repeat(filled) + &"░".repeat(WIDTH.saturating_sub(filled));
+    Line::from(vec![Span::styled(format!(" {}", bar), Style::default().fg(color))])
+}
+
 fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
     let last_ms = app.last_latency_ms.unwrap_or(0);
     let rag = if app.rag_enabled { "on" } else { "off" };
+    let rag_mode = match app.rag_mode {
+        workspace::RagMode::Keyword => "keyword",
+        workspace::RagMode::Semantic => "semantic",
+        workspace::RagMode::Hybrid => "hybrid",
+    };
     let pending = app.pending_command.as_ref().map(|_| "yes").unwrap_or("no");
-    let running = if app.command_running { "yes" } else { "no" };
-    let pid = app
-        .command_pid
+    let current_job = app.job_manager.last_command();
+    let running = current_job
+        .map(|j| matches!(j.state, JobState::Running | JobState::Idle))
+        .unwrap_or(false);
+    let running = if running { "yes" } else { "no" };
+    let pid = current_job
+        .and_then(|j| j.pid)
         .map(|p| p.to_string())
         .unwrap_or_else(|| "-".to_string());
+    let jobs_tracked = app.job_manager.list().len();
     let planner = if app.planner_steps.is_empty() {
         "none".to_string()
     } else {
@@ -105,29 +226,56 @@ fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
         .as_ref()
         .map(|i| i.indexed_files.to_string())
         .unwrap_or_else(|| "0".to_string());
+    let (tokens_used, context_window) = count_tokens(app);
 
+    let accent = parse_color(&app.theme.accent);
     let sidebar = vec![
         Line::from(vec![Span::styled(
             "Session",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(accent)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from(format!("messages: {}", app.history.len())),
         Line::from(format!("responses: {}", app.total_responses)),
         Line::from(format!("output chars: {}", app.total_output_chars)),
         Line::from(format!("last latency: {} ms", last_ms)),
-        Line::from(format!("rag: {}", rag)),
+        Line::from(format!("rag: {} ({})", rag, rag_mode)),
         Line::from(format!("pending cmd: {}", pending)),
         Line::from(format!("command running: {}", running)),
         Line::from(format!("command pid: {}", pid)),
+        Line::from(format!("jobs tracked: {}", jobs_tracked)),
         Line::from(format!("index files: {}", index_files)),
+        Line::from(format!(
+            "watch: {}",
+            if app.watch_enabled { "on" } else { "off" }
+        )),
         Line::from(format!("plan progress: {}", planner)),
+        Line::from(format!("tokens: {} / {}", tokens_used, context_window)),
+        render_token_gauge(tokens_used, context_window, &app.theme),
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            "Git",
+            Style::default()
+                .fg(accent)
+                .add_modifier(Modifier::BOLD),
+        )]),
+        match &app.git_status {
+            Some(status) => Line::from(format!("branch: {}", status.branch)),
+            None => Line::from("branch: (no repo)"),
+        },
+        match &app.git_status {
+            Some(status) => Line::from(format!(
+                "dirty files: {}",
+                status.staged + status.modified + status.untracked
+            )),
+            None => Line::from("dirty files: -"),
+        },
         Line::from(""),
         Line::from(vec![Span::styled(
             "Navigation",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(accent)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from("Tab switch focus"),
@@ -143,7 +291,7 @@ fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
                 Block::default()
                     .title("Panel")
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::DarkGray)),
+                    .border_style(Style::default().fg(parse_color(&app.theme.border))),
             )
             .wrap(Wrap { trim: false }),
         area,
@@ -151,6 +299,30 @@ fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
+    if app.search_active {
+        let counter = if app.search_matches.is_empty() {
+            "no matches".to_string()
+        } else {
+            format!("match {}/{}", app.search_current + 1, app.search_matches.len())
+        };
+        let footer = Line::from(vec![
+            Span::styled(
+                " FIND ",
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(parse_color(&app.theme.match_current_bg))
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!(" /{}", app.search_query), Style::default().fg(Color::White)),
+            Span::styled(
+                format!("  {}  (Enter/Shift+Enter next/prev, Esc cancel)", counter),
+                Style::default().fg(parse_color(&app.theme.dim)),
+            ),
+        ]);
+        f.render_widget(Paragraph::new(footer).alignment(Alignment::Left), area);
+        return;
+    }
+
     let mode = if app.focus == Focus::Input {
         Span::styled(
             " INSERT ",
@@ -169,16 +341,18 @@ fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
         )
     };
 
-    let hints = " /help /search /open /plan /next /run /approve /stop /index /rag on|off ";
+    let hints =
+        " /help /search /open /plan /next /run /approve /deny /diff /stop /index /rag on|off  Ctrl+F find ";
     let footer = Line::from(vec![
         mode,
-        Span::styled(hints, Style::default().fg(Color::DarkGray)),
+        Span::styled(hints, Style::default().fg(parse_color(&app.theme.dim))),
     ]);
     f.render_widget(Paragraph::new(footer).alignment(Alignment::Left), area);
 }
 
-fn draw_output_history(f: &mut Frame, app: &App, area: Rect) {
+fn draw_output_history(f: &mut Frame, app: &mut App, area: Rect) {
     let mut history_text = Text::default();
+    let theme = resolve_theme(app);
 
     if app.history.is_empty()
         && app.result_buffer.is_empty()
@@ -191,10 +365,22 @@ fn draw_output_history(f: &mut Frame, app: &App, area: Rect) {
         )]));
     }
 
+    if !app.result_buffer.is_empty() {
+        for line in app.result_buffer.lines() {
+            history_text.lines.push(Line::from(line.to_string()));
+        }
+        history_text.lines.push(Line::from(""));
+    }
+
     for entry in &app.history {
         if entry.is_user {
             history_text.lines.push(Line::from(vec![
-                Span::styled(" You ", Style::default().fg(Color::Black).bg(Color::Cyan)),
+                Span::styled(
+                    " You ",
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(parse_color(&app.theme.user_badge_bg)),
+                ),
                 Span::styled(" ", Style::default()),
             ]));
             for line in entry.text.lines() {
@@ -203,13 +389,23 @@ fn draw_output_history(f: &mut Frame, app: &App, area: Rect) {
         } else {
             history_text.lines.push(Line::from(vec![Span::styled(
                 " Niko ",
-                Style::default().fg(Color::Black).bg(Color::Green),
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(parse_color(&app.theme.assistant_badge_bg)),
             )]));
-            let mut in_code = false;
-            for line in entry.text.lines() {
-                history_text
-                    .lines
-                    .push(parse_markdown_line(line, &mut in_code));
+            if entry.is_command_output {
+                history_text.lines.extend(parse_ansi_lines(&entry.text));
+            } else {
+                let mut code_state = CodeBlockState::new();
+                for line in entry.text.lines() {
+                    history_text.lines.push(parse_markdown_line(
+                        line,
+                        &mut code_state,
+                        &app.syntax_set,
+                        theme,
+                        &app.theme,
+                    ));
+                }
             }
         }
         history_text.lines.push(Line::from(""));
@@ -220,11 +416,15 @@ fn draw_output_history(f: &mut Frame, app: &App, area: Rect) {
         let spinner_char = dots[(app.spinner_state as usize / 2) % dots.len()];
 
         if !app.streaming_buffer.is_empty() {
-            let mut in_code = false;
+            let mut code_state = CodeBlockState::new();
             for line in app.streaming_buffer.lines() {
-                history_text
-                    .lines
-                    .push(parse_markdown_line(line, &mut in_code));
+                history_text.lines.push(parse_markdown_line(
+                    line,
+                    &mut code_state,
+                    &app.syntax_set,
+                    theme,
+                    &app.theme,
+                ));
             }
         }
         history_text.lines.push(Line::from(vec![Span::styled(
@@ -233,13 +433,93 @@ fn draw_output_history(f: &mut Frame, app: &App, area: Rect) {
         )]));
     }
 
-    let mut total_visual_lines = 0;
+    if app.search_active {
+        // Scan the rendered lines for `search_query`, advance `search_current`
+        // per any pending jump, then paint matches directly into `history_text`
+        // (current match in yellow, other matches inverted).
+        let query_lower = app.search_query.to_lowercase();
+        let mut matches = Vec::new();
+        if !query_lower.is_empty() {
+            for (line_no, line) in history_text.lines.iter().enumerate() {
+                let plain: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+                let plain_lower = plain.to_lowercase();
+                let mut search_from = 0;
+                while let Some(pos) = plain_lower[search_from..].find(&query_lower) {
+                    let start = search_from + pos;
+                    let end = start + query_lower.len();
+                    matches.push((line_no, start, end));
+                    search_from = end.max(start + 1);
+                    if search_from >= plain_lower.len() {
+                        break;
+                    }
+                }
+            }
+        }
+        app.search_matches = matches;
+
+        if let Some(dir) = app.search_pending_jump.take() {
+            if !app.search_matches.is_empty() {
+                let len = app.search_matches.len();
+                app.search_current = match dir {
+                    1 => (app.search_current + 1) % len,
+                    -1 => (app.search_current + len - 1) % len,
+                    _ => 0,
+                };
+            } else {
+                app.search_current = 0;
+            }
+        }
+
+        let mut by_line: HashMap<usize, Vec<(usize, usize, usize)>> = HashMap::new();
+        for (idx, &(line_no, start, end)) in app.search_matches.iter().enumerate() {
+            by_line.entry(line_no).or_default().push((idx, start, end));
+        }
+
+        for (line_no, ranges) in by_line {
+            let line = &mut history_text.lines[line_no];
+            let plain: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+            let mut spans = Vec::new();
+            let mut cursor = 0;
+            for (idx, start, end) in ranges {
+                if start > cursor {
+                    spans.push(Span::raw(plain[cursor..start].to_string()));
+                }
+                let style = if idx == app.search_current {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(parse_color(&app.theme.match_current_bg))
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                };
+                spans.push(Span::styled(plain[start..end].to_string(), style));
+                cursor = end;
+            }
+            if cursor < plain.len() {
+                spans.push(Span::raw(plain[cursor..].to_string()));
+            }
+            *line = Line::from(spans);
+        }
+    }
+
+    let mut visual_line_counts: Vec<u16> = Vec::with_capacity(history_text.lines.len());
+    let mut total_visual_lines: u16 = 0;
     for line in &history_text.lines {
         let w = line.width() as u16;
-        total_visual_lines += 1 + w.saturating_sub(1) / area.width.max(1);
+        let vlines = 1 + w.saturating_sub(1) / area.width.max(1);
+        visual_line_counts.push(vlines);
+        total_visual_lines += vlines;
     }
     let max_scroll = total_visual_lines.saturating_sub(area.height.saturating_sub(2));
 
+    if app.search_active {
+        if let Some(&(line_no, _, _)) = app.search_matches.get(app.search_current) {
+            let offset: u16 = visual_line_counts[..line_no].iter().sum();
+            let half = area.height.saturating_sub(2) / 2;
+            app.result_scroll = offset.saturating_sub(half).min(max_scroll);
+        }
+    }
+
     let current_scroll = if app.is_loading {
         max_scroll
     } else {
@@ -254,34 +534,207 @@ fn draw_output_history(f: &mut Frame, app: &App, area: Rect) {
                 Block::default()
                     .title("Conversation")
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::DarkGray)),
+                    .border_style(Style::default().fg(parse_color(&app.theme.border))),
             ),
         area,
     );
 }
 
-fn parse_markdown_line<'a>(line: &'a str, in_code_block: &mut bool) -> Line<'a> {
+/// Parse raw command output containing ANSI SGR (CSI `ESC [ ... m`) escapes
+/// into styled lines, one per newline. Unsupported/unknown CSI sequences are
+/// consumed and discarded so they don't corrupt the display.
+fn parse_ansi_lines(text: &str) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut style = Style::default();
+    let mut buf = String::new();
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                let mut params = String::new();
+                let mut final_byte = None;
+                for pc in chars.by_ref() {
+                    if pc.is_ascii_alphabetic() {
+                        final_byte = Some(pc);
+                        break;
+                    }
+                    params.push(pc);
+                }
+                if final_byte == Some('m') {
+                    if !buf.is_empty() {
+                        spans.push(Span::styled(std::mem::take(&mut buf), style));
+                    }
+                    style = apply_sgr_params(style, &params);
+                }
+                // Any other final byte (cursor movement, clear-line, etc.)
+                // is simply discarded — we only render colors/attributes.
+            }
+            continue;
+        }
+
+        if c == '\n' {
+            if !buf.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut buf), style));
+            }
+            lines.push(Line::from(std::mem::take(&mut spans)));
+            continue;
+        }
+
+        buf.push(c);
+    }
+
+    if !buf.is_empty() {
+        spans.push(Span::styled(buf, style));
+    }
+    if !spans.is_empty() {
+        lines.push(Line::from(spans));
+    }
+
+    lines
+}
+
+/// Apply a `;`-separated SGR parameter list to a running `Style`
+fn apply_sgr_params(mut style: Style, params: &str) -> Style {
+    let codes: Vec<i32> = params.split(';').filter_map(|p| p.parse().ok()).collect();
+    let codes: Vec<i32> = if codes.is_empty() { vec![0] } else { codes };
+
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => style = Style::default(),
+            1 => style = style.add_modifier(Modifier::BOLD),
+            4 => style = style.add_modifier(Modifier::UNDERLINED),
+            22 => style = style.remove_modifier(Modifier::BOLD),
+            24 => style = style.remove_modifier(Modifier::UNDERLINED),
+            30..=37 => style = style.fg(ansi_16_color((codes[i] - 30) as u8)),
+            90..=97 => style = style.fg(ansi_16_color((codes[i] - 90) as u8 + 8)),
+            39 => style = style.fg(Color::Reset),
+            40..=47 => style = style.bg(ansi_16_color((codes[i] - 40) as u8)),
+            100..=107 => style = style.bg(ansi_16_color((codes[i] - 100) as u8 + 8)),
+            49 => style = style.bg(Color::Reset),
+            38 => {
+                if codes.get(i + 1) == Some(&5) {
+                    if let Some(&n) = codes.get(i + 2) {
+                        style = style.fg(Color::Indexed(n as u8));
+                    }
+                    i += 2;
+                } else if codes.get(i + 1) == Some(&2) {
+                    if let (Some(&r), Some(&g), Some(&b)) =
+                        (codes.get(i + 2), codes.get(i + 3), codes.get(i + 4))
+                    {
+                        style = style.fg(Color::Rgb(r as u8, g as u8, b as u8));
+                    }
+                    i += 4;
+                }
+            }
+            48 => {
+                if codes.get(i + 1) == Some(&5) {
+                    if let Some(&n) = codes.get(i + 2) {
+                        style = style.bg(Color::Indexed(n as u8));
+                    }
+                    i += 2;
+                } else if codes.get(i + 1) == Some(&2) {
+                    if let (Some(&r), Some(&g), Some(&b)) =
+                        (codes.get(i + 2), codes.get(i + 3), codes.get(i + 4))
+                    {
+                        style = style.bg(Color::Rgb(r as u8, g as u8, b as u8));
+                    }
+                    i += 4;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    style
+}
+
+fn ansi_16_color(n: u8) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::Gray,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        15 => Color::White,
+        _ => Color::Reset,
+    }
+}
+
+fn parse_markdown_line<'a>(
+    line: &'a str,
+    code_state: &mut CodeBlockState,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    ui_theme: &super::theme::Theme,
+) -> Line<'a> {
     let line_trim = line.trim();
+    let dim = parse_color(&ui_theme.dim);
 
     if line_trim.starts_with("```") {
-        *in_code_block = !*in_code_block;
-        if *in_code_block {
-            return Line::from(vec![Span::styled(
-                " ┌ code",
-                Style::default().fg(Color::DarkGray),
-            )]);
+        code_state.active = !code_state.active;
+        if code_state.active {
+            let lang = line_trim.trim_start_matches('`').trim();
+            let syntax = syntax_set
+                .find_syntax_by_token(lang)
+                .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+            code_state.parse_state = Some(ParseState::new(syntax));
+            code_state.highlight_state = Some(HighlightState::new(
+                &Highlighter::new(theme),
+                ScopeStack::new(),
+            ));
+            return Line::from(vec![Span::styled(" ┌ code", Style::default().fg(dim))]);
         }
-        return Line::from(vec![Span::styled(
-            " └",
-            Style::default().fg(Color::DarkGray),
-        )]);
+        code_state.parse_state = None;
+        code_state.highlight_state = None;
+        return Line::from(vec![Span::styled(" └", Style::default().fg(dim))]);
     }
 
-    if *in_code_block {
-        return Line::from(vec![
-            Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
-            Span::styled(line, Style::default().fg(Color::Cyan)),
-        ]);
+    if code_state.active {
+        let mut spans = vec![Span::styled(" │ ", Style::default().fg(dim))];
+        if let (Some(parse_state), Some(highlight_state)) = (
+            code_state.parse_state.as_mut(),
+            code_state.highlight_state.as_mut(),
+        ) {
+            let highlighter = Highlighter::new(theme);
+            for fragment in LinesWithEndings::from(line) {
+                if let Ok(ops) = parse_state.parse_line(fragment, syntax_set) {
+                    let ranges = syntect::highlighting::HighlightIterator::new(
+                        highlight_state,
+                        &ops,
+                        fragment,
+                        &highlighter,
+                    );
+                    for (style, text) in ranges {
+                        let fg = style.foreground;
+                        spans.push(Span::styled(
+                            text.trim_end_matches(['\n', '\r']).to_string(),
+                            Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)),
+                        ));
+                    }
+                }
+            }
+            return Line::from(spans);
+        }
+        spans.push(Span::styled(
+            line,
+            Style::default().fg(parse_color(&ui_theme.code_color)),
+        ));
+        return Line::from(spans);
     }
 
     let mut spans = Vec::new();
@@ -292,14 +745,17 @@ fn parse_markdown_line<'a>(line: &'a str, in_code_block: &mut bool) -> Line<'a>
         spans.push(Span::styled(
             format!(" {} ", text.to_uppercase()),
             Style::default()
-                .fg(Color::Magenta)
+                .fg(parse_color(&ui_theme.heading_color))
                 .add_modifier(Modifier::BOLD),
         ));
         return Line::from(spans);
     }
 
     if line_trim.starts_with("- ") || line_trim.starts_with("* ") {
-        spans.push(Span::styled(" • ", Style::default().fg(Color::Yellow)));
+        spans.push(Span::styled(
+            " • ",
+            Style::default().fg(parse_color(&ui_theme.bullet_color)),
+        ));
         current_pos = line.find(|c| c == '-' || c == '*').unwrap_or(0) + 2;
     }
 
@@ -371,9 +827,9 @@ fn draw_input_area(f: &mut Frame, app: &mut App, area: Rect) {
             .title("Prompt")
             .borders(Borders::ALL)
             .border_style(if app.focus == Focus::Input {
-                Style::default().fg(Color::Cyan)
+                Style::default().fg(parse_color(&app.theme.accent))
             } else {
-                Style::default().fg(Color::DarkGray)
+                Style::default().fg(parse_color(&app.theme.border))
             }),
     );
     app.input_buffer.set_style(focus_style);
@@ -388,7 +844,7 @@ fn draw_help_overlay(f: &mut Frame, app: &App) {
         Line::from(Span::styled(
             "Niko Commands",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(parse_color(&app.theme.accent))
                 .add_modifier(Modifier::BOLD),
         )),
         Line::from(""),
@@ -400,17 +856,27 @@ fn draw_help_overlay(f: &mut Frame, app: &App) {
         Line::from("/index           Build/rebuild workspace index"),
         Line::from("/search <q>      Search files in index"),
         Line::from("/open <path>     Preview file in chat"),
-        Line::from("/plan <task>     Build task plan"),
-        Line::from("/next            Show next planned step"),
+        Line::from("/fetch <url>     Download a page and attach as context"),
+        Line::from("/diagnostics     Run the project build and summarize errors"),
+        Line::from("/plan <task>     Build and persist task plan"),
+        Line::from("/plan --resume [task]  Resume saved plan (add --force to override a task mismatch)"),
+        Line::from("/next [--force]  Show next step (runs real checks at the validation step)"),
         Line::from("/rag on|off      Enable or disable retrieval"),
+        Line::from("/watch on|off    Auto re-index the workspace as files change"),
+        Line::from("/rag keyword|semantic|hybrid  Switch retrieval mode"),
         Line::from("/run <cmd>       Stage shell command"),
-        Line::from("/approve         Execute staged command"),
+        Line::from("/approve         Execute staged command or write staged edits"),
         Line::from("/stop            Stop running command"),
-        Line::from("/deny            Cancel staged command"),
+        Line::from("/deny            Cancel staged command or discard staged edits"),
+        Line::from("/diff            Review proposed file edits"),
+        Line::from("/history [n|search <q>]  List or search past commands"),
+        Line::from("/rerun <id>      Stage a past command from /history again"),
         Line::from("/stats           Session metrics"),
         Line::from("/clear           Clear conversation"),
+        Line::from("/<name>          Run niko-<name> on PATH if not a built-in command"),
         Line::from(""),
         Line::from("Tip: use @path/to/file in prompts to attach files."),
+        Line::from("Ctrl+F searches the conversation; Enter/Shift+Enter jump matches."),
         Line::from("Esc closes this panel."),
         Line::from(format!(
             "RAG currently: {}",
@@ -425,7 +891,7 @@ fn draw_help_overlay(f: &mut Frame, app: &App) {
                 Block::default()
                     .title("Help")
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::LightBlue)),
+                    .border_style(Style::default().fg(parse_color(&app.theme.overlay_border))),
             )
             .alignment(Alignment::Left)
             .wrap(Wrap { trim: false }),
@@ -433,6 +899,377 @@ fn draw_help_overlay(f: &mut Frame, app: &App) {
     );
 }
 
+/// Max fuzzy-matched candidates shown in the file-picker overlay
+const FILE_PICKER_MAX_RESULTS: usize = 12;
+
+/// A scored fuzzy match against a workspace-indexed file path
+pub(crate) struct FileMatch<'a> {
+    pub(crate) path: &'a str,
+    score: i32,
+    matched: Vec<usize>,
+}
+
+/// Rank workspace-indexed files against `query` using subsequence fuzzy
+/// matching. Returns up to `FILE_PICKER_MAX_RESULTS` matches sorted by
+/// descending score.
+pub(crate) fn fuzzy_rank_files<'a>(app: &'a App, query: &str) -> Vec<FileMatch<'a>> {
+    let Some(index) = app.workspace_index.as_ref() else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<FileMatch> = index
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            fuzzy_match(query, &entry.path).map(|(score, matched)| FileMatch {
+                path: entry.path.as_str(),
+                score,
+                matched,
+            })
+        })
+        .collect();
+
+    matches.sort_by(|a, b| b.score.cmp(&a.score));
+    matches.truncate(FILE_PICKER_MAX_RESULTS);
+    matches
+}
+
+/// Subsequence fuzzy match: every char of `query` must appear in `candidate`
+/// in order. Rewards consecutive runs, matches at path-separator/camelCase
+/// boundaries, and matches at the start of the basename; penalizes leading
+/// gaps and overall match distance. Returns `None` if the query doesn't
+/// match at all. Returns the matched char indices for highlighting.
+fn fuzzy_match(query: &str, candidate: &str) -> Option<(i32, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let query_lower: Vec<char> = query.to_lowercase().chars().collect();
+    let cand_chars: Vec<char> = candidate.chars().collect();
+    let cand_lower: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    let basename_byte = candidate
+        .rfind(|c| c == '/' || c == '\\')
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let basename_idx = candidate[..basename_byte].chars().count();
+
+    let mut matched = Vec::with_capacity(query_lower.len());
+    let mut qi = 0;
+    let mut score: i32 = 0;
+    let mut last_match: Option<usize> = None;
+    let mut first_match: Option<usize> = None;
+
+    for (ci, &c) in cand_lower.iter().enumerate() {
+        if qi >= query_lower.len() {
+            break;
+        }
+        if c != query_lower[qi] {
+            continue;
+        }
+
+        let mut char_score = 10;
+        if let Some(last) = last_match {
+            if ci == last + 1 {
+                char_score += 15; // consecutive run
+            }
+        }
+        let prev_is_boundary = ci == 0
+            || matches!(cand_chars[ci - 1], '/' | '\\' | '_' | '-' | '.');
+        let is_camel_boundary =
+            ci > 0 && cand_chars[ci].is_uppercase() && cand_chars[ci - 1].is_lowercase();
+        if prev_is_boundary || is_camel_boundary {
+            char_score += 10;
+        }
+        if ci == basename_idx {
+            char_score += 20; // start of basename
+        }
+
+        score += char_score;
+        matched.push(ci);
+        first_match.get_or_insert(ci);
+        last_match = Some(ci);
+        qi += 1;
+    }
+
+    if qi < query_lower.len() {
+        return None;
+    }
+
+    let leading_gap = first_match.unwrap_or(0) as i32;
+    let span = last_match.unwrap_or(0) as i32 - first_match.unwrap_or(0) as i32 + 1;
+    score -= leading_gap;
+    score -= (span - query_lower.len() as i32).max(0);
+
+    Some((score, matched))
+}
+
+fn draw_file_picker_overlay(f: &mut Frame, app: &App) {
+    let popup = centered_rect(70, 60, f.area());
+    let results = fuzzy_rank_files(app, &app.file_picker_query);
+    let cursor = if results.is_empty() {
+        0
+    } else {
+        app.file_picker_cursor.min(results.len() - 1)
+    };
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("Find file: {}", app.file_picker_query),
+            Style::default()
+                .fg(parse_color(&app.theme.accent))
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+
+    if results.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No matches. ↑/↓ navigate, Enter insert, Esc close.",
+            Style::default().fg(Color::DarkGray),
+        )));
+    } else {
+        for (i, m) in results.iter().enumerate() {
+            let mut spans = Vec::new();
+            let base_style = if i == cursor {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::White)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            let match_style = if i == cursor {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(parse_color(&app.theme.match_current_bg))
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+                    .fg(parse_color(&app.theme.match_current_bg))
+                    .add_modifier(Modifier::BOLD)
+            };
+            spans.push(Span::styled(
+                if i == cursor { " > " } else { "   " },
+                base_style,
+            ));
+            for (ci, c) in m.path.chars().enumerate() {
+                let style = if m.matched.contains(&ci) {
+                    match_style
+                } else {
+                    base_style
+                };
+                spans.push(Span::styled(c.to_string(), style));
+            }
+            lines.push(Line::from(spans));
+        }
+    }
+
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title("Attach file (fuzzy search)")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(parse_color(&app.theme.overlay_border))),
+            )
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
+/// Lines of unchanged context kept around each change when building hunks
+const DIFF_CONTEXT: usize = 3;
+
+/// One line of a computed diff, tagged with its role and original line numbers
+struct DiffOp<'a> {
+    tag: char,
+    text: &'a str,
+    old_no: Option<usize>,
+    new_no: Option<usize>,
+}
+
+/// Longest-common-subsequence line diff between `old` and `new`, returned as
+/// a flat list of ` `/`-`/`+` tagged lines (not yet grouped into hunks).
+fn lcs_diff_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffOp<'a>> {
+    let n = old.len();
+    let m = new.len();
+    let mut dp = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            dp[i][j] = if old[i] == new[j] {
+                dp[i + 1][j + 1] + 1
+            } else {
+                dp[i + 1][j].max(dp[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut old_no, mut new_no) = (1usize, 1usize);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            ops.push(DiffOp { tag: ' ', text: old[i], old_no: Some(old_no), new_no: Some(new_no) });
+            i += 1;
+            j += 1;
+            old_no += 1;
+            new_no += 1;
+        } else if dp[i + 1][j] >= dp[i][j + 1] {
+            ops.push(DiffOp { tag: '-', text: old[i], old_no: Some(old_no), new_no: None });
+            i += 1;
+            old_no += 1;
+        } else {
+            ops.push(DiffOp { tag: '+', text: new[j], old_no: None, new_no: Some(new_no) });
+            j += 1;
+            new_no += 1;
+        }
+    }
+    while i < n {
+        ops.push(DiffOp { tag: '-', text: old[i], old_no: Some(old_no), new_no: None });
+        i += 1;
+        old_no += 1;
+    }
+    while j < m {
+        ops.push(DiffOp { tag: '+', text: new[j], old_no: None, new_no: Some(new_no) });
+        j += 1;
+        new_no += 1;
+    }
+    ops
+}
+
+/// Group diff ops into unified-diff hunks, keeping `DIFF_CONTEXT` lines of
+/// unchanged context around each run of changes and merging hunks whose
+/// context windows overlap.
+fn build_hunks<'a>(ops: &[DiffOp<'a>]) -> Vec<&[DiffOp<'a>]> {
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.tag != ' ')
+        .map(|(i, _)| i)
+        .collect();
+    if changed.is_empty() {
+        return Vec::new();
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &idx in &changed {
+        let start = idx.saturating_sub(DIFF_CONTEXT);
+        let end = (idx + DIFF_CONTEXT).min(ops.len() - 1);
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges.into_iter().map(|(s, e)| &ops[s..=e]).collect()
+}
+
+/// Render a unified diff between `old_contents` and `new_contents` as
+/// ratatui lines: `@@` headers in the theme's accent color, removed lines on
+/// `theme.diff_removed_bg`, added lines on `theme.diff_added_bg`, and plain
+/// context lines.
+fn render_unified_diff(
+    old_contents: &str,
+    new_contents: &str,
+    theme: &super::theme::Theme,
+) -> Vec<Line<'static>> {
+    let old_lines: Vec<&str> = old_contents.lines().collect();
+    let new_lines: Vec<&str> = new_contents.lines().collect();
+    let ops = lcs_diff_ops(&old_lines, &new_lines);
+    let hunks = build_hunks(&ops);
+
+    if hunks.is_empty() {
+        return vec![Line::from(Span::styled(
+            "(no changes)",
+            Style::default().fg(Color::DarkGray),
+        ))];
+    }
+
+    let mut lines = Vec::new();
+    for hunk in hunks {
+        let old_start = hunk.iter().find_map(|op| op.old_no).unwrap_or(1);
+        let new_start = hunk.iter().find_map(|op| op.new_no).unwrap_or(1);
+        let old_count = hunk.iter().filter(|op| op.old_no.is_some()).count();
+        let new_count = hunk.iter().filter(|op| op.new_no.is_some()).count();
+
+        lines.push(Line::from(Span::styled(
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old_start, old_count, new_start, new_count
+            ),
+            Style::default()
+                .fg(parse_color(&theme.accent))
+                .add_modifier(Modifier::BOLD),
+        )));
+
+        for op in hunk {
+            let (prefix, style) = match op.tag {
+                '-' => (
+                    "-",
+                    Style::default()
+                        .fg(parse_color(&theme.diff_fg))
+                        .bg(parse_color(&theme.diff_removed_bg)),
+                ),
+                '+' => (
+                    "+",
+                    Style::default()
+                        .fg(parse_color(&theme.diff_fg))
+                        .bg(parse_color(&theme.diff_added_bg)),
+                ),
+                _ => (" ", Style::default().fg(Color::Gray)),
+            };
+            lines.push(Line::from(Span::styled(
+                format!("{}{}", prefix, op.text),
+                style,
+            )));
+        }
+    }
+    lines
+}
+
+fn draw_diff_overlay(f: &mut Frame, app: &App) {
+    let popup = centered_rect(85, 85, f.area());
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("{} file(s) proposed for edit", app.pending_edits.len()),
+            Style::default()
+                .fg(parse_color(&app.theme.accent))
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from("/approve writes all staged edits, /deny discards them. Esc closes this preview."),
+        Line::from(""),
+    ];
+
+    for edit in &app.pending_edits {
+        lines.push(Line::from(Span::styled(
+            format!("--- {} ---", edit.path),
+            Style::default()
+                .fg(parse_color(&app.theme.match_current_bg))
+                .add_modifier(Modifier::BOLD),
+        )));
+        let old_contents = std::fs::read_to_string(&edit.path).unwrap_or_default();
+        lines.extend(render_unified_diff(&old_contents, &edit.new_contents, &app.theme));
+        lines.push(Line::from(""));
+    }
+
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title("Review proposed edits")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(parse_color(&app.theme.overlay_border))),
+            )
+            .scroll((app.diff_overlay_scroll, 0))
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -0,0 +1,143 @@
+use std::path::Path;
+use std::process::Command;
+
+/// One check run by `/next`'s validation gate — the format check,
+/// build/test run, or TODO/FIXME marker scan that backs the plan's "Run
+/// validation" step with something that actually executes instead of a
+/// manual reminder.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub command: String,
+    pub passed: bool,
+    pub exit_code: Option<i32>,
+    pub output_tail: String,
+}
+
+/// Cap on captured output per check, so a noisy build log doesn't dominate
+/// the plan history — only the tail, where failures usually show up.
+const OUTPUT_TAIL_CHARS: usize = 400;
+
+fn tail(output: &str) -> String {
+    if output.len() <= OUTPUT_TAIL_CHARS {
+        return output.trim().to_string();
+    }
+    let mut start = output.len() - OUTPUT_TAIL_CHARS;
+    while start < output.len() && !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("...{}", output[start..].trim())
+}
+
+fn run_shell(cwd: &Path, command: &str) -> (bool, Option<i32>, String) {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .args(["/C", command])
+            .current_dir(cwd)
+            .output()
+    } else {
+        Command::new("sh")
+            .args(["-lc", command])
+            .current_dir(cwd)
+            .output()
+    };
+    match output {
+        Ok(out) => {
+            let mut combined = String::from_utf8_lossy(&out.stdout).into_owned();
+            combined.push_str(&String::from_utf8_lossy(&out.stderr));
+            (out.status.success(), out.status.code(), tail(&combined))
+        }
+        Err(e) => (false, None, format!("failed to run: {}", e)),
+    }
+}
+
+fn format_check(cwd: &Path) -> Option<CheckResult> {
+    let command = if cwd.join("Cargo.toml").exists() {
+        "cargo fmt --all --check"
+    } else if cwd.join("package.json").exists() {
+        "npx --no-install prettier --check ."
+    } else {
+        return None;
+    };
+    let (passed, exit_code, output_tail) = run_shell(cwd, command);
+    Some(CheckResult {
+        name: "Format".to_string(),
+        command: command.to_string(),
+        passed,
+        exit_code,
+        output_tail,
+    })
+}
+
+fn build_check(cwd: &Path) -> Option<CheckResult> {
+    let command = if cwd.join("Cargo.toml").exists() {
+        "cargo build --workspace && cargo test --workspace"
+    } else if cwd.join("package.json").exists() {
+        "npm run build --if-present && npm test --if-present"
+    } else {
+        return None;
+    };
+    let (passed, exit_code, output_tail) = run_shell(cwd, command);
+    Some(CheckResult {
+        name: "Build/Test".to_string(),
+        command: command.to_string(),
+        passed,
+        exit_code,
+        output_tail,
+    })
+}
+
+/// Scans files tracked by git for `TODO`/`FIXME` markers — a cheap,
+/// toolchain-agnostic hygiene gate that doesn't need a build or test suite
+/// to exist, mirroring the xtask-style marker scans some repos run in CI.
+fn marker_scan(cwd: &Path) -> CheckResult {
+    let command = "git grep -n -E 'TODO|FIXME' -- .";
+    let output = if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .args(["/C", command])
+            .current_dir(cwd)
+            .output()
+    } else {
+        Command::new("sh")
+            .args(["-lc", command])
+            .current_dir(cwd)
+            .output()
+    };
+    match output {
+        // `git grep` exits 1 when nothing matches — that's a pass here.
+        Ok(out) => {
+            let matched = String::from_utf8_lossy(&out.stdout).into_owned();
+            let passed = matched.trim().is_empty();
+            CheckResult {
+                name: "Marker scan".to_string(),
+                command: command.to_string(),
+                passed,
+                exit_code: out.status.code(),
+                output_tail: tail(&matched),
+            }
+        }
+        Err(e) => CheckResult {
+            name: "Marker scan".to_string(),
+            command: command.to_string(),
+            passed: false,
+            exit_code: None,
+            output_tail: format!("failed to run: {}", e),
+        },
+    }
+}
+
+/// Runs the plan's validation battery against `cwd`: a format check and a
+/// build/test run for whichever toolchain is detected (skipped for
+/// toolchain-less projects), plus a repo-wide TODO/FIXME marker scan that
+/// always runs.
+pub fn run_checks(cwd: &Path) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    if let Some(r) = format_check(cwd) {
+        results.push(r);
+    }
+    if let Some(r) = build_check(cwd) {
+        results.push(r);
+    }
+    results.push(marker_scan(cwd));
+    results
+}
@@ -1,28 +1,130 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use notify::Watcher;
 use serde::{Deserialize, Serialize};
 
+use crate::config;
+use crate::llm::{self, EmbeddingProvider};
+
+/// Quiet period `watch` waits for before applying a batch of incremental
+/// updates: each further fs event restarts the wait, so a burst of saves (or an editor's
+/// unlink-then-rewrite) re-tokenizes each touched file once instead of once
+/// per intermediate write.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Which retrieval strategy `/rag` should use. Keyword stays the default
+/// since it's free (no model call); semantic trades latency for recall on
+/// paraphrased queries the BM25 keyword path misses entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RagMode {
+    Keyword,
+    Semantic,
+    /// Blends BM25 and cosine-similarity rankings via `retrieve_hybrid`.
+    Hybrid,
+}
+
+/// ~40-line windows with ~8-line overlap, so a match near a window boundary
+/// still has enough surrounding context in at least one chunk.
+const CHUNK_WINDOW_LINES: usize = 40;
+const CHUNK_OVERLAP_LINES: usize = 8;
+
+/// Dimensionality of the offline hashing-trick fallback vectorizer, used
+/// when no embedding-capable provider is configured or reachable.
+const HASH_EMBED_DIM: usize = 256;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexedFile {
     pub path: String,
     pub content: String,
-    pub terms: HashSet<String>,
+    /// Term → occurrence count, used for BM25 term-frequency scoring in
+    /// `WorkspaceIndex::bm25_score` (was a `HashSet<String>` before BM25).
+    pub terms: HashMap<String, u32>,
+    /// Total token count (sum of `terms`' counts), i.e. `|d|` in the BM25
+    /// length-normalization term.
+    #[serde(default)]
+    pub doc_len: usize,
     pub modified_unix: u64,
     pub size: u64,
+    /// Fast content fingerprint, checked on rebuild when `modified_unix` has
+    /// drifted but the bytes might not have (a touch, or an edit-then-revert)
+    #[serde(default)]
+    pub content_hash: u64,
+    /// Number of times this file has been surfaced by `retrieve` or
+    /// referenced by a generated command, via `WorkspaceIndex::record_access`.
+    #[serde(default)]
+    pub access_count: u32,
+    /// Unix timestamp of the most recent `record_access` call, or 0 if never
+    /// accessed — used for the recency half of frecency weighting.
+    #[serde(default)]
+    pub last_access_unix: u64,
+}
+
+/// An embedded ~40-line window of an indexed file, used for semantic
+/// retrieval. `content_hash` lets incremental rebuilds skip re-embedding
+/// chunks whose text hasn't changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddedChunk {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content_hash: u64,
+    pub vector: Vec<f32>,
+    /// Precomputed L2 norm of `vector`, so cosine similarity at query time
+    /// doesn't recompute it for every chunk on every query.
+    pub norm: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkspaceIndex {
     pub root: PathBuf,
     pub entries: Vec<IndexedFile>,
+    /// Present only once `/rag semantic` (or a rebuild since) has populated
+    /// them; empty on indexes built before this field existed.
+    #[serde(default)]
+    pub chunks: Vec<EmbeddedChunk>,
     pub indexed_files: usize,
     pub skipped_files: usize,
+    /// Files carried forward unchanged from the previous build (matched by
+    /// mtime, or by content hash when mtime drifted but bytes didn't)
+    #[serde(default)]
+    pub reused_files: usize,
+    /// Files re-read and re-indexed because their content actually changed
+    #[serde(default)]
+    pub reindexed_files: usize,
     pub built_unix: u64,
+    /// Corpus-wide average document length (`avgdl` in BM25), over `entries`.
+    #[serde(default)]
+    pub avgdl: f64,
+    /// Corpus-wide document frequency `n(t)` — number of `entries` whose
+    /// `terms` contain term `t` — used for BM25's IDF component.
+    #[serde(default)]
+    pub doc_freq: HashMap<String, u32>,
+    /// Entries dropped by the most recent `prune` because their file no
+    /// longer exists on disk.
+    #[serde(default)]
+    pub pruned_deleted: usize,
+    /// Entries evicted by the most recent `prune` for falling outside the
+    /// access-retention window once the cache grew past
+    /// `PRUNE_SIZE_THRESHOLD`.
+    #[serde(default)]
+    pub pruned_stale: usize,
 }
 
+/// Default `prune` retention window: entries untouched by `record_access`
+/// for longer than this are eligible for eviction once the cache exceeds
+/// `PRUNE_SIZE_THRESHOLD`.
+pub const DEFAULT_RETENTION_SECS: u64 = 90 * 24 * 3600;
+
+/// Cache size (entry count) above which `prune` starts evicting
+/// unaccessed/stale entries instead of leaving them — below this, a few
+/// stray never-queried files aren't worth the churn.
+const PRUNE_SIZE_THRESHOLD: usize = 2000;
+
 #[derive(Debug, Clone)]
 struct CandidateFile {
     path: PathBuf,
@@ -41,9 +143,11 @@ impl WorkspaceIndex {
         cache_path: &Path,
         max_files: usize,
         max_file_bytes: u64,
+        retention_secs: u64,
     ) -> Self {
         let cached = Self::load_cache(cache_path).ok();
-        let index = Self::build_from_existing(root, max_files, max_file_bytes, cached.as_ref());
+        let mut index = Self::build_from_existing(root, max_files, max_file_bytes, cached.as_ref());
+        index.prune(retention_secs);
         let _ = index.save_cache(cache_path);
         index
     }
@@ -63,6 +167,85 @@ impl WorkspaceIndex {
         fs::write(cache_path, raw).map_err(|e| format!("failed to write cache: {}", e))
     }
 
+    /// Bumps `path`'s access stats — call whenever a retrieved file is
+    /// surfaced to the user or referenced by a generated command, so
+    /// frequently and recently touched files float to the top of future
+    /// `retrieve` calls via `frecency_multiplier`. No-op if `path` isn't
+    /// (or is no longer) in the index.
+    pub fn record_access(&mut self, path: &str) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.path == path) {
+            entry.access_count = entry.access_count.saturating_add(1);
+            entry.last_access_unix = unix_now();
+        }
+    }
+
+    /// Daemon mode: loads `cache_path` (or builds fresh), then blocks
+    /// watching `root` for filesystem events, re-tokenizing just the
+    /// changed file(s) on each debounced batch instead of paying a full
+    /// rescan per save — so a long-lived editor session's context stays
+    /// current without repeatedly walking the whole tree. Respects the same
+    /// ignore rules and `max_file_bytes` limit as `collect_candidates`.
+    /// Persists the cache and calls `on_update` after every batch that
+    /// actually changed something. Returns once the watcher itself errors
+    /// out or its channel is dropped; callers that want to stop watching
+    /// should run this on its own thread and drop the returned control, if
+    /// any — currently there is none, so stopping means killing the thread.
+    pub fn watch(
+        root: &Path,
+        cache_path: &Path,
+        max_files: usize,
+        max_file_bytes: u64,
+        retention_secs: u64,
+        mut on_update: impl FnMut(&WorkspaceIndex),
+    ) -> Result<(), String> {
+        let mut index = Self::load_cache(cache_path)
+            .unwrap_or_else(|_| Self::build(root, max_files, max_file_bytes));
+        index.prune(retention_secs);
+        index.save_cache(cache_path)?;
+
+        let (raw_tx, raw_rx) = mpsc::channel::<notify::Event>();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                let _ = raw_tx.send(event);
+            }
+        })
+        .map_err(|e| format!("failed to start watcher: {}", e))?;
+        watcher
+            .watch(root, notify::RecursiveMode::Recursive)
+            .map_err(|e| format!("failed to watch '{}': {}", root.display(), e))?;
+
+        loop {
+            let first = match raw_rx.recv() {
+                Ok(event) => event,
+                Err(_) => return Ok(()), // watcher dropped
+            };
+            let mut changed_paths = first.paths;
+
+            // Drain the rest of this burst so N rapid saves coalesce into one batch.
+            loop {
+                match raw_rx.recv_timeout(WATCH_DEBOUNCE) {
+                    Ok(event) => changed_paths.extend(event.paths),
+                    Err(_) => break,
+                }
+            }
+            changed_paths.sort();
+            changed_paths.dedup();
+
+            let mut changed = false;
+            for path in &changed_paths {
+                if index.apply_change(root, path, max_file_bytes) {
+                    changed = true;
+                }
+            }
+
+            if changed {
+                index.prune(retention_secs);
+                index.save_cache(cache_path)?;
+                on_update(&index);
+            }
+        }
+    }
+
     pub fn retrieve(&self, query: &str, top_k: usize, max_chars: usize) -> Vec<(String, String)> {
         let query_terms = extract_terms(query);
         if query_terms.is_empty() {
@@ -73,16 +256,124 @@ impl WorkspaceIndex {
             .entries
             .iter()
             .filter_map(|entry| {
-                let overlap = query_terms.intersection(&entry.terms).count();
-                if overlap == 0 {
-                    None
-                } else {
-                    Some((overlap, entry))
+                let score = self.bm25_score(&query_terms, entry);
+                if score <= 0.0 {
+                    return None;
                 }
+                let frecency = frecency_multiplier(entry.access_count, entry.last_access_unix);
+                let boosted = score * (1.0 + (1.0 + frecency).ln());
+                Some((boosted, entry))
             })
             .collect::<Vec<_>>();
 
-        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut out = Vec::new();
+        for (_, entry) in scored.into_iter().take(top_k) {
+            let mut snippet = entry.content.clone();
+            if snippet.len() > max_chars {
+                let mut end = max_chars;
+                while end > 0 && !snippet.is_char_boundary(end) {
+                    end -= 1;
+                }
+                snippet.truncate(end);
+                snippet.push_str("\n[...truncated]");
+            }
+            out.push((entry.path.clone(), snippet));
+        }
+        out
+    }
+
+    /// Embedding-backed counterpart to `retrieve`: ranks chunks by cosine
+    /// similarity instead of keyword overlap, so paraphrased queries that
+    /// share no terms with the target file can still surface it. Falls back
+    /// to `retrieve` when the index predates chunking (empty `chunks`).
+    pub fn retrieve_semantic(&self, query: &str, top_k: usize, max_chars: usize) -> Vec<(String, String)> {
+        if self.chunks.is_empty() {
+            return self.retrieve(query, top_k, max_chars);
+        }
+
+        let scored = self.chunk_similarities(query);
+
+        let file_content: HashMap<&str, &str> = self
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.content.as_str()))
+            .collect();
+
+        let mut out = Vec::new();
+        for (_, chunk) in scored.into_iter().take(top_k) {
+            let Some(content) = file_content.get(chunk.path.as_str()) else {
+                continue;
+            };
+            let lines = content.lines().collect::<Vec<_>>();
+            let end = chunk.end_line.min(lines.len());
+            if chunk.start_line >= end {
+                continue;
+            }
+            let mut snippet = lines[chunk.start_line..end].join("\n");
+            if snippet.len() > max_chars {
+                let mut char_end = max_chars;
+                while char_end > 0 && !snippet.is_char_boundary(char_end) {
+                    char_end -= 1;
+                }
+                snippet.truncate(char_end);
+                snippet.push_str("\n[...truncated]");
+            }
+            out.push((chunk.path.clone(), snippet));
+        }
+        out
+    }
+
+    /// Blends lexical (BM25) and semantic (cosine) retrieval: each ranking
+    /// is divided by its own highest score independently — since BM25 and cosine
+    /// similarity land in very different numeric ranges — then combined as
+    /// `0.5 * lex + 0.5 * sem` per file, taking the best-matching chunk's
+    /// similarity as that file's semantic score. Falls back to pure
+    /// `retrieve` when the index predates chunking (empty `chunks`).
+    pub fn retrieve_hybrid(&self, query: &str, top_k: usize, max_chars: usize) -> Vec<(String, String)> {
+        if self.chunks.is_empty() {
+            return self.retrieve(query, top_k, max_chars);
+        }
+
+        let query_terms = extract_terms(query);
+        let lex_scores: HashMap<&str, f64> = self
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), self.bm25_score(&query_terms, e)))
+            .collect();
+        let lex_max = lex_scores.values().cloned().fold(0.0_f64, f64::max);
+
+        let mut sem_scores: HashMap<&str, f32> = HashMap::new();
+        for (score, chunk) in self.chunk_similarities(query) {
+            sem_scores
+                .entry(chunk.path.as_str())
+                .and_modify(|best| {
+                    if score > *best {
+                        *best = score;
+                    }
+                })
+                .or_insert(score);
+        }
+        let sem_max = sem_scores.values().cloned().fold(0.0_f32, f32::max) as f64;
+
+        let mut scored = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let lex = lex_scores.get(entry.path.as_str()).copied().unwrap_or(0.0);
+                let sem = sem_scores.get(entry.path.as_str()).copied().unwrap_or(0.0) as f64;
+                let lex_norm = if lex_max > 0.0 { lex / lex_max } else { 0.0 };
+                let sem_norm = if sem_max > 0.0 { sem / sem_max } else { 0.0 };
+                let blended = 0.5 * lex_norm + 0.5 * sem_norm;
+                if blended > 0.0 {
+                    Some((blended, entry))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
 
         let mut out = Vec::new();
         for (_, entry) in scored.into_iter().take(top_k) {
@@ -100,6 +391,37 @@ impl WorkspaceIndex {
         out
     }
 
+    /// Cosine-similarity score for every embedded chunk against `query`,
+    /// descending by score — shared by `retrieve_semantic` and
+    /// `retrieve_hybrid`. Empty if the index predates chunking or the query
+    /// embeds to an all-zero vector.
+    fn chunk_similarities(&self, query: &str) -> Vec<(f32, &EmbeddedChunk)> {
+        if self.chunks.is_empty() {
+            return Vec::new();
+        }
+
+        let query_vec = embed_texts(&[query.to_string()])
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let query_norm = l2_norm(&query_vec);
+        if query_norm == 0.0 {
+            return Vec::new();
+        }
+
+        let mut scored = self
+            .chunks
+            .iter()
+            .filter(|c| c.norm > 0.0 && c.vector.len() == query_vec.len())
+            .map(|c| {
+                let dot: f32 = c.vector.iter().zip(&query_vec).map(|(a, b)| a * b).sum();
+                (dot / (c.norm * query_norm), c)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        scored
+    }
+
     pub fn search_paths(&self, query: &str, top_k: usize) -> Vec<String> {
         let q = query.to_lowercase();
         let mut out = self
@@ -116,21 +438,47 @@ impl WorkspaceIndex {
                 .entries
                 .iter()
                 .filter_map(|entry| {
-                    let score = terms.intersection(&entry.terms).count();
-                    if score == 0 {
-                        None
-                    } else {
+                    let score = self.bm25_score(&terms, entry);
+                    if score > 0.0 {
                         Some((score, entry.path.clone()))
+                    } else {
+                        None
                     }
                 })
                 .collect::<Vec<_>>();
-            scored.sort_by(|a, b| b.0.cmp(&a.0));
+            scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
             out = scored.into_iter().take(top_k).map(|(_, p)| p).collect();
         }
 
         out
     }
 
+    /// Okapi BM25 score of `entry` against `query_terms`, using this index's
+    /// corpus-wide document frequencies (`doc_freq`) and average document
+    /// length (`avgdl`). Query terms that don't appear anywhere in the
+    /// corpus are skipped — they carry no IDF signal either way.
+    fn bm25_score(&self, query_terms: &HashSet<String>, entry: &IndexedFile) -> f64 {
+        const K1: f64 = 1.2;
+        const B: f64 = 0.75;
+
+        let n = self.entries.len() as f64;
+        let avgdl = if self.avgdl > 0.0 { self.avgdl } else { 1.0 };
+
+        query_terms
+            .iter()
+            .filter_map(|t| {
+                let f = *entry.terms.get(t).unwrap_or(&0) as f64;
+                if f == 0.0 {
+                    return None;
+                }
+                let n_t = *self.doc_freq.get(t)? as f64;
+                let idf = (1.0 + (n - n_t + 0.5) / (n_t + 0.5)).ln();
+                let denom = f + K1 * (1.0 - B + B * (entry.doc_len as f64) / avgdl);
+                Some(idf * (f * (K1 + 1.0)) / denom)
+            })
+            .sum()
+    }
+
     fn build_from_existing(
         root: &Path,
         max_files: usize,
@@ -152,6 +500,8 @@ impl WorkspaceIndex {
             .unwrap_or_default();
 
         let mut entries = Vec::new();
+        let mut reused_files = 0usize;
+        let mut reindexed_files = 0usize;
         for candidate in candidates {
             if indexed_files >= max_files {
                 break;
@@ -161,6 +511,7 @@ impl WorkspaceIndex {
                 if old.modified_unix == candidate.modified_unix && old.size == candidate.size {
                     entries.push(old.clone());
                     indexed_files += 1;
+                    reused_files += 1;
                     continue;
                 }
             }
@@ -172,24 +523,472 @@ impl WorkspaceIndex {
                     continue;
                 }
             };
+            let content_hash = hash_text(&content);
+
+            if let Some(old) = existing_map.get(&candidate.rel) {
+                if old.size == candidate.size && old.content_hash == content_hash {
+                    // mtime drifted (touched, or edited-then-reverted) but the
+                    // bytes are identical — carry the cached entry forward
+                    // instead of recomputing terms for unchanged text.
+                    let mut reused = old.clone();
+                    reused.modified_unix = candidate.modified_unix;
+                    entries.push(reused);
+                    indexed_files += 1;
+                    reused_files += 1;
+                    continue;
+                }
+            }
+
+            let terms = extract_term_counts(&content);
+            let doc_len = terms.values().map(|&c| c as usize).sum();
 
             entries.push(IndexedFile {
                 path: candidate.rel,
-                terms: extract_terms(&content),
+                terms,
+                doc_len,
+                content_hash,
                 content,
                 modified_unix: candidate.modified_unix,
                 size: candidate.size,
+                access_count: 0,
+                last_access_unix: 0,
             });
             indexed_files += 1;
+            reindexed_files += 1;
         }
 
+        let chunks = build_chunks(&entries, existing.map(|idx| idx.chunks.as_slice()).unwrap_or(&[]));
+        let (avgdl, doc_freq) = corpus_stats(&entries);
+
         Self {
             root: root.to_path_buf(),
             entries,
+            chunks,
             indexed_files,
             skipped_files,
+            reused_files,
+            reindexed_files,
             built_unix: unix_now(),
+            avgdl,
+            doc_freq,
+            pruned_deleted: 0,
+            pruned_stale: 0,
+        }
+    }
+
+    /// Cache maintenance: drops any entry whose file no longer exists on
+    /// disk (catches a cache loaded without an intervening disk walk, e.g.
+    /// `watch`'s startup load), then — only once the cache has grown past
+    /// `PRUNE_SIZE_THRESHOLD` — evicts entries last accessed (or never
+    /// accessed) more than `retention_secs` ago. Recomputes `avgdl`/
+    /// `doc_freq`/`indexed_files` and drops orphaned chunks if anything was
+    /// pruned. Called by `build_incremental` and `watch` before persisting,
+    /// so the on-disk cache doesn't grow unbounded across a long session.
+    pub fn prune(&mut self, retention_secs: u64) {
+        let before = self.entries.len();
+        self.entries.retain(|e| self.root.join(&e.path).is_file());
+        self.pruned_deleted = before - self.entries.len();
+
+        self.pruned_stale = 0;
+        if self.entries.len() > PRUNE_SIZE_THRESHOLD {
+            let now = unix_now();
+            let before_stale = self.entries.len();
+            self.entries
+                .retain(|e| now.saturating_sub(e.last_access_unix) <= retention_secs);
+            self.pruned_stale = before_stale - self.entries.len();
+        }
+
+        if self.pruned_deleted > 0 || self.pruned_stale > 0 {
+            let kept_paths: HashSet<&str> = self.entries.iter().map(|e| e.path.as_str()).collect();
+            self.chunks.retain(|c| kept_paths.contains(c.path.as_str()));
+            let (avgdl, doc_freq) = corpus_stats(&self.entries);
+            self.avgdl = avgdl;
+            self.doc_freq = doc_freq;
+            self.indexed_files = self.entries.len();
+        }
+    }
+
+    /// Applies a single `watch` filesystem event: removes `path`'s entry if
+    /// it no longer exists or no longer qualifies (deleted, grown past
+    /// `max_file_bytes`, now ignored), otherwise re-reads and re-tokenizes
+    /// it in place, carrying its access stats forward. Either way,
+    /// `avgdl`/`doc_freq` and the chunk set are recomputed over the full
+    /// (now-updated) entry list, same as a normal rebuild — cheap relative
+    /// to the embedding cost `build_chunks` dedupes away for files that
+    /// didn't change. Returns `true` if the index actually changed.
+    fn apply_change(&mut self, root: &Path, path: &Path, max_file_bytes: u64) -> bool {
+        let Ok(rel_path) = path.strip_prefix(root) else {
+            return false;
+        };
+        let rel = rel_path.display().to_string();
+
+        let is_ignored = path.components().any(|c| {
+            c.as_os_str()
+                .to_str()
+                .map(|s| is_ignored_dir(&s.to_lowercase()))
+                .unwrap_or(false)
+        }) || path_is_ignored(&ancestor_ignore_patterns(root, path), path, false);
+
+        let too_large = fs::metadata(path)
+            .map(|m| m.len() > max_file_bytes)
+            .unwrap_or(false);
+        if !path.is_file() || is_ignored || too_large || !looks_like_text_file(path) {
+            let had_entry = self.entries.iter().any(|e| e.path == rel);
+            if !had_entry {
+                return false;
+            }
+            self.entries.retain(|e| e.path != rel);
+            self.chunks.retain(|c| c.path != rel);
+            let (avgdl, doc_freq) = corpus_stats(&self.entries);
+            self.avgdl = avgdl;
+            self.doc_freq = doc_freq;
+            self.indexed_files = self.entries.len();
+            return true;
+        }
+
+        let metadata = match fs::metadata(path) {
+            Ok(m) => m,
+            Err(_) => return false,
+        };
+        if metadata.len() > max_file_bytes {
+            return false;
         }
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(_) => return false,
+        };
+        let content_hash = hash_text(&content);
+
+        let (access_count, last_access_unix) = match self.entries.iter().find(|e| e.path == rel) {
+            Some(old) if old.content_hash == content_hash => return false, // bytes unchanged
+            Some(old) => (old.access_count, old.last_access_unix),
+            None => (0, 0),
+        };
+
+        let terms = extract_term_counts(&content);
+        let doc_len = terms.values().map(|&c| c as usize).sum();
+        let modified_unix = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.entries.retain(|e| e.path != rel);
+        self.entries.push(IndexedFile {
+            path: rel.clone(),
+            content,
+            terms,
+            doc_len,
+            modified_unix,
+            size: metadata.len(),
+            content_hash,
+            access_count,
+            last_access_unix,
+        });
+        self.chunks.retain(|c| c.path != rel);
+
+        let existing_chunks = std::mem::take(&mut self.chunks);
+        self.chunks = build_chunks(&self.entries, &existing_chunks);
+        let (avgdl, doc_freq) = corpus_stats(&self.entries);
+        self.avgdl = avgdl;
+        self.doc_freq = doc_freq;
+        self.indexed_files = self.entries.len();
+
+        true
+    }
+}
+
+/// Computes BM25's corpus-wide `avgdl` (average document length) and
+/// `doc_freq` (`n(t)`, the number of documents containing each term) over
+/// every entry in the index.
+fn corpus_stats(entries: &[IndexedFile]) -> (f64, HashMap<String, u32>) {
+    let mut doc_freq: HashMap<String, u32> = HashMap::new();
+    let mut total_len = 0usize;
+
+    for entry in entries {
+        total_len += entry.doc_len;
+        for term in entry.terms.keys() {
+            *doc_freq.entry(term.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let avgdl = if entries.is_empty() {
+        0.0
+    } else {
+        total_len as f64 / entries.len() as f64
+    };
+
+    (avgdl, doc_freq)
+}
+
+/// Splits every indexed file into overlapping windows and embeds each one,
+/// reusing the existing vector (by content hash) for chunks whose text is
+/// unchanged from the previous build so an incremental rebuild only pays
+/// for what actually changed.
+fn build_chunks(entries: &[IndexedFile], existing_chunks: &[EmbeddedChunk]) -> Vec<EmbeddedChunk> {
+    let existing_by_key: HashMap<(&str, usize, usize), &EmbeddedChunk> = existing_chunks
+        .iter()
+        .map(|c| ((c.path.as_str(), c.start_line, c.end_line), c))
+        .collect();
+
+    let mut chunks = Vec::new();
+    let mut pending_texts = Vec::new();
+    let mut pending_meta = Vec::new();
+
+    for entry in entries {
+        for (start_line, end_line, text) in chunk_lines(&entry.content) {
+            let content_hash = hash_text(&text);
+            let key = (entry.path.as_str(), start_line, end_line);
+            if let Some(old) = existing_by_key.get(&key) {
+                if old.content_hash == content_hash {
+                    chunks.push((*old).clone());
+                    continue;
+                }
+            }
+            pending_meta.push((entry.path.clone(), start_line, end_line, content_hash));
+            pending_texts.push(text);
+        }
+    }
+
+    if !pending_texts.is_empty() {
+        let vectors = embed_texts(&pending_texts);
+        for ((path, start_line, end_line, content_hash), vector) in
+            pending_meta.into_iter().zip(vectors)
+        {
+            let norm = l2_norm(&vector);
+            chunks.push(EmbeddedChunk {
+                path,
+                start_line,
+                end_line,
+                content_hash,
+                vector,
+                norm,
+            });
+        }
+    }
+
+    chunks
+}
+
+/// Splits `content` into `CHUNK_WINDOW_LINES`-line windows overlapping by
+/// `CHUNK_OVERLAP_LINES`, returned as `(start_line, end_line, text)`.
+fn chunk_lines(content: &str) -> Vec<(usize, usize, String)> {
+    let lines = content.lines().collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Vec::new();
+    }
+
+    let step = CHUNK_WINDOW_LINES - CHUNK_OVERLAP_LINES;
+    let mut out = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + CHUNK_WINDOW_LINES).min(lines.len());
+        out.push((start, end, lines[start..end].join("\n")));
+        if end >= lines.len() {
+            break;
+        }
+        start += step;
+    }
+    out
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn l2_norm(vector: &[f32]) -> f32 {
+    vector.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+/// Embeds `texts` via the active provider's `/api/embeddings`-style endpoint
+/// when it's configured and reachable, falling back to a deterministic
+/// hashing-trick vectorizer (bag-of-terms hashed into `HASH_EMBED_DIM`
+/// buckets) when offline, so semantic mode degrades gracefully instead of
+/// failing outright.
+fn embed_texts(texts: &[String]) -> Vec<Vec<f32>> {
+    if let Some(vectors) = embed_via_configured_provider(texts) {
+        return vectors;
+    }
+    texts.iter().map(|t| hash_embed(t)).collect()
+}
+
+/// Pluggable by provider kind, not hardcoded to one backend: Ollama and any
+/// OpenAI-compatible endpoint both implement `EmbeddingProvider`, so whatever
+/// the user has configured as their active provider is tried first.
+fn embed_via_configured_provider(texts: &[String]) -> Option<Vec<Vec<f32>>> {
+    let (name, pcfg) = config::active_provider().ok()?;
+
+    match pcfg.kind.as_str() {
+        "ollama" => {
+            let base_url = if pcfg.base_url.is_empty() {
+                "http://127.0.0.1:11434"
+            } else {
+                &pcfg.base_url
+            };
+            let provider = llm::ollama::OllamaProvider::new(
+                base_url,
+                &pcfg.model,
+                llm::ollama::options_from_config(&pcfg),
+            )
+            .ok()?;
+            provider.embed(texts).ok()
+        }
+        "openai_compat" => {
+            let provider = llm::openai_compat::OpenAICompatProvider::new(
+                &name,
+                &pcfg.api_key,
+                &pcfg.base_url,
+                &pcfg.model,
+                pcfg.context_window,
+                &pcfg.options,
+            );
+            provider.embed(texts).ok()
+        }
+        _ => None,
+    }
+}
+
+fn hash_embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0f32; HASH_EMBED_DIM];
+    for term in extract_terms(text) {
+        let bucket = (hash_text(&term) as usize) % HASH_EMBED_DIM;
+        vector[bucket] += 1.0;
+    }
+    vector
+}
+
+/// One parsed line from a `.gitignore`/`.nikoignore` file.
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    /// Glob, stripped of its leading `!`/`/` and trailing `/`.
+    glob: String,
+    /// `!pattern` re-includes a path an earlier pattern excluded.
+    negate: bool,
+    /// Trailing `/` in the source line — only matches directories.
+    dir_only: bool,
+    /// A `/` anywhere but the end anchors the match to the ignore file's own
+    /// directory; without one, the glob matches a path's basename at any depth.
+    anchored: bool,
+}
+
+/// Loads and parses `.gitignore` and `.nikoignore` (checked in that order)
+/// directly inside `dir`, paired with `dir` itself so later matching knows
+/// which directory an anchored pattern is relative to.
+fn load_ignore_patterns(dir: &Path) -> Vec<(PathBuf, IgnorePattern)> {
+    let mut patterns = Vec::new();
+    for name in [".gitignore", ".nikoignore"] {
+        let Ok(raw) = fs::read_to_string(dir.join(name)) else {
+            continue;
+        };
+        for line in raw.lines() {
+            if let Some(pattern) = parse_ignore_line(line) {
+                patterns.push((dir.to_path_buf(), pattern));
+            }
+        }
+    }
+    patterns
+}
+
+/// Reconstructs the patterns that would apply to a single `path` if
+/// `collect_candidates` had walked down to it: root's own patterns plus
+/// each ancestor directory's, accumulated in the same order a directory
+/// walk builds them in. Needed by `apply_change`, which reacts to one
+/// changed path at a time rather than a live DFS stack.
+fn ancestor_ignore_patterns(root: &Path, path: &Path) -> Vec<(PathBuf, IgnorePattern)> {
+    let mut patterns = load_ignore_patterns(root);
+    if let Ok(rel) = path.strip_prefix(root) {
+        let mut dir = root.to_path_buf();
+        if let Some(parent_rel) = rel.parent() {
+            for component in parent_rel.components() {
+                dir.push(component);
+                patterns.extend(load_ignore_patterns(&dir));
+            }
+        }
+    }
+    patterns
+}
+
+fn parse_ignore_line(line: &str) -> Option<IgnorePattern> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negate, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    if line.is_empty() {
+        return None;
+    }
+
+    let dir_only = line.len() > 1 && line.ends_with('/');
+    let trimmed = if dir_only { &line[..line.len() - 1] } else { line };
+    let anchored = trimmed.starts_with('/') || trimmed.trim_start_matches('/').contains('/');
+    let glob = trimmed.trim_start_matches('/').to_string();
+    if glob.is_empty() {
+        return None;
+    }
+
+    Some(IgnorePattern { glob, negate, dir_only, anchored })
+}
+
+/// Whether `path` (a directory if `is_dir`) is excluded by `patterns` — later
+/// patterns override earlier ones, and a trailing `!pattern` re-includes a
+/// path an earlier pattern excluded, matching standard gitignore precedence.
+fn path_is_ignored(patterns: &[(PathBuf, IgnorePattern)], path: &Path, is_dir: bool) -> bool {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let mut ignored = false;
+
+    for (base, pattern) in patterns {
+        if pattern.dir_only && !is_dir {
+            continue;
+        }
+
+        let matched = if pattern.anchored {
+            match path.strip_prefix(base) {
+                Ok(rel) => glob_match(&pattern.glob, &rel.to_string_lossy().replace('\\', "/")),
+                Err(_) => false,
+            }
+        } else {
+            glob_match(&pattern.glob, file_name)
+        };
+
+        if matched {
+            ignored = !pattern.negate;
+        }
+    }
+
+    ignored
+}
+
+/// Minimal glob matcher supporting `*` (any run of characters) and `?` (any
+/// single character) — enough for the patterns real `.gitignore` files
+/// actually use. Unlike git, `*` here also matches `/`, which only differs
+/// from git's behavior for patterns mixing wildcards with explicit path
+/// separators — rare enough in practice not to warrant the extra complexity.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    glob_match_bytes(pattern.as_bytes(), text.as_bytes())
+}
+
+fn glob_match_bytes(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') => {
+            let mut rest = pattern;
+            while rest.first() == Some(&b'*') {
+                rest = &rest[1..];
+            }
+            if rest.is_empty() {
+                return true;
+            }
+            (0..=text.len()).any(|i| glob_match_bytes(rest, &text[i..]))
+        }
+        Some(b'?') => !text.is_empty() && glob_match_bytes(&pattern[1..], &text[1..]),
+        Some(&c) => !text.is_empty() && text[0] == c && glob_match_bytes(&pattern[1..], &text[1..]),
     }
 }
 
@@ -200,9 +999,9 @@ fn collect_candidates(
     skipped_files: &mut usize,
 ) -> Vec<CandidateFile> {
     let mut out = Vec::new();
-    let mut stack = vec![root.to_path_buf()];
+    let mut stack = vec![(root.to_path_buf(), load_ignore_patterns(root))];
 
-    while let Some(dir) = stack.pop() {
+    while let Some((dir, patterns)) = stack.pop() {
         let read_dir = match fs::read_dir(&dir) {
             Ok(rd) => rd,
             Err(_) => {
@@ -220,10 +1019,16 @@ fn collect_candidates(
                 .to_lowercase();
 
             if path.is_dir() {
-                if is_ignored_dir(&file_name) {
+                // The hardcoded list stays an always-on floor (so `.git`,
+                // `target`, etc. are excluded even from a repo with no
+                // ignore files at all); `.gitignore`/`.nikoignore` patterns
+                // layer additional exclusions on top.
+                if is_ignored_dir(&file_name) || path_is_ignored(&patterns, &path, true) {
                     continue;
                 }
-                stack.push(path);
+                let mut child_patterns = patterns.clone();
+                child_patterns.extend(load_ignore_patterns(&path));
+                stack.push((path, child_patterns));
                 continue;
             }
 
@@ -232,6 +1037,11 @@ fn collect_candidates(
                 continue;
             }
 
+            if path_is_ignored(&patterns, &path, false) {
+                *skipped_files += 1;
+                continue;
+            }
+
             let metadata = match fs::metadata(&path) {
                 Ok(m) => m,
                 Err(_) => {
@@ -273,6 +1083,33 @@ fn collect_candidates(
     out
 }
 
+/// Frecency weight for a file, borrowed from navigation-tool "frecency"
+/// models: recency of `last_access_unix` scales `access_count` so files
+/// touched often *and* recently outrank ones touched often but long ago.
+/// Zero for never-accessed files, leaving their `retrieve` score unboosted.
+fn frecency_multiplier(access_count: u32, last_access_unix: u64) -> f64 {
+    if access_count == 0 {
+        return 0.0;
+    }
+
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 24 * HOUR;
+    const WEEK: u64 = 7 * DAY;
+
+    let age_secs = unix_now().saturating_sub(last_access_unix);
+    let recency = if age_secs <= HOUR {
+        4.0
+    } else if age_secs <= DAY {
+        2.0
+    } else if age_secs <= WEEK {
+        0.5
+    } else {
+        0.25
+    };
+
+    recency * access_count as f64
+}
+
 fn unix_now() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -280,7 +1117,9 @@ fn unix_now() -> u64 {
         .unwrap_or(0)
 }
 
-fn is_ignored_dir(name: &str) -> bool {
+/// Exposed so the filesystem watcher can drop events under these directories
+/// without waiting for a full re-index to discard them.
+pub(crate) fn is_ignored_dir(name: &str) -> bool {
     matches!(
         name,
         ".git"
@@ -337,8 +1176,12 @@ fn looks_like_text_file(path: &Path) -> bool {
     )
 }
 
-fn extract_terms(input: &str) -> HashSet<String> {
-    let mut terms = HashSet::new();
+/// Splits `input` into lowercased alphanumeric/underscore runs of at least 3
+/// characters — the shared tokenizer behind both `extract_terms` (unique
+/// terms, for queries) and `extract_term_counts` (term frequencies, for the
+/// BM25 index).
+fn tokenize(input: &str) -> Vec<String> {
+    let mut terms = Vec::new();
     let mut buf = String::new();
 
     for ch in input.chars() {
@@ -346,15 +1189,186 @@ fn extract_terms(input: &str) -> HashSet<String> {
             buf.push(ch.to_ascii_lowercase());
         } else if !buf.is_empty() {
             if buf.len() >= 3 {
-                terms.insert(buf.clone());
+                terms.push(buf.clone());
             }
             buf.clear();
         }
     }
 
     if !buf.is_empty() && buf.len() >= 3 {
-        terms.insert(buf);
+        terms.push(buf);
     }
 
     terms
 }
+
+fn extract_terms(input: &str) -> HashSet<String> {
+    tokenize(input).into_iter().collect()
+}
+
+/// Term → occurrence count over `input`, the BM25 term-frequency `f(t,d)`
+/// input for a single document.
+fn extract_term_counts(input: &str) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+    for term in tokenize(input) {
+        *counts.entry(term).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_entry(path: &str, content: &str) -> IndexedFile {
+        let terms = extract_term_counts(content);
+        let doc_len = terms.values().sum::<u32>() as usize;
+        IndexedFile {
+            path: path.to_string(),
+            content: content.to_string(),
+            terms,
+            doc_len,
+            modified_unix: 0,
+            size: content.len() as u64,
+            content_hash: 0,
+            access_count: 0,
+            last_access_unix: 0,
+        }
+    }
+
+    fn make_index(entries: Vec<IndexedFile>) -> WorkspaceIndex {
+        let (avgdl, doc_freq) = corpus_stats(&entries);
+        WorkspaceIndex {
+            root: PathBuf::from("."),
+            entries,
+            chunks: Vec::new(),
+            indexed_files: 0,
+            skipped_files: 0,
+            reused_files: 0,
+            reindexed_files: 0,
+            built_unix: 0,
+            avgdl,
+            doc_freq,
+            pruned_deleted: 0,
+            pruned_stale: 0,
+        }
+    }
+
+    #[test]
+    fn test_bm25_score_rewards_higher_term_frequency() {
+        let index = make_index(vec![
+            make_entry("a.rs", "parser parser parser token"),
+            make_entry("b.rs", "parser token"),
+            make_entry("c.rs", "unrelated unrelated words here"),
+        ]);
+        let query: HashSet<String> = extract_terms("parser");
+
+        let high_tf = index.bm25_score(&query, &index.entries[0]);
+        let low_tf = index.bm25_score(&query, &index.entries[1]);
+        assert!(high_tf > low_tf, "higher term frequency should score higher: {} vs {}", high_tf, low_tf);
+    }
+
+    #[test]
+    fn test_bm25_score_rewards_rarer_terms() {
+        // "common" appears in every document (no IDF signal); "rare" appears
+        // in only one, so it should carry more weight for the doc it's in.
+        let index = make_index(vec![
+            make_entry("a.rs", "common rare"),
+            make_entry("b.rs", "common other"),
+            make_entry("c.rs", "common other"),
+        ]);
+
+        let common_only: HashSet<String> = extract_terms("common");
+        let rare_only: HashSet<String> = extract_terms("rare");
+
+        let common_score = index.bm25_score(&common_only, &index.entries[0]);
+        let rare_score = index.bm25_score(&rare_only, &index.entries[0]);
+        assert!(rare_score > common_score, "rarer term should score higher: {} vs {}", rare_score, common_score);
+    }
+
+    #[test]
+    fn test_bm25_score_zero_for_absent_terms() {
+        let index = make_index(vec![
+            make_entry("a.rs", "alpha beta"),
+            make_entry("b.rs", "gamma delta"),
+        ]);
+        let query: HashSet<String> = extract_terms("nonexistent");
+        assert_eq!(index.bm25_score(&query, &index.entries[0]), 0.0);
+    }
+
+    #[test]
+    fn test_glob_match_basics() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "main.toml"));
+        assert!(glob_match("test_?.rs", "test_1.rs"));
+        assert!(!glob_match("test_?.rs", "test_12.rs"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("exact", "exacter"));
+    }
+
+    #[test]
+    fn test_parse_ignore_line_negation_dir_only_anchored() {
+        let negated = parse_ignore_line("!keep.rs").unwrap();
+        assert!(negated.negate);
+        assert_eq!(negated.glob, "keep.rs");
+
+        let dir_only = parse_ignore_line("target/").unwrap();
+        assert!(dir_only.dir_only);
+        assert_eq!(dir_only.glob, "target");
+
+        let anchored = parse_ignore_line("/build").unwrap();
+        assert!(anchored.anchored);
+        assert_eq!(anchored.glob, "build");
+
+        let nested_anchored = parse_ignore_line("src/generated").unwrap();
+        assert!(nested_anchored.anchored);
+
+        let basename = parse_ignore_line("*.log").unwrap();
+        assert!(!basename.anchored);
+
+        assert!(parse_ignore_line("# comment").is_none());
+        assert!(parse_ignore_line("").is_none());
+    }
+
+    #[test]
+    fn test_path_is_ignored_basename_pattern_matches_any_depth() {
+        let root = PathBuf::from("/repo");
+        let patterns = vec![(root.clone(), parse_ignore_line("*.log").unwrap())];
+
+        assert!(path_is_ignored(&patterns, &root.join("deep/nested/debug.log"), false));
+        assert!(!path_is_ignored(&patterns, &root.join("deep/nested/debug.txt"), false));
+    }
+
+    #[test]
+    fn test_path_is_ignored_anchored_pattern_only_matches_from_its_directory() {
+        let root = PathBuf::from("/repo");
+        let patterns = vec![(root.clone(), parse_ignore_line("/build").unwrap())];
+
+        assert!(path_is_ignored(&patterns, &root.join("build"), true));
+        // An anchored pattern shouldn't match a same-named dir elsewhere.
+        assert!(!path_is_ignored(&patterns, &root.join("nested/build"), true));
+    }
+
+    #[test]
+    fn test_path_is_ignored_dir_only_pattern_spares_files() {
+        let root = PathBuf::from("/repo");
+        let patterns = vec![(root.clone(), parse_ignore_line("target/").unwrap())];
+
+        assert!(path_is_ignored(&patterns, &root.join("target"), true));
+        assert!(!path_is_ignored(&patterns, &root.join("target"), false));
+    }
+
+    #[test]
+    fn test_path_is_ignored_negation_precedence() {
+        let root = PathBuf::from("/repo");
+        // Later patterns override earlier ones, and `!` re-includes a path
+        // an earlier pattern excluded — standard gitignore precedence.
+        let patterns = vec![
+            (root.clone(), parse_ignore_line("*.log").unwrap()),
+            (root.clone(), parse_ignore_line("!keep.log").unwrap()),
+        ];
+
+        assert!(path_is_ignored(&patterns, &root.join("debug.log"), false));
+        assert!(!path_is_ignored(&patterns, &root.join("keep.log"), false));
+    }
+}
@@ -1,13 +1,79 @@
 use std::io::{self, BufRead, IsTerminal, Read, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 use crossterm::terminal;
 
 use colored::*;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 
 use unicode_width::UnicodeWidthStr;
 
+// ─── Color policy ────────────────────────────────────────────────────────────
+
+/// User-requested color behavior for the whole process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Colorize when the output looks like a terminal and the user hasn't
+    /// opted out via `NO_COLOR`.
+    Auto,
+    /// Always colorize, even when piped.
+    Always,
+    /// Never colorize.
+    Never,
+}
+
+impl std::str::FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!(
+                "invalid color mode '{}' (expected auto, always, or never)",
+                other
+            )),
+        }
+    }
+}
+
+static COLOR_ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Resolve `choice` against the `NO_COLOR` / `CLICOLOR_FORCE` env vars and
+/// terminal detection, then apply it process-wide via
+/// `colored::control::set_override` so every `.red()`/`.dimmed()`/etc call
+/// in the codebase picks it up automatically. Call once at startup, before
+/// any other `ui::` function runs.
+pub fn init_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                false
+            } else if std::env::var_os("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+                true
+            } else {
+                io::stdout().is_terminal() || io::stderr().is_terminal()
+            }
+        }
+    };
+    colored::control::set_override(enabled);
+    let _ = COLOR_ENABLED.set(enabled);
+}
+
+/// Whether colored output is currently enabled (see `init_color`). Defaults
+/// to `true` if `init_color` was never called (e.g. in contexts that reuse
+/// these helpers without going through `main`).
+fn color_enabled() -> bool {
+    *COLOR_ENABLED.get().unwrap_or(&true)
+}
+
 // ─── Box-drawing constants (Claude Code-inspired) ───────────────────────────
 
 const BOX_TL: &str = "╭";
@@ -145,6 +211,108 @@ pub fn box_line(content: &str) {
     );
 }
 
+/// Draw one or more boxed lines for `content`, word-wrapping at
+/// `max_content_width` instead of truncating — unlike `box_line`, nothing is
+/// lost for long explanation paragraphs or generated commands. Wrapping is
+/// greedy first-fit (pack words until the next one would overflow, then
+/// break); an ANSI style left active across a break is closed at the end of
+/// one line and reopened at the start of the next so color doesn't bleed or
+/// get cut mid-escape.
+pub fn box_paragraph(content: &str) {
+    let width = get_box_width();
+    let sanitized = sanitize_text(content);
+    let max_content_width = width.saturating_sub(2);
+    for line in wrap_ansi_aware(&sanitized, max_content_width) {
+        box_line(&line);
+    }
+}
+
+/// Greedy first-fit word wrap that stays ANSI-aware: words are measured via
+/// `strip_ansi_len`, a word wider than `max_width` on its own is truncated
+/// with `truncate_ansi`, and the last non-reset escape seen is carried across
+/// line breaks.
+fn wrap_ansi_aware(content: &str, max_width: usize) -> Vec<String> {
+    if max_width == 0 {
+        return vec![content.to_string()];
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0usize;
+    let mut active_style: Option<String> = None;
+
+    let close_line = |line: &str, active_style: &Option<String>| -> String {
+        if active_style.is_some() && !line.ends_with("\x1b[0m") {
+            format!("{}\x1b[0m", line)
+        } else {
+            line.to_string()
+        }
+    };
+
+    for word in content.split_whitespace() {
+        let word_width = strip_ansi_len(word);
+
+        if word_width > max_width {
+            if !current.is_empty() {
+                lines.push(close_line(&current, &active_style));
+                current.clear();
+                current_width = 0;
+            }
+            let truncated = truncate_ansi(word, max_width);
+            lines.push(close_line(&truncated, &active_style));
+            if let Some(esc) = last_ansi_escape(word) {
+                active_style = if esc == "\x1b[0m" { None } else { Some(esc) };
+            }
+            continue;
+        }
+
+        let sep_width = if current.is_empty() { 0 } else { 1 };
+        if current_width + sep_width + word_width > max_width {
+            lines.push(close_line(&current, &active_style));
+            current.clear();
+            current_width = 0;
+            if let Some(style) = &active_style {
+                current.push_str(style);
+            }
+        } else if !current.is_empty() {
+            current.push(' ');
+            current_width += 1;
+        }
+
+        current.push_str(word);
+        current_width += word_width;
+
+        if let Some(esc) = last_ansi_escape(word) {
+            active_style = if esc == "\x1b[0m" { None } else { Some(esc) };
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push(close_line(&current, &active_style));
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// The last ANSI escape sequence (e.g. `\x1b[1m`) found in `s`, if any.
+fn last_ansi_escape(s: &str) -> Option<String> {
+    let mut last = None;
+    let mut in_escape = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '\x1b' {
+            in_escape = true;
+            start = i;
+        } else if in_escape && c == 'm' {
+            last = Some(s[start..=i].to_string());
+            in_escape = false;
+        }
+    }
+    last
+}
+
 /// Draw a key-value line inside a box
 pub fn box_kv(key: &str, value: &str) {
     let key = sanitize_text(key);
@@ -351,14 +519,75 @@ pub fn read_stdin_input() -> io::Result<String> {
 
 /// Show a compact code preview with "[N lines] — press Enter to expand"
 /// Returns true if the user chose to expand
-pub fn show_code_preview(code: &str) -> bool {
+static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+
+/// Syntax-highlight `code` into one ANSI-styled string per line. `lang` is a
+/// hint — a file extension (e.g. "rs", "py") or syntect token name — used to
+/// pick the syntax definition; when absent, the highlighter falls back to
+/// sniffing the first line (shebangs, `<?php`, etc). Degrades to the plain
+/// input lines, unstyled, when `lang` doesn't resolve to a known syntax or
+/// stdout isn't a terminal, so callers can apply their own fallback styling.
+pub fn highlight_code(code: &str, lang: Option<&str>) -> Vec<String> {
+    let plain: Vec<String> = code.lines().map(|l| l.to_string()).collect();
+    if !io::stdout().is_terminal() {
+        return plain;
+    }
+
+    let syntax_set = SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines);
+    let syntax = lang
+        .and_then(|l| {
+            syntax_set
+                .find_syntax_by_extension(l)
+                .or_else(|| syntax_set.find_syntax_by_token(l))
+        })
+        .or_else(|| syntax_set.find_syntax_by_first_line(code));
+
+    let Some(syntax) = syntax else {
+        return plain;
+    };
+
+    let theme_set = THEME_SET.get_or_init(ThemeSet::load_defaults);
+    let theme = &theme_set.themes["base16-ocean.dark"];
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    let mut out = Vec::with_capacity(plain.len());
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, syntax_set) {
+            Ok(ranges) => {
+                let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
+                out.push(escaped.trim_end_matches('\n').to_string());
+            }
+            Err(_) => return plain,
+        }
+    }
+    out
+}
+
+/// Show a collapsible preview of `code`, prompting to expand if it's long.
+/// `lang` is passed through to `highlight_code` as a language hint (e.g. the
+/// source file's extension); pass `None` when the language is unknown.
+/// Returns `true` if the user chose to expand the full listing.
+pub fn show_code_preview(code: &str, lang: Option<&str>) -> bool {
     let lines: Vec<&str> = code.lines().collect();
+    let highlighted = highlight_code(code, lang);
     let count = lines.len();
+
+    // Fall back to the old plain-dimmed look for any line `highlight_code`
+    // didn't actually style (unknown language, non-terminal output, etc).
+    let styled = |i: usize, emphasize: bool| -> String {
+        match highlighted.get(i) {
+            Some(hl) if hl != lines[i] => hl.clone(),
+            _ if emphasize => lines[i].white().to_string(),
+            _ => lines[i].dimmed().to_string(),
+        }
+    };
+
     if count <= 5 {
         // Very short code - show it all
         box_top(&format!("{}", format!("Code ({} lines)", count).dimmed()));
-        for line in &lines {
-            box_line(&line.dimmed().to_string());
+        for i in 0..count {
+            box_line(&styled(i, false));
         }
         box_bottom();
         return false;
@@ -366,7 +595,7 @@ pub fn show_code_preview(code: &str) -> bool {
 
     // Default: collapse for anything > 5 lines
     box_top(&format!("{}", format!("Code ({} lines)", count).dimmed()));
-    box_line(&lines[0].white().to_string());
+    box_line(&styled(0, true));
     if count > 2 {
         box_line(&"".to_string());
         box_line(&format!(
@@ -376,7 +605,7 @@ pub fn show_code_preview(code: &str) -> bool {
         box_line(&"".to_string());
     }
     if count > 1 {
-        box_line(&lines[count - 1].white().to_string());
+        box_line(&styled(count - 1, true));
     }
     box_bottom();
 
@@ -395,9 +624,9 @@ pub fn show_code_preview(code: &str) -> bool {
             "{}",
             format!("Code ({} lines) — expanded", count).dimmed()
         ));
-        for (i, line) in lines.iter().enumerate() {
+        for i in 0..count {
             let line_num = format!("{:>4}", i + 1).dimmed();
-            box_line(&format!("{} {}", line_num, line.dimmed()));
+            box_line(&format!("{} {}", line_num, styled(i, false)));
         }
         box_bottom();
         return true;
@@ -439,6 +668,88 @@ pub fn render_markdown(text: &str) -> String {
     result
 }
 
+/// Render a block of markdown-lite text into display-ready lines: ATX
+/// headings, ordered/unordered lists, blockquotes, and fenced code blocks
+/// (syntax-highlighted via `highlight_code`, indented as a block) are all
+/// recognized, with everything else falling back to `render_markdown`'s
+/// inline styling. Shared by every `display_explanation` section so they
+/// stay in sync instead of re-implementing the same per-branch parsing.
+pub fn render_markdown_block(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut in_code_block = false;
+    let mut code_lang: Option<String> = None;
+    let mut code_buf: Vec<String> = Vec::new();
+
+    let flush_code_block = |out: &mut Vec<String>, buf: &[String], lang: Option<&str>| {
+        if buf.is_empty() {
+            return;
+        }
+        for hl in highlight_code(&buf.join("\n"), lang) {
+            out.push(format!("    {}", hl));
+        }
+    };
+
+    for raw in text.lines() {
+        let line = raw.trim_end();
+        let trimmed = line.trim();
+
+        if let Some(fence_lang) = trimmed.strip_prefix("```") {
+            if in_code_block {
+                flush_code_block(&mut out, &code_buf, code_lang.as_deref());
+                code_buf.clear();
+                code_lang = None;
+                in_code_block = false;
+            } else {
+                in_code_block = true;
+                code_lang = Some(fence_lang.trim().to_string()).filter(|s| !s.is_empty());
+            }
+            continue;
+        }
+
+        if in_code_block {
+            code_buf.push(line.to_string());
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            out.push(String::new());
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix('#') {
+            let heading = rest.trim_start_matches('#').trim();
+            out.push(format!("  {}", render_markdown(heading).bold().magenta()));
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("> ") {
+            out.push(format!("  {} {}", "▏".dimmed(), render_markdown(rest).italic()));
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            out.push(format!("  {} {}", "•".cyan(), render_markdown(rest)));
+            continue;
+        }
+
+        if let Some(dot) = trimmed.find(". ") {
+            let marker = &trimmed[..dot];
+            if !marker.is_empty() && marker.chars().all(|c| c.is_ascii_digit()) {
+                let rest = &trimmed[dot + 2..];
+                out.push(format!("    {} {}", format!("{}.", marker).cyan(), render_markdown(rest)));
+                continue;
+            }
+        }
+
+        out.push(format!("  {}", render_markdown(trimmed)));
+    }
+
+    // An unterminated fence shouldn't silently swallow whatever was buffered
+    flush_code_block(&mut out, &code_buf, code_lang.as_deref());
+
+    out
+}
+
 // ─── Explanation display (Claude Code/OpenCode-inspired) ─────────────────────
 
 /// Display a formatted explanation result with a borderless, minimalist aesthetic
@@ -467,19 +778,8 @@ pub fn display_explanation(result: &crate::chunker::ExplainResult) {
     if result.total_chunks > 1 {
         println!("  {}", "Overview".bold().cyan());
         println!("  {}", "────────".dimmed().cyan());
-        for line in result.overall_summary.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                println!();
-                continue;
-            }
-            if line.starts_with("#") {
-                println!("  {}", render_markdown(line.trim_start_matches('#').trim()).bold().magenta());
-            } else if line.starts_with("- ") || line.starts_with("* ") {
-                println!("  {} {}", "•".cyan(), render_markdown(&line[2..]));
-            } else {
-                println!("  {}", render_markdown(line));
-            }
+        for line in render_markdown_block(&result.overall_summary) {
+            println!("{}", line);
         }
         println!();
 
@@ -491,35 +791,15 @@ pub fn display_explanation(result: &crate::chunker::ExplainResult) {
             let chunk_title = format!("Lines {}-{}", chunk.start_line, chunk.end_line);
             println!("  {} {}", "󰚗".magenta(), chunk_title.bold());
             
-            for line in chunk.explanation.lines() {
-                let line = line.trim();
-                if line.is_empty() {
-                    println!();
-                } else if line.starts_with("### ") || line.starts_with("## ") {
-                    println!("  {}", render_markdown(line.trim_start_matches('#').trim()).bold().magenta());
-                } else if line.starts_with("- ") || line.starts_with("* ") {
-                    println!("  {} {}", "•".cyan(), render_markdown(&line[2..]));
-                } else {
-                    println!("  {}", render_markdown(line));
-                }
+            for line in render_markdown_block(&chunk.explanation) {
+                println!("{}", line);
             }
         }
     } else {
         println!("  {}", "Analysis".bold().cyan());
         println!("  {}", "────────".dimmed().cyan());
-        for line in result.overall_summary.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                println!();
-                continue;
-            }
-            if line.starts_with("## ") || line.starts_with("### ") {
-                println!("\n  {}", render_markdown(line.trim_start_matches('#').trim()).bold().magenta());
-            } else if line.starts_with("- ") || line.starts_with("* ") {
-                println!("  {} {}", "•".cyan(), render_markdown(&line[2..]));
-            } else {
-                println!("  {}", render_markdown(line));
-            }
+        for line in render_markdown_block(&result.overall_summary) {
+            println!("{}", line);
         }
     }
     println!();
@@ -529,16 +809,39 @@ pub fn display_explanation(result: &crate::chunker::ExplainResult) {
         print_rule();
         println!("  {}", "Follow-up Questions".bold().cyan());
         for (i, q) in result.follow_up_questions.iter().enumerate() {
-            let cat_end = q.find(']').unwrap_or(0);
-            let (cat, text) = if cat_end > 0 {
-                (format!("{} ", &q[..=cat_end].cyan()), &q[cat_end+1..])
-            } else {
-                (String::new(), q.as_str())
-            };
-            println!("  {} {}{}", format!("{}.", i + 1).dimmed(), cat, render_markdown(text));
+            let cat = format!("[{}] ", q.category).cyan();
+            println!("  {} {}{}", format!("{}.", i + 1).dimmed(), cat, render_markdown(&q.question));
         }
         eprintln!();
     }
+
+    // Token usage / estimated cost - only shown when the provider actually
+    // reported usage, since most local providers don't.
+    if result.usage.input_tokens > 0 || result.usage.output_tokens > 0 {
+        let cost = crate::chunker::estimate_cost_usd(&result.provider_name, result.usage);
+        let cost_str = if cost > 0.0 {
+            format!("  •  est. ${:.4}", cost)
+        } else {
+            String::new()
+        };
+        let cache_str = if result.usage.cache_read_input_tokens > 0 {
+            format!(
+                "  •  {} cached",
+                result.usage.cache_read_input_tokens.to_string().green()
+            )
+        } else {
+            String::new()
+        };
+        println!(
+            "  {}",
+            format!(
+                "{} in / {} out tokens{}{}",
+                result.usage.input_tokens, result.usage.output_tokens, cache_str, cost_str
+            )
+            .dimmed()
+        );
+        eprintln!();
+    }
 }
 
 // ─── Command output (Claude Code-inspired) ──────────────────────────────────
@@ -576,6 +879,11 @@ pub fn prompt_input(prompt: &str) -> io::Result<String> {
 
 /// Get the display length of a string, stripping ANSI escape codes
 fn strip_ansi_len(s: &str) -> usize {
+    if !color_enabled() {
+        // Color is off process-wide, so `s` can't contain ANSI escapes —
+        // skip the char-by-char scan below.
+        return UnicodeWidthStr::width(s);
+    }
     let mut plain = String::new();
     let mut in_escape = false;
     for c in s.chars() {